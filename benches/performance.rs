@@ -1,27 +1,23 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use log_watcher::cli::Args;
 use log_watcher::config::Config;
+use log_watcher::highlighter::HighlightMode;
 use log_watcher::matcher::Matcher;
 use std::path::PathBuf;
 
 fn create_test_config(patterns: &str, regex: bool) -> Config {
+    create_test_config_with_case(patterns, regex, false)
+}
+
+fn create_test_config_with_case(patterns: &str, regex: bool, case_insensitive: bool) -> Config {
     let args = Args {
         files: vec![PathBuf::from("test.log")],
-        completions: None,
         patterns: patterns.to_string(),
         regex,
-        case_insensitive: false,
-        color_map: None,
+        case_insensitive,
         notify: false,
-        notify_patterns: None,
-        notify_throttle: 5,
-        dry_run: false,
-        quiet: false,
-        exclude: None,
         no_color: true,
-        prefix_file: None,
-        poll_interval: 100,
-        buffer_size: 8192,
+        ..Default::default()
     };
     Config::from_args(&args).unwrap()
 }
@@ -71,9 +67,7 @@ fn benchmark_regex_matching(c: &mut Criterion) {
 }
 
 fn benchmark_case_insensitive_matching(c: &mut Criterion) {
-    let config = create_test_config("ERROR,WARN,INFO", false);
-    let mut config = config;
-    config.case_insensitive = true;
+    let config = create_test_config_with_case("ERROR,WARN,INFO", false, true);
     let matcher = Matcher::new(config);
 
     let test_lines = vec![
@@ -135,12 +129,121 @@ fn benchmark_long_line_matching(c: &mut Criterion) {
     });
 }
 
+/// Synthetic workload: `count` distinct patterns and a mix of lines where most
+/// don't match, as in a busy production log
+fn many_patterns_workload(count: usize, regex: bool) -> (String, Vec<String>) {
+    let patterns: Vec<String> = (0..count)
+        .map(|i| {
+            if regex {
+                format!(r"E{:04}: \w+ failed", i)
+            } else {
+                format!("E{:04}", i)
+            }
+        })
+        .collect();
+
+    let lines = (0..100)
+        .map(|i| {
+            if i % 10 == 0 {
+                format!(
+                    "req={} E{:04}: upstream failed after retry",
+                    i,
+                    (i * 7) % count
+                )
+            } else {
+                format!("req={} GET /api/v1/items status=200 took={}ms", i, i * 3)
+            }
+        })
+        .collect();
+
+    (patterns.join(","), lines)
+}
+
+fn benchmark_many_patterns(c: &mut Criterion, name: &str, regex: bool, case_insensitive: bool) {
+    let (patterns, lines) = many_patterns_workload(300, regex);
+    let config = create_test_config_with_case(&patterns, regex, case_insensitive);
+    let rules = config.rules.clone();
+    let matcher = Matcher::new(config);
+
+    let mut group = c.benchmark_group(name);
+    group.bench_function("single_pass", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(matcher.match_line(line));
+            }
+        })
+    });
+    // Baseline: check each rule in turn, as the matcher did before
+    group.bench_function("per_rule", |b| {
+        b.iter(|| {
+            for line in &lines {
+                let lowered = case_insensitive.then(|| line.to_lowercase());
+                black_box(
+                    rules
+                        .iter()
+                        .find(|rule| rule.is_match(line, lowered.as_deref())),
+                );
+            }
+        })
+    });
+    group.finish();
+}
+
+fn benchmark_many_literal_patterns(c: &mut Criterion) {
+    benchmark_many_patterns(c, "many_literal_patterns", false, false);
+}
+
+fn benchmark_many_case_insensitive_patterns(c: &mut Criterion) {
+    benchmark_many_patterns(c, "many_case_insensitive_patterns", false, true);
+}
+
+fn benchmark_many_regex_patterns(c: &mut Criterion) {
+    benchmark_many_patterns(c, "many_regex_patterns", true, false);
+}
+
+/// Every line matches: in line mode nothing beyond the single pass should
+/// run, while span mode also locates each match
+fn benchmark_matched_lines(c: &mut Criterion) {
+    let (patterns, _) = many_patterns_workload(300, true);
+    let lines: Vec<String> = (0..100)
+        .map(|i| {
+            format!(
+                "req={} E{:04}: upstream failed after retry",
+                i,
+                (i * 7) % 300
+            )
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("matched_lines");
+    for (name, mode) in [
+        ("line_mode", HighlightMode::Line),
+        ("span_mode", HighlightMode::Span),
+    ] {
+        let mut config = create_test_config(&patterns, true);
+        config.highlight_mode = mode;
+        let matcher = Matcher::new(config);
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for line in &lines {
+                    black_box(matcher.match_line(line));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
     benchmark_literal_matching,
     benchmark_regex_matching,
     benchmark_case_insensitive_matching,
     benchmark_multiple_patterns,
-    benchmark_long_line_matching
+    benchmark_long_line_matching,
+    benchmark_many_literal_patterns,
+    benchmark_many_case_insensitive_patterns,
+    benchmark_many_regex_patterns,
+    benchmark_matched_lines
 );
 criterion_main!(benches);
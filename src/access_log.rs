@@ -0,0 +1,227 @@
+//! Web server access logs: Common and Combined Log Format, and custom nginx
+//! `log_format` strings.
+//!
+//! ```text
+//! 127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326 "-" "curl/8.0"
+//! ```
+//!
+//! Fields are named after the nginx variables (`remote_addr`, `status`,
+//! `request_time`, ...). `request` is also split into `method`, `path` and
+//! `protocol`, and `status` gets a `status_class` (`5xx`). Numeric values are
+//! decoded as numbers and `-` values are left out.
+
+use anyhow::Result;
+use regex::Regex;
+use serde_json::{Map, Number, Value};
+
+const COMMON: &str =
+    r#"$remote_addr $remote_ident $remote_user [$time_local] "$request" $status $body_bytes_sent"#;
+
+const COMBINED: &str = r#"$remote_addr $remote_ident $remote_user [$time_local] "$request" $status $body_bytes_sent "$http_referer" "$http_user_agent""#;
+
+/// A compiled access log format
+#[derive(Debug, Clone)]
+pub struct AccessLogFormat {
+    regex: Regex,
+    /// Variable name for each capture group
+    fields: Vec<String>,
+}
+
+impl AccessLogFormat {
+    /// Compile a format: `common`, `combined`, or an nginx `log_format`
+    /// string such as `'$remote_addr [$time_local] "$request" $status $request_time'`
+    pub fn parse(format: &str) -> Result<Self> {
+        match format {
+            "common" => Self::compile(COMMON),
+            "combined" => Self::compile(COMBINED),
+            custom => Self::compile(custom),
+        }
+    }
+
+    pub fn common() -> Self {
+        Self::compile(COMMON).expect("built-in format compiles")
+    }
+
+    pub fn combined() -> Self {
+        Self::compile(COMBINED).expect("built-in format compiles")
+    }
+
+    fn compile(format: &str) -> Result<Self> {
+        let mut pattern = String::from("^");
+        let mut fields = Vec::new();
+        let mut rest = format;
+
+        while let Some(start) = rest.find('$') {
+            pattern.push_str(&regex::escape(&rest[..start]));
+            let after = &rest[start + 1..];
+
+            let (name, tail) = match after.strip_prefix('{') {
+                Some(braced) => {
+                    let end = braced.find('}').ok_or_else(|| {
+                        anyhow::anyhow!("Unterminated ${{...}} in log format: {}", format)
+                    })?;
+                    (&braced[..end], &braced[end + 1..])
+                }
+                None => {
+                    let end = after
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                        .unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            if name.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Expected a variable name after '$' in log format: {}",
+                    format
+                ));
+            }
+
+            // Quoted and bracketed variables may contain spaces; bare ones
+            // end at the next space
+            if pattern.ends_with(['"', '[']) {
+                pattern.push_str("(.*?)");
+            } else {
+                pattern.push_str(r"(\S*?)");
+            }
+            fields.push(name.to_string());
+            rest = tail;
+        }
+
+        if fields.is_empty() {
+            return Err(anyhow::anyhow!("Log format has no $variables: {}", format));
+        }
+        pattern.push_str(&regex::escape(rest));
+        pattern.push('$');
+
+        Ok(Self {
+            regex: Regex::new(&pattern)?,
+            fields,
+        })
+    }
+
+    /// Decode a line, or `None` if it doesn't follow the format
+    pub fn decode(&self, line: &str) -> Option<Map<String, Value>> {
+        let captures = self.regex.captures(line)?;
+        let mut fields = Map::new();
+
+        for (name, capture) in self.fields.iter().zip(captures.iter().skip(1)) {
+            let text = capture.map_or("", |m| m.as_str());
+            if text.is_empty() || text == "-" || fields.contains_key(name) {
+                continue;
+            }
+            fields.insert(name.clone(), typed_value(text));
+        }
+
+        if let Some(request) = fields.get("request").and_then(Value::as_str) {
+            let request = request.to_string();
+            for (key, part) in ["method", "path", "protocol"]
+                .into_iter()
+                .zip(request.splitn(3, ' '))
+            {
+                fields.entry(key).or_insert_with(|| Value::from(part));
+            }
+        }
+        if let Some(status) = fields.get("status").and_then(Value::as_u64) {
+            fields.insert(
+                "status_class".to_string(),
+                Value::String(format!("{}xx", status / 100)),
+            );
+        }
+
+        Some(fields)
+    }
+}
+
+/// Integers and decimals become numbers; everything else stays text
+fn typed_value(text: &str) -> Value {
+    let numeric = !text.is_empty()
+        && text.bytes().all(|b| b.is_ascii_digit() || b == b'.')
+        && text.bytes().filter(|&b| b == b'.').count() <= 1
+        && !text.starts_with('.')
+        && !text.ends_with('.');
+    if numeric {
+        if let Ok(number) = text.parse::<Number>() {
+            return Value::Number(number);
+        }
+    }
+    Value::String(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_combined_format() {
+        let fields = AccessLogFormat::combined()
+            .decode(r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 503 2326 "http://example.com/" "Mozilla/4.08 [en] (Win98)""#)
+            .unwrap();
+
+        assert_eq!(fields["remote_addr"], json!("127.0.0.1"));
+        assert!(!fields.contains_key("remote_ident"));
+        assert_eq!(fields["remote_user"], json!("frank"));
+        assert_eq!(fields["time_local"], json!("10/Oct/2000:13:55:36 -0700"));
+        assert_eq!(fields["method"], json!("GET"));
+        assert_eq!(fields["path"], json!("/apache_pb.gif"));
+        assert_eq!(fields["protocol"], json!("HTTP/1.0"));
+        assert_eq!(fields["status"], json!(503));
+        assert_eq!(fields["status_class"], json!("5xx"));
+        assert_eq!(fields["body_bytes_sent"], json!(2326));
+        assert_eq!(
+            fields["http_user_agent"],
+            json!("Mozilla/4.08 [en] (Win98)")
+        );
+    }
+
+    #[test]
+    fn test_common_format() {
+        let format = AccessLogFormat::parse("common").unwrap();
+        let fields = format
+            .decode(r#"10.0.0.2 - - [10/Oct/2000:13:55:36 -0700] "POST /login HTTP/1.1" 302 -"#)
+            .unwrap();
+        assert_eq!(fields["status"], json!(302));
+        assert!(!fields.contains_key("body_bytes_sent"));
+
+        // A combined line has trailing fields the common format doesn't expect
+        assert!(format
+            .decode(
+                r#"10.0.0.2 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 200 5 "-" "curl""#
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn test_custom_nginx_format() {
+        let format = AccessLogFormat::parse(
+            r#"$remote_addr [$time_local] "$request" $status ${request_time}s $upstream_response_time"#,
+        )
+        .unwrap();
+        let fields = format
+            .decode(
+                r#"10.0.0.9 [10/Oct/2000:13:55:36 -0700] "GET /api/orders HTTP/2.0" 504 2.503s -"#,
+            )
+            .unwrap();
+
+        assert_eq!(fields["request_time"], json!(2.503));
+        assert_eq!(fields["status_class"], json!("5xx"));
+        assert_eq!(fields["path"], json!("/api/orders"));
+        assert!(!fields.contains_key("upstream_response_time"));
+    }
+
+    #[test]
+    fn test_invalid_formats() {
+        assert!(AccessLogFormat::parse("no variables").is_err());
+        assert!(AccessLogFormat::parse("$ alone").is_err());
+        assert!(AccessLogFormat::parse("${unterminated").is_err());
+    }
+
+    #[test]
+    fn test_typed_values() {
+        assert_eq!(typed_value("200"), json!(200));
+        assert_eq!(typed_value("0.25"), json!(0.25));
+        assert_eq!(typed_value("10.0.0.1"), json!("10.0.0.1"));
+        assert_eq!(typed_value("1."), json!("1."));
+        assert_eq!(typed_value("abc"), json!("abc"));
+    }
+}
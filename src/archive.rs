@@ -0,0 +1,226 @@
+//! Reading rotated and compressed logs in dry-run mode. Compressed files
+//! (gzip, zstd, bzip2) are recognized by their magic bytes rather than their
+//! names and decompressed while they are read, and `--include-rotated`
+//! extends a file to its rotation set: `app.log.2.gz`, `app.log.1` and
+//! `app.log`, or date-stamped copies such as `app.log-20261015.gz`.
+
+use anyhow::{Context, Result};
+use std::cmp::Reverse;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Extensions of compressed rotations, stripped before the suffix is read
+const COMPRESSED_EXTENSIONS: &[&str] = &[".gz", ".zst", ".bz2"];
+
+/// Rotation suffixes with fewer digits than this are counters (`.1`, `.2`);
+/// longer ones are dates (`-20261015`)
+const MIN_DATE_DIGITS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// The compression of a file, from its first bytes. The file is left at
+    /// its start.
+    pub fn detect(file: &mut File) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        let mut read = 0;
+        while read < magic.len() {
+            match file.read(&mut magic[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Self::of_magic(&magic[..read]))
+    }
+
+    fn of_magic(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Self::Gzip
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Self::Zstd
+        } else if bytes.starts_with(b"BZh") {
+            Self::Bzip2
+        } else {
+            Self::None
+        }
+    }
+
+    /// A reader of the decompressed contents of `reader`
+    pub fn decoder<R: Read + 'static>(self, reader: R) -> Result<Box<dyn Read>> {
+        Ok(match self {
+            Self::None => Box::new(reader),
+            // logrotate and `cat a.gz b.gz` can produce several members
+            Self::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Self::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            Self::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+        })
+    }
+}
+
+/// How old a rotated copy is, from the suffix after the file's name
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Rotation {
+    /// `.1` is the newest; higher counters are older, so they sort first
+    Counter(Reverse<u64>),
+    /// Dates sort oldest first as written
+    Date(String),
+}
+
+impl Rotation {
+    fn of_suffix(suffix: &str) -> Option<Self> {
+        let suffix = COMPRESSED_EXTENSIONS
+            .iter()
+            .find_map(|extension| suffix.strip_suffix(extension))
+            .unwrap_or(suffix);
+        if suffix.is_empty() || !suffix.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        if suffix.len() < MIN_DATE_DIGITS && suffix.bytes().all(|b| b.is_ascii_digit()) {
+            return suffix.parse().ok().map(|n| Self::Counter(Reverse(n)));
+        }
+        suffix
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'_' | b'.'))
+            .then(|| Self::Date(suffix.to_string()))
+    }
+}
+
+/// The rotated copies of a log file next to it, oldest first, followed by
+/// the file itself
+pub fn rotation_set(path: &Path) -> Result<Vec<PathBuf>> {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut rotated = Vec::new();
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to list rotated files in {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let Some(other) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let rotation = other
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix(['.', '-']))
+            .and_then(Rotation::of_suffix);
+        if let Some(rotation) = rotation {
+            if entry.file_type()?.is_file() {
+                rotated.push((rotation, path.with_file_name(&other)));
+            }
+        }
+    }
+    rotated.sort();
+
+    let mut set: Vec<PathBuf> = rotated.into_iter().map(|(_, path)| path).collect();
+    set.push(path.to_path_buf());
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn read_all(path: &Path) -> String {
+        let mut file = File::open(path).unwrap();
+        let compression = Compression::detect(&mut file).unwrap();
+        let mut text = String::new();
+        compression
+            .decoder(file)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn test_compressed_files_are_detected_by_content() {
+        let dir = TempDir::new().unwrap();
+        let text = "ERROR: archived\n";
+
+        // Named without an extension, so only the magic bytes tell
+        let gzip = dir.path().join("gzip");
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&gzip).unwrap(), Default::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let zstd = dir.path().join("zstd");
+        std::fs::write(&zstd, zstd::encode_all(text.as_bytes(), 0).unwrap()).unwrap();
+
+        let bzip2 = dir.path().join("bzip2");
+        let mut encoder =
+            bzip2::write::BzEncoder::new(File::create(&bzip2).unwrap(), Default::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let plain = dir.path().join("plain.gz");
+        std::fs::write(&plain, text).unwrap();
+
+        for path in [&gzip, &zstd, &bzip2, &plain] {
+            assert_eq!(read_all(path), text, "{}", path.display());
+        }
+        assert_eq!(Compression::of_magic(b""), Compression::None);
+    }
+
+    #[test]
+    fn test_rotation_set_oldest_first() {
+        let dir = TempDir::new().unwrap();
+        for name in [
+            "app.log",
+            "app.log.1",
+            "app.log.2.gz",
+            "app.log.10.zst",
+            "app.log.state",
+            "app.log.bak",
+            "app.log2",
+            "other.log.1",
+        ] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let set = rotation_set(&dir.path().join("app.log")).unwrap();
+        let names: Vec<_> = set
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["app.log.10.zst", "app.log.2.gz", "app.log.1", "app.log"]
+        );
+    }
+
+    #[test]
+    fn test_date_stamped_rotations() {
+        let dir = TempDir::new().unwrap();
+        for name in ["app.log", "app.log-20261016.gz", "app.log-20261015.gz"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let set = rotation_set(&dir.path().join("app.log")).unwrap();
+        assert_eq!(
+            set,
+            vec![
+                dir.path().join("app.log-20261015.gz"),
+                dir.path().join("app.log-20261016.gz"),
+                dir.path().join("app.log"),
+            ]
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use clap::{CommandFactory, Parser};
+use crate::highlighter::{HighlightMode, OutputFormat};
+use crate::output_file::DEFAULT_KEEP;
+use crate::parser::InputFormat;
+use crate::rule::RuleSpec;
+use clap::{ArgAction, CommandFactory, Parser};
 use clap_complete::{generate, Shell};
 use std::io;
 use std::path::PathBuf;
@@ -11,24 +15,87 @@ use std::path::PathBuf;
     long_about = "LogWatcher is a CLI tool for monitoring log files in real-time. It provides pattern highlighting, desktop notifications, and handles file rotation automatically."
 )]
 pub struct Args {
-    /// Path(s) to log file(s) to watch
-    #[arg(short = 'f', long = "file", required_unless_present = "completions", num_args = 1..)]
+    /// Path(s) to log file(s) to watch, or - for standard input (required
+    /// unless --exec is used or files are set in the config file)
+    #[arg(short = 'f', long = "file", num_args = 1..)]
     pub files: Vec<PathBuf>,
 
+    /// Run a shell command and watch its standard output
+    #[arg(long = "exec", value_name = "CMD")]
+    pub exec: Option<String>,
+
     /// Generate shell completions for the specified shell
     #[arg(long = "completions", value_name = "SHELL")]
     pub completions: Option<Shell>,
 
+    /// TOML configuration file (default: $XDG_CONFIG_HOME/logwatcher/config.toml)
+    #[arg(long = "config", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Named profile from the configuration file
+    #[arg(long = "profile", value_name = "NAME")]
+    pub profile: Option<String>,
+
     /// Comma-separated patterns to match
     #[arg(short = 'p', long = "pattern", default_value = "ERROR,WARN")]
     pub patterns: String,
 
     /// Treat patterns as regular expressions
-    #[arg(short = 'r', long = "regex")]
+    #[arg(short = 'r', long = "regex", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
     pub regex: bool,
 
+    /// Field expression matched against structured lines (repeatable),
+    /// e.g. 'status >= 500' or 'msg ~ /timeout/'
+    #[arg(long = "where", value_name = "EXPR")]
+    pub field_rules: Vec<String>,
+
+    /// How lines are decoded before matching (auto when --where is used)
+    #[arg(long = "input-format", value_enum, default_value_t = InputFormat::Raw)]
+    pub input_format: InputFormat,
+
+    /// Access log format: "common", "combined" or an nginx log_format string
+    /// (implies --input-format access)
+    #[arg(long = "log-format", value_name = "FORMAT")]
+    pub log_format: Option<String>,
+
+    /// Count the values of a decoded field in the dry-run summary (repeatable)
+    #[arg(long = "summarize", value_name = "FIELD")]
+    pub summarize: Vec<String>,
+
+    /// Lines matching this regex start a record; other lines are joined to
+    /// the record before them (stack traces and other multiline records)
+    #[arg(long = "multiline-start", value_name = "REGEX")]
+    pub multiline_start: Option<String>,
+
+    /// Join indented lines to the record before them
+    #[arg(long = "multiline-indent", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub multiline_indent: bool,
+
+    /// Milliseconds a multiline record waits for more lines before it is
+    /// matched
+    #[arg(long = "multiline-timeout", value_name = "MS", default_value = "1000")]
+    pub multiline_timeout: u64,
+
+    /// Drop blank and whitespace-only lines instead of passing them through
+    #[arg(long = "skip-blank", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub skip_blank: bool,
+
+    /// Encoding of the watched files and streams, such as "latin1" or
+    /// "utf-16le" (default: UTF-8); invalid bytes are replaced
+    #[arg(long = "encoding", value_name = "LABEL")]
+    pub encoding: Option<String>,
+
+    /// Milliseconds an unfinished last line waits for its newline before it
+    /// is matched as it is
+    #[arg(long = "partial-timeout", value_name = "MS", default_value = "1000")]
+    pub partial_timeout: u64,
+
+    /// Count every matching pattern per line, not just the first
+    #[arg(long = "all-matches", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub all_matches: bool,
+
     /// Case-insensitive pattern matching
-    #[arg(short = 'i', long = "case-insensitive")]
+    #[arg(short = 'i', long = "case-insensitive", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
     pub case_insensitive: bool,
 
     /// Custom pattern:color mappings (e.g., "ERROR:red,WARN:yellow")
@@ -36,7 +103,7 @@ pub struct Args {
     pub color_map: Option<String>,
 
     /// Enable desktop notifications
-    #[arg(short = 'n', long = "notify", default_value = "true")]
+    #[arg(long = "notify", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = true, value_name = "BOOL")]
     pub notify: bool,
 
     /// Specific patterns that trigger notifications (default: all patterns)
@@ -47,12 +114,61 @@ pub struct Args {
     #[arg(long = "notify-throttle", default_value = "5")]
     pub notify_throttle: u32,
 
+    /// Notification title; {pattern}, {file}, {line} and decoded fields
+    /// like {status} are filled in
+    #[arg(long = "notify-title", value_name = "TEMPLATE")]
+    pub notify_title: Option<String>,
+
+    /// Notification body, with the same placeholders as --notify-title
+    #[arg(long = "notify-body", value_name = "TEMPLATE")]
+    pub notify_body: Option<String>,
+
+    /// Start with the last N lines of each file instead of its end (or
+    /// its start in dry-run mode)
+    #[arg(
+        short = 'n',
+        long = "lines",
+        value_name = "N",
+        conflicts_with = "since"
+    )]
+    pub lines: Option<usize>,
+
+    /// Start each file at the first line logged since a time: a duration
+    /// ago like 15m, or a time like 2026-10-17T09:00
+    #[arg(long = "since", value_name = "TIME")]
+    pub since: Option<String>,
+
+    /// Print N lines of context after each match that would otherwise be
+    /// hidden (in quiet and dry-run modes)
+    #[arg(short = 'A', long = "after-context", value_name = "N")]
+    pub after_context: Option<usize>,
+
+    /// Print N lines of context before each match that would otherwise be
+    /// hidden (in quiet and dry-run modes)
+    #[arg(short = 'B', long = "before-context", value_name = "N")]
+    pub before_context: Option<usize>,
+
+    /// Print N lines of context before and after each match; -A and -B
+    /// take precedence
+    #[arg(short = 'C', long = "context", value_name = "N")]
+    pub context: Option<usize>,
+
     /// Preview mode (no tailing, no notifications)
-    #[arg(short = 'd', long = "dry-run")]
+    #[arg(short = 'd', long = "dry-run", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
     pub dry_run: bool,
 
+    /// In dry-run mode, also read each file's rotated copies (app.log.1,
+    /// app.log.2.gz, ...), oldest first
+    #[arg(long = "include-rotated", requires = "dry_run", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub include_rotated: bool,
+
+    /// Full-screen live view with a status bar, per-rule sparklines, pause,
+    /// filters and match navigation
+    #[arg(long = "tui", conflicts_with_all = ["dry_run", "output"], action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub tui: bool,
+
     /// Suppress non-matching lines
-    #[arg(short = 'q', long = "quiet")]
+    #[arg(short = 'q', long = "quiet", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
     pub quiet: bool,
 
     /// Comma-separated patterns to exclude (inverse matching)
@@ -60,20 +176,87 @@ pub struct Args {
     pub exclude: Option<String>,
 
     /// Disable ANSI colors
-    #[arg(long = "no-color")]
+    #[arg(long = "no-color", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
     pub no_color: bool,
 
+    /// Which part of a matching line to color
+    #[arg(long = "highlight-mode", value_enum, default_value_t = HighlightMode::Line)]
+    pub highlight_mode: HighlightMode,
+
+    /// Output format: text, or json (alias ndjson) for one JSON object per
+    /// line and per event
+    #[arg(long = "output", value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Template for printed lines, such as '{time} {file}:{lineno} [{rule}]
+    /// {line}'; placeholders take widths ({file:<20}, {line:.120}) and
+    /// styles ({rule|match|bold})
+    #[arg(long = "format", value_name = "TEMPLATE")]
+    pub format: Option<String>,
+
+    /// Also record matched lines to this file, without colors; it can
+    /// rotate itself by size or age
+    #[arg(long = "output-file", value_name = "FILE")]
+    pub output_file: Option<PathBuf>,
+
+    /// Format of the --output-file records: text or json
+    #[arg(long = "output-file-format", value_enum, default_value_t = OutputFormat::Text)]
+    pub output_file_format: OutputFormat,
+
+    /// Record every line to the --output-file, not only matches
+    #[arg(long = "output-file-all", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub output_file_all: bool,
+
+    /// Rotate the --output-file before it grows past this size, such as
+    /// 10M or 512K
+    #[arg(long = "output-file-max-size", value_name = "SIZE")]
+    pub output_file_max_size: Option<String>,
+
+    /// Rotate the --output-file once it is this old, such as 1h or 1d
+    #[arg(long = "output-file-max-age", value_name = "AGE")]
+    pub output_file_max_age: Option<String>,
+
+    /// Rotated generations of the --output-file to keep (FILE.1, FILE.2,
+    /// ...)
+    #[arg(long = "output-file-keep", value_name = "N", default_value_t = DEFAULT_KEEP)]
+    pub output_file_keep: usize,
+
+    /// Re-render structured lines as aligned, colored key=value fields
+    #[arg(long = "render-fields", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub render_fields: bool,
+
     /// Prefix lines with filename (auto: true for multiple files)
     #[arg(long = "prefix-file")]
     pub prefix_file: Option<bool>,
 
-    /// File polling interval in milliseconds
+    /// Poll files for changes instead of waiting for filesystem events
+    /// (automatic for files on network filesystems)
+    #[arg(long = "poll", action = ArgAction::Set, num_args = 0..=1, require_equals = true, default_missing_value = "true", default_value_t = false, value_name = "BOOL")]
+    pub poll: bool,
+
+    /// Polling interval in milliseconds, for files that are polled
     #[arg(long = "poll-interval", default_value = "100")]
     pub poll_interval: u64,
 
     /// Read buffer size in bytes
     #[arg(long = "buffer-size", default_value = "8192")]
     pub buffer_size: usize,
+
+    /// Save how far each file was read to this file, and resume from there
+    /// on the next start
+    #[arg(long = "state-file", value_name = "FILE")]
+    pub state_file: Option<PathBuf>,
+
+    /// Rules declared in the config file (`[[rules]]` tables)
+    #[arg(skip)]
+    pub rules: Vec<RuleSpec>,
+}
+
+/// The arguments with no flags given, as the command line would parse them
+impl Default for Args {
+    fn default() -> Self {
+        Args::parse_from(["logwatcher"])
+    }
 }
 
 impl Args {
@@ -128,7 +311,7 @@ impl Args {
         if let Some(prefix) = self.prefix_file {
             prefix
         } else {
-            self.files.len() > 1
+            self.files.len() + usize::from(self.exec.is_some()) > 1
         }
     }
 
@@ -160,21 +343,13 @@ mod tests {
     fn test_color_mappings_invalid_format() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
             color_map: Some("invalid_format".to_string()),
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
-            exclude: None,
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let mappings = args.color_mappings();
@@ -185,21 +360,13 @@ mod tests {
     fn test_exclude_patterns() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
             exclude: Some("DEBUG,TRACE".to_string()),
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let patterns = args.exclude_patterns();
@@ -212,27 +379,33 @@ mod tests {
     fn test_exclude_patterns_empty() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
-            exclude: None,
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let patterns = args.exclude_patterns();
         assert!(patterns.is_empty());
     }
 
+    #[test]
+    fn test_switches_take_an_optional_value() {
+        let args = Args::try_parse_from(["logwatcher", "-f", "app.log", "-ri", "--quiet"]).unwrap();
+        assert!(args.regex && args.case_insensitive && args.quiet);
+        assert!(args.notify);
+
+        let args =
+            Args::try_parse_from(["logwatcher", "--quiet=false", "--notify=false", "-r=false"])
+                .unwrap();
+        assert!(!args.quiet && !args.notify && !args.regex);
+
+        // The value must be attached, so a switch doesn't swallow the next argument
+        assert!(Args::try_parse_from(["logwatcher", "--quiet", "false"]).is_err());
+    }
+
     #[test]
     fn test_generate_completions() {
         // Just verify the function doesn't panic
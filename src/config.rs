@@ -1,47 +1,133 @@
+use crate::access_log::AccessLogFormat;
 use crate::cli::Args;
+use crate::encoding::TextEncoding;
+use crate::highlighter::{HighlightMode, OutputFormat};
+use crate::multiline::MultilineRules;
+use crate::output_file::{parse_size, OutputFileOptions};
+use crate::parser::InputFormat;
+use crate::rule::{MatchKind, Rule, RuleSpec, Severity};
+use crate::template::Template;
+use crate::timestamp::parse_since;
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use regex::Regex;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 use termcolor::Color;
 
 /// Maximum size limit for regex patterns to prevent ReDoS attacks
-const REGEX_SIZE_LIMIT: usize = 10 * 1024 * 1024; // 10 MB
+pub(crate) const REGEX_SIZE_LIMIT: usize = 10 * 1024 * 1024; // 10 MB
+
+/// Colors for level keywords that have no explicit mapping
+const DEFAULT_COLORS: [(&str, Color); 8] = [
+    ("ERROR", Color::Red),
+    ("WARN", Color::Yellow),
+    ("WARNING", Color::Yellow),
+    ("INFO", Color::Green),
+    ("DEBUG", Color::Cyan),
+    ("TRACE", Color::Magenta),
+    ("FATAL", Color::Red),
+    ("CRITICAL", Color::Red),
+];
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub files: Vec<PathBuf>,
-    pub patterns: Vec<String>,
-    pub regex_patterns: Vec<Regex>,
+    /// Shell command whose output is watched (`--exec`)
+    pub exec: Option<String>,
+    pub rules: Vec<Rule>,
+    /// Report every matching rule per line instead of only the first
+    pub all_matches: bool,
+    pub input_format: InputFormat,
+    /// Custom access log format (`--log-format`)
+    pub log_format: Option<AccessLogFormat>,
+    /// Decoded fields whose values are counted in the dry-run summary
+    pub summary_fields: Vec<String>,
+    /// How lines are grouped into multiline records, if they are
+    pub multiline: Option<MultilineRules>,
+    /// Drop blank lines before matching (`--skip-blank`)
+    pub skip_blank: bool,
+    /// Encoding lines are decoded from (`--encoding`)
+    pub encoding: TextEncoding,
+    /// How long an unfinished line waits for the rest (`--partial-timeout`)
+    pub partial_timeout: Duration,
     pub exclude_patterns: Vec<String>,
     pub exclude_patterns_lowercase: Vec<String>, // Pre-computed for case-insensitive matching
     pub exclude_regex_patterns: Vec<Regex>,
     pub case_insensitive: bool,
     pub color_mappings: HashMap<String, Color>,
     pub notify_enabled: bool,
-    pub notify_patterns: Vec<String>,
     pub notify_throttle: u32,
+    pub notify_title: Option<Template>,
+    pub notify_body: Option<Template>,
     pub dry_run: bool,
+    /// Read each file's rotation set in dry-run mode (`--include-rotated`)
+    pub include_rotated: bool,
+    /// Start with this many of each file's last lines (`--lines`)
+    pub last_lines: Option<usize>,
+    /// Hidden lines printed before each match (`-B`, `-C`)
+    pub before_context: usize,
+    /// Hidden lines printed after each match (`-A`, `-C`)
+    pub after_context: usize,
+    /// Start each file at its first line logged at or after this (`--since`)
+    pub since: Option<DateTime<Utc>>,
+    /// Show tail mode in the full-screen view (`--tui`)
+    pub tui: bool,
     pub quiet: bool,
     pub no_color: bool,
+    pub highlight_mode: HighlightMode,
+    /// Print lines and events as text or JSON objects (`--output`)
+    pub output: OutputFormat,
+    /// Layout of printed lines in text output (`--format`)
+    pub format: Option<Template>,
+    /// Where matched lines are also recorded (`--output-file`)
+    pub output_file: Option<OutputFileOptions>,
+    /// Print decoded records as aligned key=value fields
+    pub render_fields: bool,
     pub prefix_files: bool,
+    /// Poll every file instead of relying on change events (`--poll`)
+    pub force_poll: bool,
     pub poll_interval: u64,
     pub buffer_size: usize,
+    /// Where read checkpoints are kept between runs (`--state-file`)
+    pub state_file: Option<PathBuf>,
 }
 
 impl Config {
     pub fn from_args(args: &Args) -> Result<Self> {
-        let patterns = args.patterns();
+        if args.files().is_empty() && args.exec.is_none() {
+            return Err(anyhow::anyhow!(
+                "At least one --file or --exec is required (on the command line or in the config file)"
+            ));
+        }
+        if args
+            .exec
+            .as_ref()
+            .is_some_and(|command| command.trim().is_empty())
+        {
+            return Err(anyhow::anyhow!("--exec needs a command to run"));
+        }
+
+        // The command line rejects these combinations itself, but either
+        // half may come from the config file
+        if args.lines.is_some() && args.since.is_some() {
+            return Err(anyhow::anyhow!(
+                "--lines and --since can't be used together"
+            ));
+        }
+        if args.tui && (args.dry_run || args.output == OutputFormat::Json) {
+            return Err(anyhow::anyhow!(
+                "--tui can't be used with --dry-run or --output json"
+            ));
+        }
+        if args.include_rotated && !args.dry_run {
+            return Err(anyhow::anyhow!("--include-rotated needs --dry-run"));
+        }
+
         let notify_patterns = args.notify_patterns();
         let exclude_patterns = args.exclude_patterns();
 
-        // Validate and compile regex patterns if needed
-        let regex_patterns = if args.regex {
-            Self::compile_regex_patterns(&patterns, args.case_insensitive)?
-        } else {
-            vec![]
-        };
-
         // Compile exclude patterns as regex if regex mode is enabled
         let exclude_regex_patterns = if args.regex && !exclude_patterns.is_empty() {
             Self::compile_regex_patterns(&exclude_patterns, args.case_insensitive)?
@@ -56,49 +142,194 @@ impl Config {
             vec![]
         };
 
-        // Parse color mappings
-        let color_mappings = Self::parse_color_mappings(&args.color_mappings())?;
+        // Parse color mappings; keyword defaults are kept apart so rules can
+        // tell an explicit color from a guessed one
+        let user_color_mappings = Self::parse_color_mappings(&args.color_mappings())?;
+
+        // Rules from the config file come first, followed by --where and
+        // --pattern entries
+        let kind = if args.regex {
+            MatchKind::Regex
+        } else {
+            MatchKind::Literal
+        };
+        let mut specs = args.rules.clone();
+        for expr in &args.field_rules {
+            specs.push(RuleSpec::new(MatchKind::Field, expr));
+        }
+        for pattern in args.patterns() {
+            let mut spec = RuleSpec::new(kind, &pattern);
+            spec.notify = Some(notify_patterns.contains(&pattern));
+            spec.severity = pattern.parse::<Severity>().ok();
+            specs.push(spec);
+        }
+
+        let rules = specs
+            .iter()
+            .map(|spec| Rule::from_spec(spec, args.case_insensitive, &user_color_mappings))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut color_mappings = user_color_mappings;
+        Self::add_default_color_mappings(&mut color_mappings);
+
+        let log_format = match &args.log_format {
+            Some(format) => Some(
+                AccessLogFormat::parse(format)
+                    .with_context(|| format!("Invalid --log-format: {}", format))?,
+            ),
+            None => None,
+        };
+
+        // Field rules and summaries need decoded records
+        let input_format = match args.input_format {
+            InputFormat::Raw if log_format.is_some() => InputFormat::Access,
+            InputFormat::Raw
+                if !args.summarize.is_empty()
+                    || rules.iter().any(|rule| rule.kind == MatchKind::Field) =>
+            {
+                InputFormat::Auto
+            }
+            format => format,
+        };
+
+        let notify_title = args
+            .notify_title
+            .as_deref()
+            .map(Template::parse)
+            .transpose()
+            .context("Invalid --notify-title")?;
+        let notify_body = args
+            .notify_body
+            .as_deref()
+            .map(Template::parse)
+            .transpose()
+            .context("Invalid --notify-body")?;
+        let format = args
+            .format
+            .as_deref()
+            .map(Template::parse)
+            .transpose()
+            .context("Invalid --format")?;
+
+        let multiline_start = args
+            .multiline_start
+            .as_deref()
+            .map(|pattern| Self::compile_regex(pattern, false))
+            .transpose()
+            .context("Invalid --multiline-start")?;
+        let multiline =
+            (multiline_start.is_some() || args.multiline_indent).then(|| MultilineRules {
+                start: multiline_start,
+                indent: args.multiline_indent,
+                timeout: Duration::from_millis(args.multiline_timeout),
+            });
+
+        let encoding = args
+            .encoding
+            .as_deref()
+            .map(TextEncoding::for_label)
+            .transpose()
+            .context("Invalid --encoding")?
+            .unwrap_or_default();
+
+        let output_file = args
+            .output_file
+            .as_ref()
+            .map(|path| -> Result<OutputFileOptions> {
+                let max_size = args
+                    .output_file_max_size
+                    .as_deref()
+                    .map(parse_size)
+                    .transpose()
+                    .context("Invalid --output-file-max-size")?;
+                let max_age = args
+                    .output_file_max_age
+                    .as_deref()
+                    .map(humantime::parse_duration)
+                    .transpose()
+                    .context("Invalid --output-file-max-age")?;
+                if max_age.is_some_and(|age| age.is_zero()) {
+                    anyhow::bail!("Invalid --output-file-max-age: the age must be more than 0");
+                }
+                Ok(OutputFileOptions {
+                    path: path.clone(),
+                    format: args.output_file_format,
+                    all_lines: args.output_file_all,
+                    max_size,
+                    max_age,
+                    keep: args.output_file_keep,
+                })
+            })
+            .transpose()?;
+
+        let since = args
+            .since
+            .as_deref()
+            .map(parse_since)
+            .transpose()
+            .context("Invalid --since")?;
 
         Ok(Config {
             files: args.files().to_vec(),
-            patterns,
-            regex_patterns,
+            exec: args.exec.clone(),
+            rules,
+            all_matches: args.all_matches,
+            input_format,
+            log_format,
+            summary_fields: args.summarize.clone(),
+            multiline,
+            skip_blank: args.skip_blank,
+            encoding,
+            partial_timeout: Duration::from_millis(args.partial_timeout),
             exclude_patterns,
             exclude_patterns_lowercase,
             exclude_regex_patterns,
             case_insensitive: args.case_insensitive,
             color_mappings,
             notify_enabled: args.notify,
-            notify_patterns,
             notify_throttle: args.notify_throttle,
+            notify_title,
+            notify_body,
             dry_run: args.dry_run,
+            include_rotated: args.include_rotated,
+            last_lines: args.lines,
+            before_context: args.before_context.or(args.context).unwrap_or(0),
+            after_context: args.after_context.or(args.context).unwrap_or(0),
+            since,
+            tui: args.tui,
             quiet: args.quiet,
             no_color: args.no_color,
+            highlight_mode: args.highlight_mode,
+            output: args.output,
+            format,
+            output_file,
+            render_fields: args.render_fields,
             prefix_files: args.should_prefix_files(),
+            force_poll: args.poll,
             poll_interval: args.poll_interval,
             buffer_size: args.buffer_size,
+            state_file: args.state_file.clone(),
         })
     }
 
     fn compile_regex_patterns(patterns: &[String], case_insensitive: bool) -> Result<Vec<Regex>> {
-        let mut compiled = Vec::new();
-
-        for pattern in patterns {
-            let mut regex_builder = regex::RegexBuilder::new(pattern);
-            regex_builder.case_insensitive(case_insensitive);
-            // ReDoS protection: limit compiled regex size to prevent catastrophic backtracking
-            regex_builder.size_limit(REGEX_SIZE_LIMIT);
-            // Also limit DFA size for additional protection
-            regex_builder.dfa_size_limit(REGEX_SIZE_LIMIT);
-
-            let regex = regex_builder
-                .build()
-                .with_context(|| format!("Invalid or too complex regex pattern: {}", pattern))?;
-
-            compiled.push(regex);
-        }
+        patterns
+            .iter()
+            .map(|pattern| Self::compile_regex(pattern, case_insensitive))
+            .collect()
+    }
 
-        Ok(compiled)
+    pub(crate) fn compile_regex(pattern: &str, case_insensitive: bool) -> Result<Regex> {
+        let mut regex_builder = regex::RegexBuilder::new(pattern);
+        regex_builder.case_insensitive(case_insensitive);
+        // ReDoS protection: limit compiled regex size to prevent catastrophic backtracking
+        regex_builder.size_limit(REGEX_SIZE_LIMIT);
+        // Also limit DFA size for additional protection
+        regex_builder.dfa_size_limit(REGEX_SIZE_LIMIT);
+
+        regex_builder
+            .build()
+            .with_context(|| format!("Invalid or too complex regex pattern: {}", pattern))
     }
 
     fn parse_color_mappings(mappings: &[(String, String)]) -> Result<HashMap<String, Color>> {
@@ -109,13 +340,10 @@ impl Config {
             color_map.insert(pattern.clone(), color);
         }
 
-        // Add default color mappings if not specified
-        Self::add_default_color_mappings(&mut color_map);
-
         Ok(color_map)
     }
 
-    fn parse_color(color_name: &str) -> Result<Color> {
+    pub(crate) fn parse_color(color_name: &str) -> Result<Color> {
         match color_name.to_lowercase().as_str() {
             "black" => Ok(Color::Black),
             "red" => Ok(Color::Red),
@@ -130,25 +358,27 @@ impl Config {
     }
 
     fn add_default_color_mappings(color_map: &mut HashMap<String, Color>) {
-        let defaults = [
-            ("ERROR", Color::Red),
-            ("WARN", Color::Yellow),
-            ("WARNING", Color::Yellow),
-            ("INFO", Color::Green),
-            ("DEBUG", Color::Cyan),
-            ("TRACE", Color::Magenta),
-            ("FATAL", Color::Red),
-            ("CRITICAL", Color::Red),
-        ];
-
-        for (pattern, color) in defaults {
+        for (pattern, color) in DEFAULT_COLORS {
             color_map.entry(pattern.to_string()).or_insert(color);
         }
     }
 
-    /// Check if a pattern should trigger notifications
+    /// Built-in color for a well-known level keyword
+    pub(crate) fn default_color(pattern: &str) -> Option<Color> {
+        DEFAULT_COLORS
+            .iter()
+            .find(|(keyword, _)| *keyword == pattern)
+            .map(|(_, color)| *color)
+    }
+
+    /// Check if a rule (by name) should trigger notifications
     pub fn should_notify_for_pattern(&self, pattern: &str) -> bool {
-        self.notify_enabled && self.notify_patterns.contains(&pattern.to_string())
+        self.notify_enabled && self.rule(pattern).is_some_and(|rule| rule.notify)
+    }
+
+    /// Look up a rule by name
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.name == name)
     }
 
     /// Get color for a pattern
@@ -218,21 +448,12 @@ mod tests {
     fn test_get_color_for_pattern() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
-            exclude: None,
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let config = Config::from_args(&args).unwrap();
@@ -245,25 +466,88 @@ mod tests {
         assert_eq!(config.get_color_for_pattern("UNKNOWN"), None);
     }
 
+    #[test]
+    fn test_field_rules_enable_auto_input_format() {
+        let args = Args {
+            files: vec![PathBuf::from("test.log")],
+            patterns: "ERROR".to_string(),
+            notify: false,
+            prefix_file: Some(false),
+            poll_interval: 1000,
+            field_rules: vec!["status >= 500".to_string()],
+            notify_throttle: 0,
+            ..Default::default()
+        };
+
+        let config = Config::from_args(&args).unwrap();
+        assert_eq!(config.input_format, InputFormat::Auto);
+        // Field rules come before --pattern entries
+        assert_eq!(config.rules[0].kind, MatchKind::Field);
+        assert_eq!(config.rules[1].name, "ERROR");
+    }
+
+    #[test]
+    fn test_output_file_options() {
+        use clap::Parser;
+
+        let parse = |extra: &[&str]| {
+            let argv = [
+                &["logwatcher", "-f", "app.log", "--output-file", "m.log"],
+                extra,
+            ]
+            .concat();
+            Config::from_args(&Args::try_parse_from(argv).unwrap())
+        };
+
+        let options = parse(&[
+            "--output-file-max-size",
+            "10M",
+            "--output-file-max-age",
+            "1d",
+        ])
+        .unwrap()
+        .output_file
+        .unwrap();
+        assert_eq!(options.path, PathBuf::from("m.log"));
+        assert_eq!(options.max_size, Some(10 * 1024 * 1024));
+        assert_eq!(options.max_age, Some(Duration::from_secs(86400)));
+        assert_eq!(options.keep, 5);
+
+        let error = parse(&["--output-file-max-size", "lots"]).unwrap_err();
+        assert!(format!("{:#}", error).contains("Invalid --output-file-max-size"));
+        assert!(parse(&["--output-file-max-age", "0s"]).is_err());
+    }
+
+    #[test]
+    fn test_conflicts_from_config_file() {
+        use clap::Parser;
+
+        // The command line rejects these, but a config file can combine them
+        let mut args = Args::try_parse_from(["logwatcher", "-f", "app.log", "-n", "5"]).unwrap();
+        args.since = Some("15m".to_string());
+        assert!(Config::from_args(&args).is_err());
+
+        let mut args = Args::try_parse_from(["logwatcher", "-f", "app.log", "--dry-run"]).unwrap();
+        args.tui = true;
+        assert!(Config::from_args(&args).is_err());
+
+        let mut args = Args::try_parse_from(["logwatcher", "-f", "app.log"]).unwrap();
+        args.include_rotated = true;
+        let err = Config::from_args(&args).unwrap_err().to_string();
+        assert!(err.contains("--include-rotated needs --dry-run"));
+    }
+
     #[test]
     fn test_should_exclude_literal() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
             exclude: Some("DEBUG,TRACE".to_string()),
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let config = Config::from_args(&args).unwrap();
@@ -278,21 +562,14 @@ mod tests {
     fn test_should_exclude_case_insensitive() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
             case_insensitive: true,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
             exclude: Some("debug".to_string()),
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let config = Config::from_args(&args).unwrap();
@@ -306,21 +583,14 @@ mod tests {
     fn test_should_exclude_regex() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
             regex: true,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
             exclude: Some(r"DEBUG|TRACE".to_string()),
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let config = Config::from_args(&args).unwrap();
@@ -334,21 +604,12 @@ mod tests {
     fn test_should_exclude_empty() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
-            exclude: None,
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let config = Config::from_args(&args).unwrap();
@@ -0,0 +1,626 @@
+use crate::cli::Args;
+use crate::highlighter::{HighlightMode, OutputFormat};
+use crate::parser::InputFormat;
+use crate::rule::RuleSpec;
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Declare `Settings` and its `merge` from one list of keys, so every key
+/// is layered the same way
+macro_rules! settings {
+    ($($(#[$attr:meta])* $field:ident: $ty:ty,)*) => {
+        /// Settings that can be declared in a configuration file.
+        ///
+        /// Every field is optional: anything left unset falls back to the command line
+        /// defaults. The same shape is used for the top-level table and for each
+        /// `[profiles.<name>]` table.
+        #[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+        #[serde(deny_unknown_fields)]
+        pub struct Settings {
+            $($(#[$attr])* pub $field: Option<$ty>,)*
+        }
+
+        impl Settings {
+            /// Overlay `other` on top of `self`; values set in `other` take precedence
+            pub fn merge(self, other: Settings) -> Settings {
+                Settings {
+                    $($field: other.$field.or(self.$field),)*
+                }
+            }
+        }
+    };
+}
+
+settings! {
+    files: Vec<PathBuf>,
+    exec: String,
+    patterns: Vec<String>,
+    regex: bool,
+    case_insensitive: bool,
+    all_matches: bool,
+    /// Field expressions, like `--where`
+    #[serde(rename = "where")]
+    field_rules: Vec<String>,
+    input_format: InputFormat,
+    log_format: String,
+    summarize: Vec<String>,
+    multiline_start: String,
+    multiline_indent: bool,
+    multiline_timeout: u64,
+    skip_blank: bool,
+    encoding: String,
+    partial_timeout: u64,
+    colors: BTreeMap<String, String>,
+    exclude: Vec<String>,
+    notify: bool,
+    notify_patterns: Vec<String>,
+    notify_throttle: u32,
+    notify_title: String,
+    notify_body: String,
+    quiet: bool,
+    no_color: bool,
+    highlight_mode: HighlightMode,
+    output: OutputFormat,
+    before_context: usize,
+    after_context: usize,
+    context: usize,
+    lines: usize,
+    since: String,
+    dry_run: bool,
+    include_rotated: bool,
+    tui: bool,
+    format: String,
+    output_file: PathBuf,
+    output_file_format: OutputFormat,
+    output_file_all: bool,
+    output_file_max_size: String,
+    output_file_max_age: String,
+    output_file_keep: usize,
+    render_fields: bool,
+    prefix_file: bool,
+    poll: bool,
+    poll_interval: u64,
+    buffer_size: usize,
+    state_file: PathBuf,
+    rules: Vec<RuleSpec>,
+}
+
+/// A parsed `logwatcher.toml` file: top-level defaults plus named profiles
+#[derive(Debug, Default, Clone)]
+pub struct ConfigFile {
+    pub defaults: Settings,
+    pub profiles: BTreeMap<String, Settings>,
+}
+
+impl ConfigFile {
+    /// Load and parse a configuration file from disk
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid config file: {}", path.display()))
+    }
+
+    /// Parse configuration file content
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut table: toml::Table = content.parse()?;
+
+        let profiles = match table.remove("profiles") {
+            Some(value) => value
+                .try_into::<BTreeMap<String, Settings>>()
+                .context("Invalid [profiles] section")?,
+            None => BTreeMap::new(),
+        };
+        let defaults: Settings = toml::Value::Table(table).try_into()?;
+
+        Ok(Self { defaults, profiles })
+    }
+
+    /// Resolve the effective settings, layering the named profile (if any)
+    /// over the top-level defaults
+    pub fn resolve(&self, profile: Option<&str>) -> Result<Settings> {
+        let Some(name) = profile else {
+            return Ok(self.defaults.clone());
+        };
+
+        let overrides = self.profiles.get(name).ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow::anyhow!(
+                "Unknown profile: {} (available: {})",
+                name,
+                if available.is_empty() {
+                    "none".to_string()
+                } else {
+                    available.join(", ")
+                }
+            )
+        })?;
+
+        Ok(self.defaults.clone().merge(overrides.clone()))
+    }
+
+    /// Locate the configuration file, load it and apply it to `args`.
+    ///
+    /// An explicit `--config` path must exist. Otherwise the default location
+    /// is used when present. Values given on the command line always win.
+    pub fn apply(args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let path = match &args.config {
+            Some(path) => Some(path.clone()),
+            None => default_config_path().filter(|path| path.is_file()),
+        };
+
+        let Some(path) = path else {
+            if let Some(profile) = &args.profile {
+                return Err(anyhow::anyhow!(
+                    "Profile '{}' requested but no config file was found",
+                    profile
+                ));
+            }
+            // Defaults that depend on other arguments still apply
+            return Settings::default().apply_to(args, matches);
+        };
+
+        let settings = Self::load(&path)?.resolve(args.profile.as_deref())?;
+        settings.apply_to(args, matches)
+    }
+}
+
+impl Settings {
+    /// Copy settings into `args` for every argument not given on the command line
+    pub fn apply_to(&self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let from_cli = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        // Naming every key here means a new one can't be left unapplied
+        let Settings {
+            files,
+            exec,
+            patterns,
+            regex,
+            case_insensitive,
+            all_matches,
+            field_rules,
+            input_format,
+            log_format,
+            summarize,
+            multiline_start,
+            multiline_indent,
+            multiline_timeout,
+            skip_blank,
+            encoding,
+            partial_timeout,
+            colors,
+            exclude,
+            notify,
+            notify_patterns,
+            notify_throttle,
+            notify_title,
+            notify_body,
+            quiet,
+            no_color,
+            highlight_mode,
+            output,
+            before_context,
+            after_context,
+            context,
+            lines,
+            since,
+            dry_run,
+            include_rotated,
+            tui,
+            format,
+            output_file,
+            output_file_format,
+            output_file_all,
+            output_file_max_size,
+            output_file_max_age,
+            output_file_keep,
+            render_fields,
+            prefix_file,
+            poll,
+            poll_interval,
+            buffer_size,
+            state_file,
+            rules,
+        } = self;
+
+        // Keys named after their argument and copied as they are
+        macro_rules! copy {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = $field {
+                        if !from_cli(stringify!($field)) {
+                            args.$field = value.to_owned().into();
+                        }
+                    }
+                )*
+            };
+        }
+        copy!(
+            files,
+            exec,
+            regex,
+            case_insensitive,
+            all_matches,
+            field_rules,
+            input_format,
+            log_format,
+            summarize,
+            multiline_start,
+            multiline_indent,
+            multiline_timeout,
+            skip_blank,
+            encoding,
+            partial_timeout,
+            notify,
+            notify_throttle,
+            notify_title,
+            notify_body,
+            quiet,
+            no_color,
+            highlight_mode,
+            output,
+            before_context,
+            after_context,
+            context,
+            dry_run,
+            include_rotated,
+            tui,
+            format,
+            output_file,
+            output_file_format,
+            output_file_all,
+            output_file_max_size,
+            output_file_max_age,
+            output_file_keep,
+            render_fields,
+            prefix_file,
+            poll,
+            poll_interval,
+            buffer_size,
+            state_file,
+        );
+
+        if let Some(patterns) = patterns {
+            if !from_cli("patterns") {
+                args.patterns = join_list("patterns", patterns)?;
+            }
+        }
+        if let Some(colors) = colors {
+            if !from_cli("color_map") {
+                let mut mappings = Vec::new();
+                for (pattern, color) in colors {
+                    if pattern.contains([',', ':']) {
+                        return Err(anyhow::anyhow!(
+                            "Color mapping key '{}' must not contain ',' or ':'",
+                            pattern
+                        ));
+                    }
+                    mappings.push(format!("{}:{}", pattern, color));
+                }
+                args.color_map = Some(mappings.join(","));
+            }
+        }
+        if let Some(exclude) = exclude {
+            if !from_cli("exclude") {
+                args.exclude = Some(join_list("exclude", exclude)?);
+            }
+        }
+        if let Some(notify_patterns) = notify_patterns {
+            if !from_cli("notify_patterns") {
+                args.notify_patterns = Some(join_list("notify_patterns", notify_patterns)?);
+            }
+        }
+        // A starting point on the command line replaces the file's, whichever
+        // kind it is
+        let start_from_cli = from_cli("lines") || from_cli("since");
+        if let Some(lines) = lines {
+            if !start_from_cli {
+                args.lines = Some(*lines);
+            }
+        }
+        if let Some(since) = since {
+            if !start_from_cli {
+                args.since = Some(since.clone());
+            }
+        }
+        // Patterns on the command line replace the file's rules as well as
+        // its patterns
+        if let Some(rules) = rules {
+            if !from_cli("patterns") {
+                args.rules = rules.clone();
+            }
+        }
+        // Rules and field rules, from the file or the command line, replace
+        // the built-in ERROR,WARN default unless patterns are also given
+        // explicitly
+        let has_rules = !args.rules.is_empty() || !args.field_rules.is_empty();
+        if has_rules && patterns.is_none() && !from_cli("patterns") {
+            args.patterns.clear();
+        }
+
+        Ok(())
+    }
+}
+
+/// Default config file location: `$XDG_CONFIG_HOME/logwatcher/config.toml`,
+/// falling back to `~/.config/logwatcher/config.toml`
+pub fn default_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("logwatcher").join("config.toml"))
+}
+
+/// Join a list into the comma-separated form used by the CLI arguments
+fn join_list(key: &str, values: &[String]) -> Result<String> {
+    if let Some(value) = values.iter().find(|value| value.contains(',')) {
+        return Err(anyhow::anyhow!(
+            "Value '{}' in '{}' must not contain a comma",
+            value,
+            key
+        ));
+    }
+    Ok(values.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::MatchKind;
+    use clap::{CommandFactory, FromArgMatches};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    const SAMPLE: &str = r#"
+files = ["/var/log/app.log"]
+patterns = ["ERROR", "WARN"]
+notify_throttle = 2
+
+[colors]
+ERROR = "magenta"
+
+[profiles.payments]
+files = ["/var/log/payments.log"]
+patterns = ["declined", "timeout"]
+case_insensitive = true
+"#;
+
+    fn parse_args(argv: &[&str]) -> (Args, ArgMatches) {
+        let matches = Args::command()
+            .try_get_matches_from(std::iter::once("logwatcher").chain(argv.iter().copied()))
+            .unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        (args, matches)
+    }
+
+    #[test]
+    fn test_parse_defaults_and_profiles() {
+        let file = ConfigFile::parse(SAMPLE).unwrap();
+        assert_eq!(
+            file.defaults.patterns,
+            Some(vec!["ERROR".to_string(), "WARN".to_string()])
+        );
+        assert_eq!(file.defaults.notify_throttle, Some(2));
+        assert!(file.profiles.contains_key("payments"));
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_keys() {
+        let result = ConfigFile::parse("patern = [\"ERROR\"]");
+        assert!(result.is_err());
+
+        let result = ConfigFile::parse("[profiles.x]\nbogus = 1");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_resolve_profile_layers_over_defaults() {
+        let file = ConfigFile::parse(SAMPLE).unwrap();
+        let settings = file.resolve(Some("payments")).unwrap();
+
+        assert_eq!(
+            settings.files,
+            Some(vec![PathBuf::from("/var/log/payments.log")])
+        );
+        assert_eq!(settings.case_insensitive, Some(true));
+        // Inherited from the top-level table
+        assert_eq!(settings.notify_throttle, Some(2));
+    }
+
+    #[test]
+    fn test_resolve_unknown_profile() {
+        let file = ConfigFile::parse(SAMPLE).unwrap();
+        let err = file.resolve(Some("billing")).unwrap_err().to_string();
+        assert!(err.contains("Unknown profile: billing"));
+        assert!(err.contains("payments"));
+    }
+
+    #[test]
+    fn test_apply_to_fills_unset_args() {
+        let (mut args, matches) = parse_args(&[]);
+        let file = ConfigFile::parse(SAMPLE).unwrap();
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+
+        assert_eq!(args.files, vec![PathBuf::from("/var/log/app.log")]);
+        assert_eq!(args.patterns, "ERROR,WARN");
+        assert_eq!(args.notify_throttle, 2);
+        assert_eq!(args.color_map, Some("ERROR:magenta".to_string()));
+    }
+
+    #[test]
+    fn test_apply_to_cli_overrides_file() {
+        let (mut args, matches) =
+            parse_args(&["-f", "cli.log", "-p", "FATAL", "--notify-throttle", "9"]);
+        let file = ConfigFile::parse(SAMPLE).unwrap();
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+
+        assert_eq!(args.files, vec![PathBuf::from("cli.log")]);
+        assert_eq!(args.patterns, "FATAL");
+        assert_eq!(args.notify_throttle, 9);
+    }
+
+    #[test]
+    fn test_cli_switches_turn_off_file_values() {
+        let file = ConfigFile::parse(
+            r#"
+quiet = true
+no_color = true
+regex = true
+notify = true
+"#,
+        )
+        .unwrap();
+        let (mut args, matches) = parse_args(&["--quiet=false", "--no-color=false", "-r=false"]);
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+
+        assert!(!args.quiet);
+        assert!(!args.no_color);
+        assert!(!args.regex);
+        assert!(args.notify);
+    }
+
+    #[test]
+    fn test_rules_replace_default_patterns() {
+        let (mut args, matches) = parse_args(&[]);
+        let file = ConfigFile::parse(
+            r#"
+[[rules]]
+name = "slow"
+kind = "regex"
+pattern = 'took \d{4,}ms'
+"#,
+        )
+        .unwrap();
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+
+        assert_eq!(args.rules.len(), 1);
+        assert_eq!(args.rules[0].name.as_deref(), Some("slow"));
+        assert!(args.patterns().is_empty());
+    }
+
+    #[test]
+    fn test_cli_patterns_replace_file_rules() {
+        let file = ConfigFile::parse("[[rules]]\npattern = \"declined\"").unwrap();
+
+        let (mut args, matches) = parse_args(&["-p", "FATAL"]);
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+        assert!(args.rules.is_empty());
+        assert_eq!(args.patterns(), vec!["FATAL".to_string()]);
+
+        // Patterns from the file are checked alongside its rules
+        let file =
+            ConfigFile::parse("patterns = [\"FATAL\"]\n[[rules]]\npattern = \"declined\"").unwrap();
+        let (mut args, matches) = parse_args(&[]);
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+        assert_eq!(args.rules.len(), 1);
+        assert_eq!(args.patterns(), vec!["FATAL".to_string()]);
+    }
+
+    #[test]
+    fn test_field_rules_and_input_format() {
+        let (mut args, matches) = parse_args(&[]);
+        let file = ConfigFile::parse(
+            r#"
+input_format = "json"
+
+[[rules]]
+name = "server-errors"
+kind = "field"
+pattern = 'status >= 500 and path !~ /health/'
+"#,
+        )
+        .unwrap();
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+
+        assert_eq!(args.input_format, InputFormat::Json);
+        assert_eq!(args.rules[0].kind, MatchKind::Field);
+    }
+
+    #[test]
+    fn test_field_rules_replace_default_patterns() {
+        // From the command line, with or without a config file
+        let (mut args, matches) = parse_args(&["--where", "status >= 500"]);
+        Settings::default().apply_to(&mut args, &matches).unwrap();
+        assert!(args.patterns().is_empty());
+
+        // From the file
+        let (mut args, matches) = parse_args(&[]);
+        let file = ConfigFile::parse(r#"where = ["status >= 500"]"#).unwrap();
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+        assert_eq!(args.field_rules, vec!["status >= 500".to_string()]);
+        assert!(args.patterns().is_empty());
+
+        // Patterns given in the file are kept
+        let (mut args, matches) = parse_args(&["--where", "status >= 500"]);
+        let file = ConfigFile::parse(r#"patterns = ["FATAL"]"#).unwrap();
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+        assert_eq!(args.patterns(), vec!["FATAL".to_string()]);
+    }
+
+    #[test]
+    fn test_starting_point_and_mode_keys() {
+        let (mut args, matches) = parse_args(&[]);
+        let file = ConfigFile::parse(
+            r#"
+lines = 50
+dry_run = true
+include_rotated = true
+tui = true
+"#,
+        )
+        .unwrap();
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+        assert_eq!(args.lines, Some(50));
+        assert!(args.dry_run);
+        assert!(args.include_rotated);
+        assert!(args.tui);
+
+        // --since on the command line replaces the file's lines
+        let (mut args, matches) = parse_args(&["--since", "15m"]);
+        file.defaults.apply_to(&mut args, &matches).unwrap();
+        assert_eq!(args.lines, None);
+        assert_eq!(args.since.as_deref(), Some("15m"));
+    }
+
+    #[test]
+    fn test_apply_to_rejects_commas_in_lists() {
+        let (mut args, matches) = parse_args(&[]);
+        let file = ConfigFile::parse(r#"patterns = ["a{1,3}"]"#).unwrap();
+        let result = file.defaults.apply_to(&mut args, &matches);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_apply_with_explicit_config_and_profile() {
+        let mut config_file = NamedTempFile::new().unwrap();
+        write!(config_file, "{}", SAMPLE).unwrap();
+        config_file.flush().unwrap();
+
+        let path = config_file.path().to_str().unwrap();
+        let (mut args, matches) = parse_args(&["--config", path, "--profile", "payments"]);
+        ConfigFile::apply(&mut args, &matches).unwrap();
+
+        assert_eq!(args.files, vec![PathBuf::from("/var/log/payments.log")]);
+        assert_eq!(args.patterns, "declined,timeout");
+        assert!(args.case_insensitive);
+    }
+
+    #[test]
+    fn test_apply_missing_explicit_config() {
+        let (mut args, matches) = parse_args(&["--config", "/nonexistent/logwatcher.toml"]);
+        let err = ConfigFile::apply(&mut args, &matches)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Failed to read config file"));
+    }
+}
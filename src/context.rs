@@ -0,0 +1,196 @@
+//! Lines around matches that wouldn't be printed otherwise, like grep's
+//! `-B`, `-A` and `-C`. Each source keeps its last few hidden lines in case
+//! a match follows, and counts down the lines to show after a match. Groups
+//! that aren't adjacent are separated by `--`.
+
+use crate::tail::LinePosition;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// A line printed as context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextLine {
+    pub line: String,
+    pub position: LinePosition,
+}
+
+/// What to print before a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContextOutput {
+    /// A gap since the last printed line of the source
+    Separator,
+    Line(ContextLine),
+}
+
+#[derive(Debug)]
+pub struct ContextLines {
+    before: usize,
+    after: usize,
+    sources: HashMap<PathBuf, SourceContext>,
+}
+
+#[derive(Debug, Default)]
+struct SourceContext {
+    /// The last hidden lines, oldest first, with their sequence numbers
+    recent: VecDeque<(u64, ContextLine)>,
+    /// Hidden lines still to print after the last match
+    after_left: usize,
+    /// Sequence number of the next line
+    next: u64,
+    /// Sequence number of the last line printed
+    last_printed: Option<u64>,
+}
+
+impl SourceContext {
+    /// Note that the line with sequence number `seq` is printed, with a
+    /// separator first if lines were skipped since the last one printed
+    fn print(&mut self, seq: u64, output: &mut Vec<ContextOutput>) {
+        if self.last_printed.is_some_and(|last| seq > last + 1) {
+            output.push(ContextOutput::Separator);
+        }
+        self.last_printed = Some(seq);
+    }
+}
+
+impl ContextLines {
+    pub fn new(before: usize, after: usize) -> Self {
+        Self {
+            before,
+            after,
+            sources: HashMap::new(),
+        }
+    }
+
+    /// A matching line from a source: returns what to print before it
+    pub fn matched(&mut self, source: &Path) -> Vec<ContextOutput> {
+        let source = self.sources.entry(source.to_path_buf()).or_default();
+        let mut output = Vec::new();
+        for (seq, line) in std::mem::take(&mut source.recent) {
+            source.print(seq, &mut output);
+            output.push(ContextOutput::Line(line));
+        }
+        let seq = source.next;
+        source.next += 1;
+        source.print(seq, &mut output);
+        source.after_left = self.after;
+        output
+    }
+
+    /// A line that isn't a match. It is printed anyway when `shown`;
+    /// otherwise it is returned as context after a match, or kept in case
+    /// one follows.
+    pub fn unmatched(
+        &mut self,
+        source: &Path,
+        line: &str,
+        position: LinePosition,
+        shown: bool,
+    ) -> Vec<ContextOutput> {
+        let source = self.sources.entry(source.to_path_buf()).or_default();
+        let seq = source.next;
+        source.next += 1;
+        let mut output = Vec::new();
+
+        if shown {
+            source.recent.clear();
+            source.print(seq, &mut output);
+        } else if source.after_left > 0 {
+            source.after_left -= 1;
+            source.print(seq, &mut output);
+            output.push(ContextOutput::Line(ContextLine {
+                line: line.to_string(),
+                position,
+            }));
+        } else if self.before > 0 {
+            if source.recent.len() == self.before {
+                source.recent.pop_front();
+            }
+            let line = ContextLine {
+                line: line.to_string(),
+                position,
+            };
+            source.recent.push_back((seq, line));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed lines through, matching those containing "ERROR" and hiding the
+    /// rest, and return what gets printed
+    fn run(context: &mut ContextLines, source: &str, lines: &[&str]) -> Vec<String> {
+        let mut printed = Vec::new();
+        for line in lines {
+            let output = if line.contains("ERROR") {
+                context.matched(Path::new(source))
+            } else {
+                context.unmatched(Path::new(source), line, LinePosition::default(), false)
+            };
+            for item in output {
+                printed.push(match item {
+                    ContextOutput::Separator => "--".to_string(),
+                    ContextOutput::Line(context) => context.line,
+                });
+            }
+            if line.contains("ERROR") {
+                printed.push(line.to_string());
+            }
+        }
+        printed
+    }
+
+    #[test]
+    fn test_before_and_after() {
+        let mut context = ContextLines::new(2, 1);
+        let lines = [
+            "a", "b", "c", "d", "ERROR 1", "e", "f", "g", "h", "ERROR 2", "i",
+        ];
+        assert_eq!(
+            run(&mut context, "app.log", &lines),
+            vec!["c", "d", "ERROR 1", "e", "--", "g", "h", "ERROR 2", "i"]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_groups_are_merged() {
+        let mut context = ContextLines::new(1, 1);
+        let lines = ["a", "ERROR 1", "b", "c", "ERROR 2", "ERROR 3", "d"];
+        assert_eq!(
+            run(&mut context, "app.log", &lines),
+            vec!["a", "ERROR 1", "b", "c", "ERROR 2", "ERROR 3", "d"]
+        );
+    }
+
+    #[test]
+    fn test_sources_are_separate() {
+        let mut context = ContextLines::new(1, 0);
+        assert_eq!(run(&mut context, "a.log", &["a1"]), Vec::<String>::new());
+        assert_eq!(
+            run(&mut context, "b.log", &["b1", "ERROR b"]),
+            vec!["b1", "ERROR b"]
+        );
+        assert_eq!(
+            run(&mut context, "a.log", &["ERROR a"]),
+            vec!["a1", "ERROR a"]
+        );
+    }
+
+    #[test]
+    fn test_shown_lines_are_not_repeated() {
+        let mut context = ContextLines::new(2, 0);
+        let source = Path::new("app.log");
+        let position = LinePosition::default();
+        assert!(context.unmatched(source, "a", position, true).is_empty());
+        assert!(context.unmatched(source, "b", position, false).is_empty());
+        assert_eq!(
+            context.matched(source),
+            vec![ContextOutput::Line(ContextLine {
+                line: "b".to_string(),
+                position,
+            })]
+        );
+    }
+}
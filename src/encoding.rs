@@ -0,0 +1,202 @@
+//! Text encodings of log sources (`--encoding`). Lines are split on the
+//! encoding's newline, so UTF-16 files from Windows services are read a code
+//! unit at a time, and decoded lossily: bytes that aren't valid in the
+//! encoding become U+FFFD instead of stopping the source.
+
+use anyhow::Result;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use std::io::{self, BufRead};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEncoding(&'static Encoding);
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self(UTF_8)
+    }
+}
+
+impl TextEncoding {
+    /// An encoding by its WHATWG label, such as `utf-8`, `latin1`,
+    /// `windows-1252`, `shift_jis` or `utf-16le`
+    pub fn for_label(label: &str) -> Result<Self> {
+        let encoding = Encoding::for_label(label.as_bytes())
+            .ok_or_else(|| anyhow::anyhow!("Unknown encoding: {}", label))?;
+        // Lines of anything else can't be found by looking for a newline
+        if !encoding.is_ascii_compatible() && encoding != UTF_16LE && encoding != UTF_16BE {
+            anyhow::bail!("Unsupported encoding: {}", encoding.name());
+        }
+        Ok(Self(encoding))
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    /// Bytes per code unit; line boundaries are multiples of this
+    pub fn unit_len(&self) -> usize {
+        self.newline().len()
+    }
+
+    fn newline(&self) -> &'static [u8] {
+        if self.0 == UTF_16LE {
+            b"\n\0"
+        } else if self.0 == UTF_16BE {
+            b"\0\n"
+        } else {
+            b"\n"
+        }
+    }
+
+    /// Whether bytes starting at a line boundary end with a newline
+    pub fn ends_line(&self, bytes: &[u8]) -> bool {
+        bytes.len().is_multiple_of(self.unit_len()) && bytes.ends_with(self.newline())
+    }
+
+    /// How many of the `new` bytes, appended to the unfinished `line`, are
+    /// needed to finish it, if they contain its newline
+    fn line_end(&self, line: &[u8], new: &[u8]) -> Option<usize> {
+        if self.unit_len() == 1 {
+            return new.iter().position(|&byte| byte == b'\n').map(|i| i + 1);
+        }
+        let newline = self.newline();
+        (0..new.len())
+            .filter(|i| (line.len() + i) % 2 == 1)
+            .find(|&i| {
+                let first = if i == 0 { line.last() } else { new.get(i - 1) };
+                first == Some(&newline[0]) && new[i] == newline[1]
+            })
+            .map(|i| i + 1)
+    }
+
+    /// Append the rest of a line, up to and including its newline, to
+    /// `line`, which starts at a line boundary. Returns the bytes read; the
+    /// line is unfinished if it doesn't end with a newline.
+    pub fn read_line<R: BufRead>(&self, reader: &mut R, line: &mut Vec<u8>) -> io::Result<usize> {
+        let mut read = 0;
+        loop {
+            let available = reader.fill_buf()?;
+            if available.is_empty() {
+                return Ok(read);
+            }
+            let (used, done) = match self.line_end(line, available) {
+                Some(end) => (end, true),
+                None => (available.len(), false),
+            };
+            line.extend_from_slice(&available[..used]);
+            reader.consume(used);
+            read += used;
+            if done {
+                return Ok(read);
+            }
+        }
+    }
+
+    /// [`read_line`](Self::read_line) for async readers such as standard
+    /// input and command output
+    pub async fn read_line_async<R>(&self, reader: &mut R, line: &mut Vec<u8>) -> io::Result<usize>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut read = 0;
+        loop {
+            let available = reader.fill_buf().await?;
+            if available.is_empty() {
+                return Ok(read);
+            }
+            let (used, done) = match self.line_end(line, available) {
+                Some(end) => (end, true),
+                None => (available.len(), false),
+            };
+            line.extend_from_slice(&available[..used]);
+            reader.consume(used);
+            read += used;
+            if done {
+                return Ok(read);
+            }
+        }
+    }
+
+    /// The text of a line, without its byte order mark or its `\n` or
+    /// `\r\n` ending
+    pub fn decode(&self, line: &[u8]) -> String {
+        let (text, _) = self.0.decode_with_bom_removal(line);
+        strip_line_ending(&text).to_string()
+    }
+}
+
+/// A line without its terminator, which is `\n` or `\r\n`
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn read_lines(encoding: TextEncoding, bytes: &[u8]) -> Vec<String> {
+        let mut reader = io::BufReader::with_capacity(3, Cursor::new(bytes));
+        let mut lines = Vec::new();
+        let mut line = Vec::new();
+        while encoding.read_line(&mut reader, &mut line).unwrap() > 0 {
+            lines.push(encoding.decode(&line));
+            line.clear();
+        }
+        lines
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_replaced() {
+        let lines = read_lines(TextEncoding::default(), b"ERROR \xff\xfe bad\r\nok\n");
+        assert_eq!(lines, vec!["ERROR \u{fffd}\u{fffd} bad", "ok"]);
+    }
+
+    #[test]
+    fn test_latin1() {
+        let encoding = TextEncoding::for_label("latin1").unwrap();
+        let lines = read_lines(encoding, b"Fehler: Verbindung f\xfcr M\xfcller\n");
+        assert_eq!(lines, vec!["Fehler: Verbindung für Müller"]);
+    }
+
+    #[test]
+    fn test_utf16_lines_split_on_code_units() {
+        let encoding = TextEncoding::for_label("utf-16le").unwrap();
+        let mut bytes = vec![0xff, 0xfe];
+        // U+0A0A has a newline byte in both halves
+        bytes.extend(utf16le("ERROR \u{0a0a}\r\nsecond\nthird"));
+        let lines = read_lines(encoding, &bytes);
+        assert_eq!(lines, vec!["ERROR \u{0a0a}", "second", "third"]);
+        assert!(encoding.ends_line(&utf16le("line\n")));
+        assert!(!encoding.ends_line(&utf16le("line")));
+    }
+
+    #[test]
+    fn test_unfinished_line_is_continued() {
+        let encoding = TextEncoding::default();
+        let mut line = Vec::new();
+        let mut reader = Cursor::new(b"partial".to_vec());
+        assert_eq!(encoding.read_line(&mut reader, &mut line).unwrap(), 7);
+        assert!(!encoding.ends_line(&line));
+
+        let mut reader = Cursor::new(b" line\nnext".to_vec());
+        assert_eq!(encoding.read_line(&mut reader, &mut line).unwrap(), 6);
+        assert_eq!(encoding.decode(&line), "partial line");
+    }
+
+    #[test]
+    fn test_unknown_and_unsupported_encodings() {
+        assert!(TextEncoding::for_label("klingon").is_err());
+        assert!(TextEncoding::for_label("iso-2022-jp").is_err());
+        assert_eq!(
+            TextEncoding::for_label("UTF-16BE").unwrap().name(),
+            "UTF-16BE"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+//! Expanding `--file` arguments into the files to watch. An argument is a
+//! plain path, a directory (every file directly inside it) or a glob pattern
+//! such as `/var/log/app/**/*.log`. Directories and patterns are matched
+//! again as files are created, so new files can be picked up.
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone)]
+pub struct FileSet {
+    /// Paths given literally, kept as written
+    files: Vec<PathBuf>,
+    patterns: Vec<FilePattern>,
+}
+
+#[derive(Debug, Clone)]
+struct FilePattern {
+    /// The argument as given, for display
+    source: String,
+    /// Deepest directory without glob characters
+    root: PathBuf,
+    matcher: GlobMatcher,
+    /// Whether matches can be below the root's direct children
+    recursive: bool,
+}
+
+impl FileSet {
+    pub fn new(paths: &[PathBuf]) -> Result<Self> {
+        let mut files = Vec::new();
+        let mut patterns = Vec::new();
+
+        for path in paths {
+            if is_glob(path) {
+                patterns.push(FilePattern::new(path, path)?);
+            } else if path.is_dir() {
+                patterns.push(FilePattern::new(path, &path.join("*"))?);
+            } else {
+                files.push(path.clone());
+            }
+        }
+
+        Ok(Self { files, patterns })
+    }
+
+    /// Whether any argument is a directory or pattern, so that files can
+    /// come and go while watching
+    pub fn is_dynamic(&self) -> bool {
+        !self.patterns.is_empty()
+    }
+
+    /// Directory and pattern arguments, as given
+    pub fn patterns(&self) -> Vec<&str> {
+        self.patterns
+            .iter()
+            .map(|pattern| pattern.source.as_str())
+            .collect()
+    }
+
+    /// Literal paths followed by the regular files currently matching a
+    /// directory or pattern, without duplicates
+    pub fn expand(&self) -> Vec<PathBuf> {
+        let mut expanded = self.files.clone();
+        for pattern in &self.patterns {
+            for file in pattern.scan(&pattern.root) {
+                if !expanded.contains(&file) {
+                    expanded.push(file);
+                }
+            }
+        }
+        expanded
+    }
+
+    /// Whether a path is matched by a directory or pattern argument
+    pub fn matches(&self, path: &Path) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.matcher.is_match(path))
+    }
+
+    /// Files under a newly created directory that match a pattern
+    pub fn scan(&self, dir: &Path) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for pattern in &self.patterns {
+            if pattern.recursive && dir.starts_with(&pattern.root) {
+                found.extend(pattern.scan(dir));
+            }
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// Directories to watch for new files, and whether to watch them
+    /// recursively
+    pub fn roots(&self) -> Vec<(&Path, bool)> {
+        self.patterns
+            .iter()
+            .map(|pattern| (pattern.root.as_path(), pattern.recursive))
+            .collect()
+    }
+}
+
+impl FilePattern {
+    fn new(source: &Path, pattern: &Path) -> Result<Self> {
+        // Events report absolute paths, so patterns are matched against them
+        let pattern = std::path::absolute(pattern)
+            .with_context(|| format!("Invalid file pattern: {}", source.display()))?;
+
+        let mut root = PathBuf::new();
+        let mut rest = Vec::new();
+        for component in pattern.components() {
+            if rest.is_empty() && !is_glob(Path::new(component.as_os_str())) {
+                root.push(component);
+            } else {
+                rest.push(component);
+            }
+        }
+        let recursive = rest.len() > 1
+            || rest
+                .iter()
+                .any(|component| matches!(component, Component::Normal(name) if name.to_string_lossy().contains("**")));
+
+        let matcher = GlobBuilder::new(&pattern.to_string_lossy())
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid file pattern: {}", source.display()))?
+            .compile_matcher();
+
+        Ok(Self {
+            source: source.display().to_string(),
+            root,
+            matcher,
+            recursive,
+        })
+    }
+
+    /// Matching regular files under `dir`, sorted
+    fn scan(&self, dir: &Path) -> Vec<PathBuf> {
+        let depth = if self.recursive { usize::MAX } else { 1 };
+        let mut files: Vec<PathBuf> = WalkDir::new(dir)
+            .max_depth(depth)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| self.matcher.is_match(path))
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '[', '{'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn test_glob_expansion() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        touch(&root.join("a.log"));
+        touch(&root.join("b.txt"));
+        touch(&root.join("pods/web-1/app.log"));
+
+        let set = FileSet::new(&[root.join("*.log")]).unwrap();
+        assert!(set.is_dynamic());
+        assert_eq!(set.expand(), vec![root.join("a.log")]);
+
+        let set = FileSet::new(&[root.join("**/*.log")]).unwrap();
+        assert_eq!(
+            set.expand(),
+            vec![root.join("a.log"), root.join("pods/web-1/app.log")]
+        );
+        assert!(set.matches(&root.join("pods/web-2/app.log")));
+        assert!(!set.matches(&root.join("pods/web-2/app.txt")));
+    }
+
+    #[test]
+    fn test_directory_argument() {
+        let dir = TempDir::new().unwrap();
+        touch(&dir.path().join("one.log"));
+        touch(&dir.path().join("nested/two.log"));
+
+        let set = FileSet::new(&[dir.path().to_path_buf()]).unwrap();
+        assert_eq!(set.expand(), vec![dir.path().join("one.log")]);
+        assert!(set.matches(&dir.path().join("three.log")));
+        // Only files directly inside the directory
+        assert!(!set.matches(&dir.path().join("nested/four.log")));
+        assert_eq!(set.roots(), vec![(dir.path(), false)]);
+    }
+
+    #[test]
+    fn test_plain_files_are_kept_as_given() {
+        let set = FileSet::new(&[PathBuf::from("missing.log")]).unwrap();
+        assert!(!set.is_dynamic());
+        assert_eq!(set.expand(), vec![PathBuf::from("missing.log")]);
+        assert!(!set.matches(Path::new("missing.log")));
+    }
+
+    #[test]
+    fn test_pattern_roots() {
+        let set = FileSet::new(&[PathBuf::from("/var/log/app/*/current.log")]).unwrap();
+        assert_eq!(set.roots(), vec![(Path::new("/var/log/app"), true)]);
+        assert_eq!(set.patterns(), vec!["/var/log/app/*/current.log"]);
+
+        let set = FileSet::new(&[PathBuf::from("/var/log/app-*.log")]).unwrap();
+        assert_eq!(set.roots(), vec![(Path::new("/var/log"), false)]);
+    }
+
+    #[test]
+    fn test_scan_new_directory() {
+        let dir = TempDir::new().unwrap();
+        let set = FileSet::new(&[dir.path().join("**/*.log")]).unwrap();
+
+        touch(&dir.path().join("new/pod/app.log"));
+        touch(&dir.path().join("new/pod/app.txt"));
+        assert_eq!(
+            set.scan(&dir.path().join("new")),
+            vec![dir.path().join("new/pod/app.log")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(FileSet::new(&[PathBuf::from("/var/log/[.log")]).is_err());
+    }
+}
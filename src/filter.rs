@@ -0,0 +1,529 @@
+//! Field expressions for structured records.
+//!
+//! ```text
+//! level == "error"
+//! status >= 500 and not path ~ /health/
+//! (msg ~ /timeout/i or code = ETIMEDOUT) and retries > 2
+//! ```
+//!
+//! Operators: `==` (or `=`), `!=`, `>`, `>=`, `<`, `<=`, `~` (regex match),
+//! `!~`, combined with `and`/`&&`, `or`/`||`, `not`/`!` and parentheses. A bare
+//! field name tests that the field exists. Values are quoted strings,
+//! numbers, `true`/`false`/`null`, `/regex/` (optionally `/regex/i`) or bare
+//! words, which are treated as strings.
+//!
+//! Severity names compare by syslog level, most severe first, so
+//! `severity <= err` matches `err`, `crit`, `alert` and `emerg`, and aliases
+//! like `warn`/`warning` are equal.
+
+use crate::config::Config;
+use crate::parser::Record;
+use crate::rule::Severity;
+use anyhow::Result;
+use regex::Regex;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    /// The field is present in the record
+    Exists(String),
+    Compare {
+        field: String,
+        op: Op,
+        value: Literal,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Match,
+    NotMatch,
+}
+
+#[derive(Debug, Clone)]
+pub enum Literal {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Null,
+    Regex(Regex),
+}
+
+impl Expr {
+    /// Parse an expression
+    pub fn parse(input: &str) -> Result<Self> {
+        let tokens = tokenize(input)?;
+        let mut parser = ExprParser { tokens, pos: 0 };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(anyhow::anyhow!(
+                "Unexpected {} in field expression: {}",
+                token.describe(),
+                input
+            ));
+        }
+        Ok(expr)
+    }
+
+    /// Evaluate the expression against a record
+    pub fn evaluate(&self, record: &Record) -> bool {
+        match self {
+            Expr::And(a, b) => a.evaluate(record) && b.evaluate(record),
+            Expr::Or(a, b) => a.evaluate(record) || b.evaluate(record),
+            Expr::Not(inner) => !inner.evaluate(record),
+            Expr::Exists(field) => record.get(field).is_some(),
+            Expr::Compare { field, op, value } => match record.get(field) {
+                Some(actual) => compare(actual, *op, value),
+                // A missing field never equals or matches anything
+                None => matches!(op, Op::Ne | Op::NotMatch),
+            },
+        }
+    }
+}
+
+fn compare(actual: &Value, op: Op, expected: &Literal) -> bool {
+    match op {
+        Op::Match | Op::NotMatch => {
+            let is_match = match expected {
+                Literal::Regex(regex) => regex.is_match(&value_to_string(actual)),
+                other => value_to_string(actual).contains(&literal_to_string(other)),
+            };
+            is_match == (op == Op::Match)
+        }
+        Op::Eq => equals(actual, expected),
+        Op::Ne => !equals(actual, expected),
+        Op::Gt | Op::Ge | Op::Lt | Op::Le => match ordering(actual, expected) {
+            Some(ordering) => match op {
+                Op::Gt => ordering == Ordering::Greater,
+                Op::Ge => ordering != Ordering::Less,
+                Op::Lt => ordering == Ordering::Less,
+                _ => ordering != Ordering::Greater,
+            },
+            None => false,
+        },
+    }
+}
+
+fn equals(actual: &Value, expected: &Literal) -> bool {
+    match (actual, expected) {
+        (Value::Null, Literal::Null) => true,
+        (Value::Bool(a), Literal::Bool(b)) => a == b,
+        (_, Literal::Number(n)) => numeric_or_rank(actual) == Some(*n),
+        (Value::String(a), Literal::String(b)) => match (a.parse::<Severity>(), b.parse()) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        },
+        (_, Literal::Regex(regex)) => regex.is_match(&value_to_string(actual)),
+        _ => value_to_string(actual) == literal_to_string(expected),
+    }
+}
+
+/// Numbers compare numerically (numeric strings included) and severities by
+/// level; anything else compares as text
+fn ordering(actual: &Value, expected: &Literal) -> Option<Ordering> {
+    match expected {
+        Literal::Number(n) => numeric_or_rank(actual)?.partial_cmp(n),
+        Literal::String(s) => {
+            if let (Some(a), Ok(b)) = (severity_of(actual), s.parse::<Severity>()) {
+                return Some(a.cmp(&b));
+            }
+            match (value_to_number(actual), s.parse::<f64>().ok()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => Some(value_to_string(actual).as_str().cmp(s.as_str())),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn severity_of(value: &Value) -> Option<Severity> {
+    value.as_str()?.parse().ok()
+}
+
+/// A number, or the syslog level of a severity name
+fn numeric_or_rank(value: &Value) -> Option<f64> {
+    value_to_number(value).or_else(|| severity_of(value).map(|s| f64::from(s.rank())))
+}
+
+pub(crate) fn value_to_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+pub(crate) fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => "null".to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn literal_to_string(literal: &Literal) -> String {
+    match literal {
+        Literal::String(s) => s.clone(),
+        Literal::Number(n) => n.to_string(),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Null => "null".to_string(),
+        Literal::Regex(regex) => regex.as_str().to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Regex(String, bool),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => format!("'{}'", w),
+            Token::Quoted(s) => format!("\"{}\"", s),
+            Token::Regex(r, _) => format!("/{}/", r),
+            Token::Op(op) => format!("operator {:?}", op),
+            Token::And => "'and'".to_string(),
+            Token::Or => "'or'".to_string(),
+            Token::Not => "'not'".to_string(),
+            Token::LParen => "'('".to_string(),
+            Token::RParen => "')'".to_string(),
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '@' | ':' | '/' | '+')
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = input.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let after_match_op = matches!(tokens.last(), Some(Token::Op(Op::Match | Op::NotMatch)));
+
+        match c {
+            _ if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            '"' | '\'' => {
+                let (text, next) = read_delimited(&chars, i, c)?;
+                tokens.push(Token::Quoted(text));
+                i = next;
+            }
+            '/' if after_match_op => {
+                let (text, mut next) = read_delimited(&chars, i, '/')?;
+                let case_insensitive = chars.get(next) == Some(&'i');
+                if case_insensitive {
+                    next += 1;
+                }
+                tokens.push(Token::Regex(text, case_insensitive));
+                i = next;
+            }
+            '=' | '!' | '<' | '>' | '~' | '&' | '|' => {
+                let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+                let (token, len) = match two.as_str() {
+                    "==" => (Token::Op(Op::Eq), 2),
+                    "!=" => (Token::Op(Op::Ne), 2),
+                    ">=" => (Token::Op(Op::Ge), 2),
+                    "<=" => (Token::Op(Op::Le), 2),
+                    "!~" => (Token::Op(Op::NotMatch), 2),
+                    "&&" => (Token::And, 2),
+                    "||" => (Token::Or, 2),
+                    _ => match c {
+                        '=' => (Token::Op(Op::Eq), 1),
+                        '>' => (Token::Op(Op::Gt), 1),
+                        '<' => (Token::Op(Op::Lt), 1),
+                        '~' => (Token::Op(Op::Match), 1),
+                        '!' => (Token::Not, 1),
+                        _ => {
+                            return Err(anyhow::anyhow!(
+                                "Unexpected '{}' in field expression: {}",
+                                c,
+                                input
+                            ))
+                        }
+                    },
+                };
+                tokens.push(token);
+                i += len;
+            }
+            _ if is_word_char(c) => {
+                let start = i;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                tokens.push(match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unexpected '{}' in field expression: {}",
+                    c,
+                    input
+                ))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read text between `delimiter`s starting at `start`, honoring backslash
+/// escapes of the delimiter. Returns the text and the index after the closer.
+fn read_delimited(chars: &[char], start: usize, delimiter: char) -> Result<(String, usize)> {
+    let mut text = String::new();
+    let mut i = start + 1;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if chars.get(i + 1) == Some(&delimiter) => {
+                text.push(delimiter);
+                i += 2;
+            }
+            // Inside regexes other escapes belong to the regex syntax
+            '\\' if delimiter != '/' && chars.get(i + 1) == Some(&'\\') => {
+                text.push('\\');
+                i += 2;
+            }
+            c if c == delimiter => return Ok((text, i + 1)),
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "Unterminated {} in field expression",
+        if delimiter == '/' { "regex" } else { "string" }
+    ))
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl ExprParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            left = Expr::And(Box::new(left), Box::new(self.parse_unary()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::LParen) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => Err(anyhow::anyhow!("Missing ')' in field expression")),
+                }
+            }
+            Some(Token::Word(field)) | Some(Token::Quoted(field)) => self.parse_comparison(field),
+            Some(token) => Err(anyhow::anyhow!(
+                "Expected a field name, found {}",
+                token.describe()
+            )),
+            None => Err(anyhow::anyhow!("Unexpected end of field expression")),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: String) -> Result<Expr> {
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => return Ok(Expr::Exists(field)),
+        };
+        self.pos += 1;
+
+        let value = match self.next() {
+            Some(Token::Quoted(s)) => Literal::String(s),
+            Some(Token::Regex(pattern, case_insensitive)) => {
+                Literal::Regex(Config::compile_regex(&pattern, case_insensitive)?)
+            }
+            Some(Token::Word(word)) => match word.as_str() {
+                "true" => Literal::Bool(true),
+                "false" => Literal::Bool(false),
+                "null" => Literal::Null,
+                _ => match word.parse::<f64>() {
+                    Ok(n) => Literal::Number(n),
+                    Err(_) => Literal::String(word),
+                },
+            },
+            Some(token) => {
+                return Err(anyhow::anyhow!(
+                    "Expected a value after '{}', found {}",
+                    field,
+                    token.describe()
+                ))
+            }
+            None => return Err(anyhow::anyhow!("Missing value after '{}'", field)),
+        };
+
+        Ok(Expr::Compare { field, op, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{InputFormat, Parser};
+
+    fn record(json: &str) -> Record {
+        Parser::new(InputFormat::Json).parse(json).unwrap()
+    }
+
+    fn eval(expr: &str, json: &str) -> bool {
+        Expr::parse(expr).unwrap().evaluate(&record(json))
+    }
+
+    #[test]
+    fn test_equality() {
+        let line = r#"{"level":"error","status":503,"ok":false,"user":null}"#;
+        assert!(eval(r#"level == "error""#, line));
+        assert!(eval("level = error", line));
+        assert!(!eval("level == warn", line));
+        assert!(eval("level != warn", line));
+        assert!(eval("status == 503", line));
+        assert!(eval("ok == false", line));
+        assert!(eval("user == null", line));
+    }
+
+    #[test]
+    fn test_numeric_comparisons() {
+        let line = r#"{"status":503,"latency":"2.5"}"#;
+        assert!(eval("status >= 500", line));
+        assert!(eval("status > 500", line));
+        assert!(!eval("status < 500", line));
+        assert!(eval("status <= 503", line));
+        // Numeric strings compare as numbers
+        assert!(eval("latency > 2", line));
+        assert!(!eval("latency > 10", line));
+    }
+
+    #[test]
+    fn test_regex_match() {
+        let line = r#"{"msg":"upstream Timeout after 30s"}"#;
+        assert!(!eval("msg ~ /timeout/", line));
+        assert!(eval("msg ~ /timeout/i", line));
+        assert!(eval(r"msg ~ /after \d+s/", line));
+        assert!(eval("msg !~ /refused/", line));
+        // Non-regex values match as substrings
+        assert!(eval(r#"msg ~ "after 30""#, line));
+    }
+
+    #[test]
+    fn test_boolean_logic() {
+        let line = r#"{"level":"error","status":503,"path":"/healthz"}"#;
+        assert!(eval("level == error and status >= 500", line));
+        assert!(!eval("level == error and not path ~ /health/", line));
+        assert!(eval("level == warn or status >= 500", line));
+        assert!(eval("(level == warn || status >= 500) && path", line));
+        assert!(eval("!(level == warn)", line));
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        let line = r#"{"level":"error"}"#;
+        assert!(!eval("status >= 500", line));
+        assert!(!eval("status == 500", line));
+        assert!(eval("status != 500", line));
+        assert!(!eval("status", line));
+        assert!(eval("level", line));
+    }
+
+    #[test]
+    fn test_nested_fields() {
+        let line = r#"{"http":{"status":404,"path":"/x"}}"#;
+        assert!(eval("http.status == 404", line));
+        assert!(eval(r#"http.path == "/x""#, line));
+    }
+
+    #[test]
+    fn test_severity_comparisons() {
+        let line = r#"{"severity":"error","app":"sshd"}"#;
+        assert!(eval("severity <= err and app = sshd", line));
+        assert!(eval("severity <= error", line));
+        assert!(eval("severity < warning", line));
+        assert!(!eval("severity <= crit", line));
+        assert!(eval("severity == err", line));
+        assert!(eval("severity == 3", line));
+        assert!(eval("severity >= 3", line));
+        assert!(eval("severity == warn", r#"{"severity":"warning"}"#));
+        // Non-severity text still compares as text
+        assert!(!eval("app < debug", line));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expr::parse("").is_err());
+        assert!(Expr::parse("status >=").is_err());
+        assert!(Expr::parse("(status > 1").is_err());
+        assert!(Expr::parse("status > 1 )").is_err());
+        assert!(Expr::parse(r#"msg == "open"#).is_err());
+        assert!(Expr::parse("msg ~ /[unclosed/").is_err());
+    }
+
+    #[test]
+    fn test_escaped_quotes() {
+        let line = r#"{"msg":"say \"hi\""}"#;
+        assert!(eval(r#"msg == "say \"hi\"""#, line));
+        assert!(eval(r"msg ~ /\x22hi/", line));
+    }
+}
@@ -1,14 +1,73 @@
 use crate::config::Config;
-use crate::matcher::MatchResult;
+use crate::filter::value_to_string;
+use crate::matcher::{MatchResult, MatchSpan, NamedCapture};
+use crate::parser::Record;
+use crate::source::describe_exit;
+use crate::tail::LinePosition;
+use crate::template::Style;
+use crate::timestamp::line_timestamp;
 use anyhow::Result;
+use chrono::{Local, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::process::ExitStatus;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
+/// Which part of a matching line gets colored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightMode {
+    /// Color the whole line in the first matching rule's color
+    #[default]
+    Line,
+    /// Color only the matched text, each span in its rule's color
+    Span,
+    /// Color the whole line and emphasize the matched text in bold
+    Both,
+}
+
+/// How lines and events are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Colored lines, with events and summaries as messages on stderr
+    #[default]
+    Text,
+    /// One JSON object per line and per event on stdout (newline-delimited
+    /// JSON), each with an `event` type
+    #[value(alias = "ndjson")]
+    #[serde(alias = "ndjson")]
+    Json,
+}
+
+/// Colors cycled through for field keys when re-rendering records. Red and
+/// yellow are left out so keys don't read as errors or warnings.
+const KEY_COLORS: [Color; 3] = [Color::Blue, Color::Magenta, Color::Cyan];
+
+/// Fields wider than this don't widen their column
+const MAX_ALIGNED_WIDTH: usize = 32;
+
+/// Values listed per field in the dry-run summary
+const SUMMARY_TOP_VALUES: usize = 10;
+
+/// How `{time}` is shown in `--format` templates
+const FORMAT_TIME: &str = "%Y-%m-%d %H:%M:%S";
+
 #[derive(Debug)]
 pub struct Highlighter {
     config: Config,
     stdout: StandardStream,
     stderr: StandardStream,
+    /// Widest `key=value` seen so far for each key, used to align columns
+    field_widths: HashMap<String, usize>,
+    /// Info, warning and error messages held for the TUI instead of being
+    /// written over it
+    captured: Option<Vec<String>>,
 }
 
 impl Highlighter {
@@ -23,9 +82,24 @@ impl Highlighter {
             config,
             stdout: StandardStream::stdout(color_choice),
             stderr: StandardStream::stderr(color_choice),
+            field_widths: HashMap::new(),
+            captured: None,
         }
     }
 
+    /// Hold messages for [`take_messages`](Self::take_messages) instead of
+    /// printing them, or go back to printing them
+    pub fn capture_messages(&mut self, capture: bool) {
+        self.captured = capture.then(Vec::new);
+    }
+
+    pub fn take_messages(&mut self) -> Vec<String> {
+        self.captured
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
     pub fn print_line(
         &mut self,
         line: &str,
@@ -38,30 +112,217 @@ impl Highlighter {
             return Ok(());
         }
 
-        let mut output_line = String::new();
+        let mut output_line = self.prefix(filename, match_result, dry_run);
+
+        if self.config.highlight_mode != HighlightMode::Line && !match_result.spans.is_empty() {
+            return self.print_spans(&output_line, line, match_result);
+        }
+
+        // Add the actual line content
+        output_line.push_str(line);
+
+        // Print with or without color
+        if let Some(color) = match_result.color {
+            self.print_colored(&output_line, color)?;
+        } else {
+            self.print_plain(&output_line)?;
+        }
+
+        Ok(())
+    }
+
+    /// Print a line, re-rendered from its decoded record when `--render-fields`
+    /// is on. Lines without a record print as they are.
+    pub fn print_record(
+        &mut self,
+        line: &str,
+        record: Option<&Record>,
+        filename: Option<&str>,
+        match_result: &MatchResult,
+        dry_run: bool,
+    ) -> Result<()> {
+        let Some(record) = record.filter(|_| self.config.render_fields) else {
+            return self.print_line(line, filename, match_result, dry_run);
+        };
+        if self.config.quiet && !match_result.matched {
+            return Ok(());
+        }
+
+        let prefix = self.prefix(filename, match_result, dry_run);
+        let fields = layout_fields(record, &mut self.field_widths);
+
+        let mut value_spec = ColorSpec::new();
+        value_spec.set_fg(match_result.color);
+
+        self.stdout.set_color(&value_spec)?;
+        write!(self.stdout, "{}", prefix)?;
+        for (i, (key, value, padding)) in fields.iter().enumerate() {
+            if i > 0 {
+                self.stdout.reset()?;
+                write!(self.stdout, " ")?;
+            }
+            self.stdout
+                .set_color(ColorSpec::new().set_fg(Some(key_color(key))))?;
+            write!(self.stdout, "{}", key)?;
+            self.stdout.reset()?;
+            write!(self.stdout, "=")?;
+            self.stdout.set_color(&value_spec)?;
+            write!(self.stdout, "{}{:padding$}", value, "", padding = padding)?;
+        }
+
+        self.stdout.reset()?;
+        writeln!(self.stdout)?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    /// Print a line or record that was read from `file_path`: as text, or as
+    /// a `line` event with where it was read and what matched
+    pub fn print_match(
+        &mut self,
+        file_path: &Path,
+        position: LinePosition,
+        line: &str,
+        record: Option<&Record>,
+        match_result: &MatchResult,
+        dry_run: bool,
+    ) -> Result<()> {
+        if self.config.output == OutputFormat::Text {
+            let filename = file_path
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_else(|| file_path.to_string_lossy());
+            if let Some(format) = &self.config.format {
+                if self.config.quiet && !match_result.matched {
+                    return Ok(());
+                }
+                let lookup = |name: &str| {
+                    format_value(name, file_path, position, line, record, match_result)
+                };
+                let pieces = format.render_styled(lookup);
+                return self.print_styled(&pieces, match_result.color);
+            }
+            return self.print_record(line, record, Some(&filename), match_result, dry_run);
+        }
+        if self.config.quiet && !match_result.matched {
+            return Ok(());
+        }
+
+        self.print_event(line_event(file_path, position, line, record, match_result))
+    }
+
+    /// Print a line shown as context around a match (`-A`, `-B`, `-C`):
+    /// uncolored, or as a `context` event
+    pub fn print_context(
+        &mut self,
+        file_path: &Path,
+        position: LinePosition,
+        line: &str,
+        record: Option<&Record>,
+    ) -> Result<()> {
+        let unmatched = MatchResult::default();
+        if self.json() {
+            let mut event = line_event(file_path, position, line, record, &unmatched);
+            event["event"] = json!("context");
+            return self.print_event(event);
+        }
+        if let Some(format) = &self.config.format {
+            let lookup =
+                |name: &str| format_value(name, file_path, position, line, record, &unmatched);
+            let pieces = format.render_styled(lookup);
+            return self.print_styled(&pieces, None);
+        }
+        let filename = file_path.file_name().map(|name| name.to_string_lossy());
+        let mut output_line = self.prefix(filename.as_deref(), &unmatched, false);
+        output_line.push_str(line);
+        self.print_plain(&output_line)
+    }
+
+    /// Print the `--` between context groups that aren't adjacent. JSON
+    /// consumers can tell from the line numbers instead.
+    pub fn print_context_separator(&mut self) -> Result<()> {
+        if self.json() {
+            return Ok(());
+        }
+        self.print_plain("--")
+    }
+
+    /// Print a rendered `--format` line, each placeholder in its own style
+    fn print_styled(
+        &mut self,
+        pieces: &[(String, Style)],
+        match_color: Option<Color>,
+    ) -> Result<()> {
+        for (text, style) in pieces {
+            self.stdout.set_color(&style.spec(match_color))?;
+            write!(self.stdout, "{}", text)?;
+        }
+        self.stdout.reset()?;
+        writeln!(self.stdout)?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    fn json(&self) -> bool {
+        self.config.output == OutputFormat::Json
+    }
+
+    /// Write one JSON event on its own line
+    fn print_event(&mut self, event: Value) -> Result<()> {
+        writeln!(self.stdout, "{}", event)?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
+    fn print_file_event(&mut self, event: &str, filename: &str) -> Result<()> {
+        self.print_event(json!({ "event": event, "file": filename }))
+    }
+
+    /// Dry-run marker and filename shown before a line
+    fn prefix(&self, filename: Option<&str>, match_result: &MatchResult, dry_run: bool) -> String {
+        let mut prefix = String::new();
 
         // Add dry-run prefix if needed
         if dry_run && match_result.matched {
-            output_line.push_str("[DRY-RUN] ");
+            prefix.push_str("[DRY-RUN] ");
         }
 
         // Add filename prefix if needed
         if self.config.prefix_files {
             if let Some(filename) = filename {
-                output_line.push_str(&format!("[{}] ", filename));
+                prefix.push_str(&format!("[{}] ", filename));
             }
         }
 
-        // Add the actual line content
-        output_line.push_str(line);
+        prefix
+    }
 
-        // Print with or without color
-        if let Some(color) = match_result.color {
-            self.print_colored(&output_line, color)?;
-        } else {
-            self.print_plain(&output_line)?;
+    fn print_spans(&mut self, prefix: &str, line: &str, match_result: &MatchResult) -> Result<()> {
+        let both = self.config.highlight_mode == HighlightMode::Both;
+        let base_color = if both { match_result.color } else { None };
+
+        let mut base = ColorSpec::new();
+        base.set_fg(base_color);
+
+        self.stdout.set_color(&base)?;
+        write!(self.stdout, "{}", prefix)?;
+
+        for (range, span) in segments(line.len(), &match_result.spans) {
+            match span {
+                Some(span) => {
+                    let mut spec = ColorSpec::new();
+                    spec.set_fg(span.color.or(base_color))
+                        .set_bold(both || span.color.is_none());
+                    self.stdout.set_color(&spec)?;
+                }
+                None => self.stdout.set_color(&base)?,
+            }
+            write!(self.stdout, "{}", &line[range])?;
         }
 
+        self.stdout.reset()?;
+        writeln!(self.stdout)?;
+        self.stdout.flush()?;
         Ok(())
     }
 
@@ -81,6 +342,10 @@ impl Highlighter {
     }
 
     pub fn print_error(&mut self, message: &str) -> Result<()> {
+        if let Some(captured) = &mut self.captured {
+            captured.push(format!("Error: {}", message));
+            return Ok(());
+        }
         self.stderr
             .set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
         writeln!(self.stderr, "Error: {}", message)?;
@@ -90,6 +355,10 @@ impl Highlighter {
     }
 
     pub fn print_warning(&mut self, message: &str) -> Result<()> {
+        if let Some(captured) = &mut self.captured {
+            captured.push(format!("Warning: {}", message));
+            return Ok(());
+        }
         self.stderr
             .set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
         writeln!(self.stderr, "Warning: {}", message)?;
@@ -99,6 +368,10 @@ impl Highlighter {
     }
 
     pub fn print_info(&mut self, message: &str) -> Result<()> {
+        if let Some(captured) = &mut self.captured {
+            captured.push(format!("Info: {}", message));
+            return Ok(());
+        }
         self.stderr
             .set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
         writeln!(self.stderr, "Info: {}", message)?;
@@ -107,25 +380,104 @@ impl Highlighter {
         Ok(())
     }
 
-    pub fn print_dry_run_summary(&mut self, matches: &[(String, usize)]) -> Result<()> {
+    /// Print per-pattern match counts, then the most common values of each
+    /// `--summarize` field and each named regex capture among matching lines
+    pub fn print_dry_run_summary(
+        &mut self,
+        matches: &[(String, usize)],
+        stats: &WatcherStats,
+    ) -> Result<()> {
+        if self.json() {
+            let fields: BTreeMap<&String, BTreeMap<&String, &usize>> = self
+                .config
+                .summary_fields
+                .iter()
+                .filter_map(|field| Some((field, stats.field_values.get(field)?.iter().collect())))
+                .collect();
+            let captures: Vec<Value> = stats
+                .capture_values
+                .iter()
+                .map(|((pattern, name), values)| {
+                    let values: BTreeMap<&String, &usize> = values.iter().collect();
+                    json!({ "rule": pattern, "name": name, "values": values })
+                })
+                .collect();
+            let matches: BTreeMap<&String, &usize> = matches.iter().map(|(p, c)| (p, c)).collect();
+            return self.print_event(json!({
+                "event": "dry_run_summary",
+                "matches": matches,
+                "fields": fields,
+                "captures": captures,
+            }));
+        }
         if matches.is_empty() {
             self.print_info("No matching lines found")?;
             return Ok(());
         }
 
         self.print_info("Dry-run summary:")?;
-        for (pattern, count) in matches {
+        for (pattern, count) in sorted_counts(matches.iter().map(|(p, c)| (p, c))) {
             self.print_plain(&format!("  {}: {} matches", pattern, count))?;
         }
+        for field in self.config.summary_fields.clone() {
+            let Some(values) = stats.field_values.get(&field) else {
+                continue;
+            };
+            self.print_plain(&format!("  By {}:", field))?;
+            self.print_top_values(values)?;
+        }
+        for ((pattern, name), values) in &stats.capture_values {
+            self.print_plain(&format!("  By {} ({}):", name, pattern))?;
+            self.print_top_values(values)?;
+        }
         self.print_info("Dry-run complete. No notifications sent.")?;
         Ok(())
     }
 
-    pub fn print_startup_info(&mut self) -> Result<()> {
-        self.print_info(&format!("Watching {} file(s)", self.config.files.len()))?;
+    fn print_top_values(&mut self, values: &HashMap<String, usize>) -> Result<()> {
+        let sorted = sorted_counts(values.iter());
+        for (value, count) in sorted.iter().take(SUMMARY_TOP_VALUES) {
+            self.print_plain(&format!("    {}: {}", value, count))?;
+        }
+        if sorted.len() > SUMMARY_TOP_VALUES {
+            self.print_plain(&format!(
+                "    ... {} more",
+                sorted.len() - SUMMARY_TOP_VALUES
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// `patterns` are the directory and glob arguments watched for new files
+    pub fn print_startup_info(&mut self, stats: &WatcherStats, patterns: &[&str]) -> Result<()> {
+        if self.json() {
+            let rules: Vec<&str> = self.config.rules.iter().map(|r| r.name.as_str()).collect();
+            return self.print_event(json!({
+                "event": "start",
+                "files_watched": stats.files_watched,
+                "patterns": patterns,
+                "command": self.config.exec,
+                "rules": rules,
+                "notifications": self.config.notify_enabled,
+                "dry_run": self.config.dry_run,
+            }));
+        }
+        if !self.config.files.is_empty() {
+            self.print_info(&format!("Watching {} file(s)", stats.files_watched))?;
+        }
+        if !patterns.is_empty() && !self.config.dry_run {
+            self.print_info(&format!(
+                "Watching for new files matching: {}",
+                patterns.join(", ")
+            ))?;
+        }
+        if let Some(command) = &self.config.exec {
+            self.print_info(&format!("Watching output of: {}", command))?;
+        }
 
-        if !self.config.patterns.is_empty() {
-            self.print_info(&format!("Patterns: {}", self.config.patterns.join(", ")))?;
+        if !self.config.rules.is_empty() {
+            let names: Vec<&str> = self.config.rules.iter().map(|r| r.name.as_str()).collect();
+            self.print_info(&format!("Patterns: {}", names.join(", ")))?;
         }
 
         if self.config.notify_enabled {
@@ -140,69 +492,383 @@ impl Highlighter {
     }
 
     pub fn print_file_rotation(&mut self, filename: &str) -> Result<()> {
+        if self.json() {
+            return self.print_file_event("rotated", filename);
+        }
         self.print_warning(&format!("File rotation detected for {}", filename))?;
         Ok(())
     }
 
+    pub fn print_file_truncated(&mut self, filename: &str) -> Result<()> {
+        if self.json() {
+            return self.print_file_event("truncated", filename);
+        }
+        self.print_warning(&format!(
+            "File truncated: {} (reading from the start)",
+            filename
+        ))?;
+        Ok(())
+    }
+
+    pub fn print_file_missing(&mut self, filename: &str) -> Result<()> {
+        if self.json() {
+            return self.print_file_event("missing", filename);
+        }
+        self.print_warning(&format!(
+            "File disappeared: {} (waiting for it to be recreated)",
+            filename
+        ))?;
+        Ok(())
+    }
+
+    pub fn print_file_added(&mut self, filename: &str) -> Result<()> {
+        if self.json() {
+            return self.print_file_event("added", filename);
+        }
+        self.print_info(&format!("New file: {}", filename))?;
+        Ok(())
+    }
+
+    pub fn print_file_removed(&mut self, filename: &str) -> Result<()> {
+        if self.json() {
+            return self.print_file_event("removed", filename);
+        }
+        self.print_warning(&format!("File removed: {} (no longer watched)", filename))?;
+        Ok(())
+    }
+
     pub fn print_file_reopened(&mut self, filename: &str) -> Result<()> {
+        if self.json() {
+            return self.print_file_event("reopened", filename);
+        }
         self.print_info(&format!("Reopened file: {}", filename))?;
         Ok(())
     }
 
+    /// The `--exec` command ended, labelled as its lines are
+    pub fn print_command_exited(
+        &mut self,
+        label: &str,
+        status: &std::io::Result<ExitStatus>,
+    ) -> Result<()> {
+        let status = match status {
+            Ok(status) => *status,
+            Err(e) => {
+                if self.json() {
+                    return self.print_event(json!({
+                        "event": "command_exited",
+                        "file": label,
+                        "exit_code": null,
+                        "signal": null,
+                        "error": e.to_string(),
+                    }));
+                }
+                return self.print_warning(&format!("Command status couldn't be read: {}", e));
+            }
+        };
+        if self.json() {
+            #[cfg(unix)]
+            let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+            #[cfg(not(unix))]
+            let signal: Option<i32> = None;
+            return self.print_event(json!({
+                "event": "command_exited",
+                "file": label,
+                "exit_code": status.code(),
+                "signal": signal,
+            }));
+        }
+        self.print_warning(&format!("Command {}", describe_exit(status)))
+    }
+
     pub fn print_file_error(&mut self, filename: &str, error: &str) -> Result<()> {
+        if self.json() {
+            return self.print_event(json!({
+                "event": "error",
+                "file": filename,
+                "message": error,
+            }));
+        }
         self.print_error(&format!("Error watching {}: {}", filename, error))?;
         Ok(())
     }
 
     pub fn print_shutdown_summary(&mut self, stats: &WatcherStats) -> Result<()> {
+        if self.json() {
+            let rule_matches: BTreeMap<&String, &usize> = stats.rule_matches.iter().collect();
+            return self.print_event(json!({
+                "event": "summary",
+                "files_watched": stats.files_watched,
+                "files_added": stats.files_added,
+                "files_removed": stats.files_removed,
+                "lines_processed": stats.lines_processed,
+                "lines_excluded": stats.lines_excluded,
+                "matches_found": stats.matches_found,
+                "rule_matches": rule_matches,
+                "notifications_sent": stats.notifications_sent,
+                "command_exit_code": stats.command_status.and_then(|status| status.code()),
+            }));
+        }
         self.print_info("Shutdown summary:")?;
         self.print_plain(&format!("  Files watched: {}", stats.files_watched))?;
+        if stats.files_added > 0 {
+            self.print_plain(&format!("  Files added: {}", stats.files_added))?;
+        }
+        if stats.files_removed > 0 {
+            self.print_plain(&format!("  Files removed: {}", stats.files_removed))?;
+        }
         self.print_plain(&format!("  Lines processed: {}", stats.lines_processed))?;
         if stats.lines_excluded > 0 {
             self.print_plain(&format!("  Lines excluded: {}", stats.lines_excluded))?;
         }
         self.print_plain(&format!("  Matches found: {}", stats.matches_found))?;
+        if self.config.all_matches || stats.rule_matches.len() > 1 {
+            for (pattern, count) in sorted_counts(stats.rule_matches.iter()) {
+                self.print_plain(&format!("    {}: {}", pattern, count))?;
+            }
+        }
         self.print_plain(&format!(
             "  Notifications sent: {}",
             stats.notifications_sent
         ))?;
+        if let Some(status) = stats.command_status {
+            self.print_plain(&format!("  Command {}", describe_exit(status)))?;
+        }
         Ok(())
     }
 }
 
+/// The `line` event for a line read from `file_path`
+pub(crate) fn line_event(
+    file_path: &Path,
+    position: LinePosition,
+    line: &str,
+    record: Option<&Record>,
+    match_result: &MatchResult,
+) -> Value {
+    // A group name used by several rules keeps the first rule's value
+    let mut captures = Map::new();
+    for capture in &match_result.captures {
+        captures
+            .entry(capture.name.clone())
+            .or_insert_with(|| Value::String(capture.value.clone()));
+    }
+    let severity = match_result
+        .severity
+        .or_else(|| record.and_then(Record::severity));
+    let timestamp = line_timestamp(line, record).map(|time| time.to_rfc3339());
+
+    json!({
+        "event": "line",
+        "file": file_path.display().to_string(),
+        "offset": position.offset,
+        "line_number": position.number,
+        "line": line,
+        "matched": match_result.matched,
+        "rules": match_result.patterns,
+        "captures": captures,
+        "severity": severity.map(|severity| severity.as_str()),
+        "timestamp": timestamp,
+    })
+}
+
+/// The value of a `--format` placeholder: what was read and matched, or else
+/// a named capture or decoded field
+pub(crate) fn format_value(
+    name: &str,
+    file_path: &Path,
+    position: LinePosition,
+    line: &str,
+    record: Option<&Record>,
+    match_result: &MatchResult,
+) -> Option<String> {
+    match name {
+        "line" => Some(line.to_string()),
+        "file" => file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned()),
+        "filepath" => Some(file_path.display().to_string()),
+        "lineno" => position.number.map(|number| number.to_string()),
+        "offset" => Some(position.offset.to_string()),
+        "rule" | "pattern" => match_result.pattern.clone(),
+        "rules" => Some(match_result.patterns.join(",")),
+        "severity" => match_result
+            .severity
+            .or_else(|| record.and_then(Record::severity))
+            .map(|severity| severity.as_str().to_string()),
+        // When the line was logged, or else when it was read
+        "time" => {
+            let time = line_timestamp(line, record).unwrap_or_else(Utc::now);
+            Some(time.with_timezone(&Local).format(FORMAT_TIME).to_string())
+        }
+        field => match_result
+            .captures
+            .iter()
+            .find(|capture| capture.name == field)
+            .map(|capture| capture.value.clone())
+            .or_else(|| {
+                record
+                    .and_then(|record| record.get(field))
+                    .map(value_to_string)
+            }),
+    }
+}
+
+/// Order per-pattern counts by count (descending), then name
+fn sorted_counts<'a>(
+    counts: impl Iterator<Item = (&'a String, &'a usize)>,
+) -> Vec<(&'a String, &'a usize)> {
+    let mut counts: Vec<_> = counts.collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    counts
+}
+
+/// Format a record's fields as `(key, value, padding)` triples, widening the
+/// column for each key as wider values are seen. The last field is never
+/// padded.
+fn layout_fields(
+    record: &Record,
+    widths: &mut HashMap<String, usize>,
+) -> Vec<(String, String, usize)> {
+    let count = record.fields.len();
+    record
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, (key, value))| {
+            let value = format_field_value(value);
+            let width = key.chars().count() + 1 + value.chars().count();
+            let column = widths.entry(key.clone()).or_insert(0);
+            if width <= MAX_ALIGNED_WIDTH {
+                *column = (*column).max(width);
+            }
+            let padding = if i + 1 < count {
+                column.saturating_sub(width)
+            } else {
+                0
+            };
+            (key.clone(), value, padding)
+        })
+        .collect()
+}
+
+/// Render a field value the way logfmt would write it: bare when it can be,
+/// quoted when it contains spaces, quotes or `=`
+fn format_field_value(value: &Value) -> String {
+    match value {
+        Value::String(s)
+            if s.is_empty() || s.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') =>
+        {
+            Value::String(s.clone()).to_string()
+        }
+        other => value_to_string(other),
+    }
+}
+
+/// Stable color for a field key, so the same key looks the same on every line
+fn key_color(key: &str) -> Color {
+    let hash = key.bytes().fold(0usize, |hash, b| {
+        hash.wrapping_mul(31).wrapping_add(b as usize)
+    });
+    KEY_COLORS[hash % KEY_COLORS.len()]
+}
+
+/// Split `0..len` at every span boundary. Each piece is paired with the
+/// highest-priority span covering it (spans are ordered by priority), or
+/// `None` for unmatched text.
+fn segments(len: usize, spans: &[MatchSpan]) -> Vec<(Range<usize>, Option<&MatchSpan>)> {
+    let mut bounds: Vec<usize> = spans
+        .iter()
+        .flat_map(|span| [span.start.min(len), span.end.min(len)])
+        .chain([0, len])
+        .collect();
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut pieces: Vec<(Range<usize>, Option<&MatchSpan>)> = Vec::new();
+    for window in bounds.windows(2) {
+        let range = window[0]..window[1];
+        let covering = spans
+            .iter()
+            .find(|span| span.start <= range.start && span.end >= range.end);
+
+        // Merge with the previous piece when the owner doesn't change
+        match pieces.last_mut() {
+            Some((last, owner)) if *owner == covering => last.end = range.end,
+            _ => pieces.push((range, covering)),
+        }
+    }
+
+    pieces
+}
+
 #[derive(Debug, Default)]
 pub struct WatcherStats {
+    /// Files watched at startup
     pub files_watched: usize,
+    /// Files matching a directory or glob argument that appeared later
+    pub files_added: usize,
+    /// Files that were released after being deleted
+    pub files_removed: usize,
     pub lines_processed: usize,
     pub lines_excluded: usize,
     pub matches_found: usize,
     pub notifications_sent: usize,
+    /// Matches counted per rule name
+    pub rule_matches: HashMap<String, usize>,
+    /// For each `--summarize` field, matches counted per value
+    pub field_values: HashMap<String, HashMap<String, usize>>,
+    /// For each named capture group, keyed by rule name and group name,
+    /// matches counted per captured value
+    pub capture_values: BTreeMap<(String, String), HashMap<String, usize>>,
+    /// How the `--exec` command ended, once it has
+    pub command_status: Option<ExitStatus>,
+}
+
+impl WatcherStats {
+    /// Count the summarized fields of a matching record
+    pub fn count_fields(&mut self, fields: &[String], record: &Record) {
+        for field in fields {
+            if let Some(value) = record.get(field) {
+                *self
+                    .field_values
+                    .entry(field.clone())
+                    .or_default()
+                    .entry(value_to_string(value))
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Count the named capture groups of a match
+    pub fn count_captures(&mut self, captures: &[NamedCapture]) {
+        for capture in captures {
+            *self
+                .capture_values
+                .entry((capture.pattern.clone(), capture.name.clone()))
+                .or_default()
+                .entry(capture.value.clone())
+                .or_insert(0) += 1;
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::cli::Args;
+    use crate::parser::{InputFormat, Parser};
+    use crate::template::Template;
+    use chrono::TimeZone;
+    use serde_json::json;
     use std::path::PathBuf;
 
     fn create_test_config() -> Config {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
-            notify: true,
-            notify_patterns: None,
-            notify_throttle: 5,
-            dry_run: false,
-            quiet: false,
-            exclude: None,
             no_color: true, // Disable colors for testing
-            prefix_file: None,
-            poll_interval: 100,
-            buffer_size: 8192,
+            ..Default::default()
         };
         Config::from_args(&args).unwrap()
     }
@@ -217,6 +883,7 @@ mod tests {
             pattern: None,
             color: None,
             should_notify: false,
+            ..Default::default()
         };
 
         // This should not panic
@@ -235,6 +902,7 @@ mod tests {
             pattern: Some("ERROR".to_string()),
             color: Some(Color::Red),
             should_notify: true,
+            ..Default::default()
         };
 
         // This should not panic
@@ -253,6 +921,7 @@ mod tests {
             pattern: Some("ERROR".to_string()),
             color: Some(Color::Red),
             should_notify: true,
+            ..Default::default()
         };
 
         // This should not panic
@@ -279,6 +948,7 @@ mod tests {
             lines_excluded: 10,
             matches_found: 5,
             notifications_sent: 3,
+            ..Default::default()
         };
         let result = highlighter.print_shutdown_summary(&stats);
         assert!(result.is_ok());
@@ -292,6 +962,14 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_print_file_added_and_removed() {
+        let config = create_test_config();
+        let mut highlighter = Highlighter::new(config);
+        assert!(highlighter.print_file_added("new.log").is_ok());
+        assert!(highlighter.print_file_removed("new.log").is_ok());
+    }
+
     #[test]
     fn test_print_file_reopened() {
         let config = create_test_config();
@@ -304,7 +982,9 @@ mod tests {
     fn test_print_startup_info() {
         let config = create_test_config();
         let mut highlighter = Highlighter::new(config);
-        let result = highlighter.print_startup_info();
+        let result = highlighter.print_startup_info(&WatcherStats::default(), &[]);
+        assert!(result.is_ok());
+        let result = highlighter.print_startup_info(&WatcherStats::default(), &["/var/log/*.log"]);
         assert!(result.is_ok());
     }
 
@@ -328,21 +1008,13 @@ mod tests {
     fn test_color_choice_never() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
-            exclude: None,
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
             no_color: true, // Force no color
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let config = Config::from_args(&args).unwrap();
@@ -356,21 +1028,13 @@ mod tests {
     fn test_quiet_mode_skip_non_matching() {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
             quiet: true, // Enable quiet mode
-            dry_run: false,
-            exclude: None,
             prefix_file: Some(false),
             poll_interval: 1000,
-            buffer_size: 8192,
-            no_color: false,
             notify_throttle: 0,
+            ..Default::default()
         };
 
         let config = Config::from_args(&args).unwrap();
@@ -382,6 +1046,7 @@ mod tests {
             pattern: None,
             color: None,
             should_notify: false,
+            ..Default::default()
         };
 
         let result = highlighter.print_line("Normal line", None, &match_result, false);
@@ -395,7 +1060,7 @@ mod tests {
 
         // Test empty matches (covers line 112-113)
         let matches = vec![];
-        let result = highlighter.print_dry_run_summary(&matches);
+        let result = highlighter.print_dry_run_summary(&matches, &WatcherStats::default());
         assert!(result.is_ok());
     }
 
@@ -406,7 +1071,7 @@ mod tests {
 
         // Test with matches (covers line 116)
         let matches = vec![("ERROR".to_string(), 5), ("WARN".to_string(), 3)];
-        let result = highlighter.print_dry_run_summary(&matches);
+        let result = highlighter.print_dry_run_summary(&matches, &WatcherStats::default());
         assert!(result.is_ok());
     }
 
@@ -417,7 +1082,386 @@ mod tests {
 
         // Test print_dry_run_summary to cover line 116 (self.print_info("Dry-run summary:"))
         let matches = vec![("ERROR".to_string(), 2)];
-        let result = highlighter.print_dry_run_summary(&matches);
+        let result = highlighter.print_dry_run_summary(&matches, &WatcherStats::default());
         assert!(result.is_ok());
     }
+
+    fn span(start: usize, end: usize, pattern: &str) -> MatchSpan {
+        MatchSpan {
+            start,
+            end,
+            pattern: pattern.to_string(),
+            color: Some(Color::Red),
+        }
+    }
+
+    #[test]
+    fn test_segments_without_spans() {
+        assert_eq!(segments(5, &[]), vec![(0..5, None)]);
+    }
+
+    #[test]
+    fn test_segments_split_around_spans() {
+        let spans = vec![span(2, 4, "a"), span(6, 8, "b")];
+        let pieces: Vec<(Range<usize>, Option<&str>)> = segments(10, &spans)
+            .into_iter()
+            .map(|(range, span)| (range, span.map(|s| s.pattern.as_str())))
+            .collect();
+        assert_eq!(
+            pieces,
+            vec![
+                (0..2, None),
+                (2..4, Some("a")),
+                (4..6, None),
+                (6..8, Some("b")),
+                (8..10, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_segments_overlap_prefers_earlier_span() {
+        // "a" covers 0..6 and has priority over "b" at 4..9
+        let spans = vec![span(0, 6, "a"), span(4, 9, "b")];
+        let pieces: Vec<(Range<usize>, Option<&str>)> = segments(9, &spans)
+            .into_iter()
+            .map(|(range, span)| (range, span.map(|s| s.pattern.as_str())))
+            .collect();
+        assert_eq!(pieces, vec![(0..6, Some("a")), (6..9, Some("b"))]);
+    }
+
+    #[test]
+    fn test_print_line_span_modes() {
+        for mode in [HighlightMode::Span, HighlightMode::Both] {
+            let mut config = create_test_config();
+            config.highlight_mode = mode;
+            let mut highlighter = Highlighter::new(config);
+
+            let match_result = MatchResult {
+                matched: true,
+                pattern: Some("ERROR".to_string()),
+                color: Some(Color::Red),
+                spans: vec![span(0, 5, "ERROR")],
+                ..Default::default()
+            };
+            let result =
+                highlighter.print_line("ERROR: disk full", Some("app.log"), &match_result, true);
+            assert!(result.is_ok());
+        }
+    }
+
+    #[test]
+    fn test_sorted_counts() {
+        let counts: HashMap<String, usize> = [("WARN", 3), ("ERROR", 5), ("timeout", 3)]
+            .into_iter()
+            .map(|(p, c)| (p.to_string(), c))
+            .collect();
+        let sorted: Vec<(&str, usize)> = sorted_counts(counts.iter())
+            .into_iter()
+            .map(|(p, c)| (p.as_str(), *c))
+            .collect();
+        assert_eq!(sorted, vec![("ERROR", 5), ("WARN", 3), ("timeout", 3)]);
+    }
+
+    #[test]
+    fn test_print_shutdown_summary_per_rule() {
+        let mut config = create_test_config();
+        config.all_matches = true;
+        let mut highlighter = Highlighter::new(config);
+        let mut stats = WatcherStats {
+            matches_found: 2,
+            ..Default::default()
+        };
+        stats.rule_matches.insert("ERROR".to_string(), 2);
+        stats.rule_matches.insert("timeout".to_string(), 1);
+        assert!(highlighter.print_shutdown_summary(&stats).is_ok());
+    }
+
+    #[test]
+    fn test_layout_fields_aligns_columns() {
+        let parser = Parser::new(InputFormat::Logfmt);
+        let mut widths = HashMap::new();
+
+        let first = parser
+            .parse(r#"level=warning msg="disk low" pct=93"#)
+            .unwrap();
+        let layout = layout_fields(&first, &mut widths);
+        assert_eq!(
+            layout,
+            vec![
+                ("level".to_string(), "warning".to_string(), 0),
+                ("msg".to_string(), r#""disk low""#.to_string(), 0),
+                ("pct".to_string(), "93".to_string(), 0),
+            ]
+        );
+
+        // Narrower values are padded to the widest seen for their key
+        let second = parser.parse("level=info msg=ok pct=5").unwrap();
+        let padding: Vec<usize> = layout_fields(&second, &mut widths)
+            .into_iter()
+            .map(|(_, _, padding)| padding)
+            .collect();
+        assert_eq!(padding, vec![3, 8, 0]);
+    }
+
+    #[test]
+    fn test_layout_fields_caps_wide_columns() {
+        let parser = Parser::new(InputFormat::Logfmt);
+        let mut widths = HashMap::new();
+
+        let long = format!("msg={} level=error", "x".repeat(100));
+        layout_fields(&parser.parse(&long).unwrap(), &mut widths);
+        let layout = layout_fields(&parser.parse("msg=short level=error").unwrap(), &mut widths);
+        assert_eq!(layout[0].2, 0);
+    }
+
+    #[test]
+    fn test_format_field_value() {
+        assert_eq!(format_field_value(&json!("plain")), "plain");
+        assert_eq!(format_field_value(&json!("two words")), r#""two words""#);
+        assert_eq!(format_field_value(&json!("")), r#""""#);
+        assert_eq!(format_field_value(&json!(503)), "503");
+        assert_eq!(format_field_value(&json!({"a": 1})), r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn test_key_color_is_stable() {
+        assert_eq!(key_color("level"), key_color("level"));
+        assert!(KEY_COLORS.contains(&key_color("msg")));
+    }
+
+    #[test]
+    fn test_print_record() {
+        let mut config = create_test_config();
+        config.render_fields = true;
+        let mut highlighter = Highlighter::new(config);
+        let record = Parser::new(InputFormat::Json)
+            .parse(r#"{"level":"error","status":503}"#)
+            .unwrap();
+        let result = MatchResult {
+            matched: true,
+            color: Some(Color::Red),
+            ..Default::default()
+        };
+
+        assert!(highlighter
+            .print_record("raw", Some(&record), Some("app.log"), &result, true)
+            .is_ok());
+        assert!(highlighter
+            .print_record("raw", None, None, &result, false)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_count_fields() {
+        let parser = Parser::new(InputFormat::Access);
+        let mut stats = WatcherStats::default();
+        let fields = vec!["status_class".to_string(), "missing".to_string()];
+
+        for status in [503, 502, 404] {
+            let line = format!(
+                r#"10.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" {} 1"#,
+                status
+            );
+            stats.count_fields(&fields, &parser.parse(&line).unwrap());
+        }
+
+        assert_eq!(stats.field_values["status_class"]["5xx"], 2);
+        assert_eq!(stats.field_values["status_class"]["4xx"], 1);
+        assert!(!stats.field_values.contains_key("missing"));
+    }
+
+    #[test]
+    fn test_print_dry_run_summary_with_fields() {
+        let mut config = create_test_config();
+        config.summary_fields = vec!["path".to_string()];
+        let mut highlighter = Highlighter::new(config);
+
+        let paths: HashMap<String, usize> = (0..15).map(|i| (format!("/p{}", i), i)).collect();
+        let stats = WatcherStats {
+            field_values: HashMap::from([("path".to_string(), paths)]),
+            ..Default::default()
+        };
+        let matches = vec![("5xx".to_string(), 15)];
+        assert!(highlighter.print_dry_run_summary(&matches, &stats).is_ok());
+    }
+
+    #[test]
+    fn test_count_captures() {
+        let capture = |value: &str| NamedCapture {
+            pattern: "login".to_string(),
+            name: "user".to_string(),
+            value: value.to_string(),
+        };
+        let mut stats = WatcherStats::default();
+        for user in ["root", "alice", "root"] {
+            stats.count_captures(&[capture(user)]);
+        }
+
+        let users = &stats.capture_values[&("login".to_string(), "user".to_string())];
+        assert_eq!(users["root"], 2);
+        assert_eq!(users["alice"], 1);
+
+        let mut highlighter = Highlighter::new(create_test_config());
+        let matches = vec![("login".to_string(), 3)];
+        assert!(highlighter.print_dry_run_summary(&matches, &stats).is_ok());
+    }
+
+    #[test]
+    fn test_line_event() {
+        let line = r#"{"time":"2026-10-17T09:05:00Z","level":"warn","msg":"login failed"}"#;
+        let record = Parser::new(InputFormat::Json).parse(line).unwrap();
+        let capture = |pattern: &str, value: &str| NamedCapture {
+            pattern: pattern.to_string(),
+            name: "user".to_string(),
+            value: value.to_string(),
+        };
+        let result = MatchResult {
+            matched: true,
+            patterns: vec!["login".to_string(), "audit".to_string()],
+            captures: vec![capture("login", "root"), capture("audit", "admin")],
+            ..Default::default()
+        };
+        let position = LinePosition {
+            offset: 120,
+            number: Some(4),
+        };
+
+        let event = line_event(
+            Path::new("/var/log/app.log"),
+            position,
+            line,
+            Some(&record),
+            &result,
+        );
+        assert_eq!(
+            event,
+            json!({
+                "event": "line",
+                "file": "/var/log/app.log",
+                "offset": 120,
+                "line_number": 4,
+                "line": line,
+                "matched": true,
+                "rules": ["login", "audit"],
+                "captures": { "user": "root" },
+                "severity": "warning",
+                "timestamp": "2026-10-17T09:05:00+00:00",
+            })
+        );
+
+        let unmatched = line_event(
+            Path::new("-"),
+            LinePosition::default(),
+            "plain",
+            None,
+            &MatchResult::default(),
+        );
+        assert_eq!(unmatched["line_number"], Value::Null);
+        assert_eq!(unmatched["severity"], Value::Null);
+        assert_eq!(unmatched["timestamp"], Value::Null);
+    }
+
+    #[test]
+    fn test_json_events() {
+        let mut config = create_test_config();
+        config.output = OutputFormat::Json;
+        let mut highlighter = Highlighter::new(config);
+        let stats = WatcherStats::default();
+        assert!(highlighter.print_startup_info(&stats, &["*.log"]).is_ok());
+        assert!(highlighter.print_file_rotation("app.log").is_ok());
+        assert!(highlighter.print_file_error("app.log", "gone").is_ok());
+        assert!(highlighter
+            .print_dry_run_summary(&[("error".to_string(), 2)], &stats)
+            .is_ok());
+        assert!(highlighter.print_shutdown_summary(&stats).is_ok());
+        assert!(highlighter
+            .print_match(
+                Path::new("app.log"),
+                LinePosition::start(),
+                "INFO ok",
+                None,
+                &MatchResult::default(),
+                false,
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_output_format_aliases() {
+        assert_eq!(
+            OutputFormat::from_str("ndjson", true).unwrap(),
+            OutputFormat::Json
+        );
+        let format: OutputFormat = serde_json::from_str(r#""ndjson""#).unwrap();
+        assert_eq!(format, OutputFormat::Json);
+    }
+
+    #[test]
+    fn test_format_values() {
+        let line = r#"{"time":"2026-10-17T09:05:00Z","status":503,"user":"bob"}"#;
+        let record = Parser::new(InputFormat::Json).parse(line).unwrap();
+        let result = MatchResult {
+            matched: true,
+            pattern: Some("login".to_string()),
+            patterns: vec!["login".to_string(), "audit".to_string()],
+            captures: vec![NamedCapture {
+                pattern: "login".to_string(),
+                name: "user".to_string(),
+                value: "root".to_string(),
+            }],
+            ..Default::default()
+        };
+        let position = LinePosition {
+            offset: 42,
+            number: Some(3),
+        };
+        let value = |name: &str| {
+            format_value(
+                name,
+                Path::new("/var/log/app.log"),
+                position,
+                line,
+                Some(&record),
+                &result,
+            )
+        };
+
+        assert_eq!(value("file").as_deref(), Some("app.log"));
+        assert_eq!(value("filepath").as_deref(), Some("/var/log/app.log"));
+        assert_eq!(value("lineno").as_deref(), Some("3"));
+        assert_eq!(value("offset").as_deref(), Some("42"));
+        assert_eq!(value("rule").as_deref(), Some("login"));
+        assert_eq!(value("rules").as_deref(), Some("login,audit"));
+        // Captures come before decoded fields
+        assert_eq!(value("user").as_deref(), Some("root"));
+        assert_eq!(value("status").as_deref(), Some("503"));
+        assert_eq!(value("missing"), None);
+        let logged = Utc.with_ymd_and_hms(2026, 10, 17, 9, 5, 0).unwrap();
+        let time = logged.with_timezone(&Local).format(FORMAT_TIME).to_string();
+        assert_eq!(value("time"), Some(time));
+    }
+
+    #[test]
+    fn test_print_match_with_format() {
+        let mut config = create_test_config();
+        config.format = Some(Template::parse("{file:<10} [{rule:>5|match|bold}] {line}").unwrap());
+        let mut highlighter = Highlighter::new(config);
+        let result = MatchResult {
+            matched: true,
+            pattern: Some("ERROR".to_string()),
+            color: Some(Color::Red),
+            ..Default::default()
+        };
+        assert!(highlighter
+            .print_match(
+                Path::new("app.log"),
+                LinePosition::start(),
+                "ERROR: disk full",
+                None,
+                &result,
+                false,
+            )
+            .is_ok());
+    }
 }
@@ -1,8 +1,26 @@
+pub mod access_log;
+pub mod archive;
 pub mod cli;
 pub mod config;
+pub mod config_file;
+pub mod context;
+pub mod encoding;
+pub mod file_set;
+pub mod filter;
 pub mod highlighter;
 pub mod matcher;
+pub mod multiline;
 pub mod notifier;
+pub mod output_file;
+pub mod parser;
+pub mod rule;
+pub mod source;
+pub mod state;
+pub mod syslog;
+pub mod tail;
+pub mod template;
+pub mod timestamp;
+pub mod tui;
 pub mod utils;
 pub mod watcher;
 
@@ -13,4 +31,5 @@ pub use config::Config;
 pub use highlighter::Highlighter;
 pub use matcher::Matcher;
 pub use notifier::Notifier;
+pub use rule::Rule;
 pub use watcher::LogWatcher;
@@ -1,16 +1,29 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches};
 use log_watcher::cli::Args;
 use log_watcher::config::Config;
+use log_watcher::config_file::ConfigFile;
+use log_watcher::tui;
 use log_watcher::watcher::LogWatcher;
+use std::io::Write;
 use std::process;
 use tracing::{error, info};
 
 #[tokio::main]
 async fn main() {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
+    // Initialize logging, on stderr so stdout carries only lines and events,
+    // and silenced while the TUI has the terminal
+    tracing_subscriber::fmt()
+        .with_writer(|| -> Box<dyn Write> {
+            if tui::is_active() {
+                Box::new(std::io::sink())
+            } else {
+                Box::new(std::io::stderr())
+            }
+        })
+        .init();
 
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
 
     // Handle shell completions generation
     if let Some(shell) = args.completions {
@@ -18,11 +31,17 @@ async fn main() {
         process::exit(0);
     }
 
+    // Merge settings from the config file; explicit CLI flags take precedence
+    if let Err(e) = ConfigFile::apply(&mut args, &matches) {
+        eprintln!("Configuration error: {:#}", e);
+        process::exit(2);
+    }
+
     // Build configuration from CLI args
     let config = match Config::from_args(&args) {
         Ok(config) => config,
         Err(e) => {
-            eprintln!("Configuration error: {}", e);
+            eprintln!("Configuration error: {:#}", e);
             process::exit(2);
         }
     };
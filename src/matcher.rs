@@ -1,151 +1,389 @@
-use crate::config::Config;
-use regex::Regex;
-use std::collections::HashMap;
-
-#[derive(Debug, Clone)]
+use crate::config::{Config, REGEX_SIZE_LIMIT};
+use crate::highlighter::HighlightMode;
+use crate::parser::Record;
+use crate::rule::{MatchKind, Rule, Severity};
+use aho_corasick::AhoCorasick;
+use regex::{Regex, RegexSet, RegexSetBuilder};
+
+#[derive(Debug, Clone, Default)]
 pub struct MatchResult {
     pub matched: bool,
+    /// Name of the first rule that matched
     pub pattern: Option<String>,
+    /// Names of every rule counted for this line, in rule order: just the
+    /// first match, or all matching rules in all-matches mode
+    pub patterns: Vec<String>,
     pub color: Option<termcolor::Color>,
     pub should_notify: bool,
+    pub severity: Option<Severity>,
+    /// Byte ranges of every match in the line, from all matching rules,
+    /// ordered by rule then position. Ranges may overlap. Only located when
+    /// `--highlight-mode` colors spans.
+    pub spans: Vec<MatchSpan>,
+    /// Named capture groups of the rules in `patterns`, in rule order
+    pub captures: Vec<NamedCapture>,
+}
+
+/// The text matched by a named group (`(?P<user>\w+)`) of a regex rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedCapture {
+    /// Name of the rule the group belongs to
+    pub pattern: String,
+    pub name: String,
+    pub value: String,
+}
+
+/// A matched byte range within a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchSpan {
+    pub start: usize,
+    pub end: usize,
+    /// Name of the rule that produced this span
+    pub pattern: String,
+    pub color: Option<termcolor::Color>,
 }
 
+/// Rules are compiled into at most three engines, each scanning the line once:
+/// an Aho-Corasick automaton for case-sensitive literals, an ASCII
+/// case-insensitive automaton for case-insensitive literals, and a `RegexSet`
+/// for everything else. Each engine maps its pattern ids back to rule indices,
+/// and the lowest matching rule index wins, which keeps the first-match
+/// semantics of checking rules in order. Field rules are evaluated one by one
+/// against the decoded record, when the line has one.
+///
+/// Matched lines are only scanned again where the output needs it: for spans
+/// when they are highlighted, and for captures by rules with named groups.
 #[derive(Debug)]
 pub struct Matcher {
     config: Config,
-    literal_patterns: Vec<String>,
-    regex_patterns: Vec<Regex>,
-    pattern_colors: HashMap<String, termcolor::Color>,
+    literals: Option<Engine<AhoCorasick>>,
+    literals_ci: Option<Engine<AhoCorasick>>,
+    regexes: Option<Engine<RegexEngine>>,
+    /// Indices of field rules
+    fields: Vec<usize>,
+    /// Whether each rule is a regex with named groups
+    named_groups: Vec<bool>,
+    /// Locate match spans, for `--highlight-mode span` and `both`
+    locate_spans: bool,
+}
+
+#[derive(Debug)]
+struct Engine<T> {
+    searcher: T,
+    /// Rule index for each pattern id
+    rules: Vec<usize>,
+}
+
+/// `RegexSet` only reports which patterns matched, so the individual
+/// expressions are kept alongside it to locate match spans
+#[derive(Debug)]
+struct RegexEngine {
+    set: RegexSet,
+    regexes: Vec<Regex>,
 }
 
 impl Matcher {
     pub fn new(config: Config) -> Self {
-        let mut literal_patterns = Vec::new();
-        let mut regex_patterns = Vec::new();
-
-        if config.regex_patterns.is_empty() {
-            // Use literal patterns
-            literal_patterns = config.patterns.clone();
-        } else {
-            // Use regex patterns
-            regex_patterns = config.regex_patterns.clone();
+        let mut literals = (Vec::new(), Vec::new());
+        let mut literals_ci = (Vec::new(), Vec::new());
+        let mut regexes = (Vec::new(), Vec::new());
+        let mut fields = Vec::new();
+
+        for (index, rule) in config.rules.iter().enumerate() {
+            if rule.kind == MatchKind::Field {
+                fields.push(index);
+                continue;
+            }
+            match rule.regex() {
+                Some(regex) => {
+                    let pattern = if rule.case_insensitive {
+                        format!("(?i){}", regex.as_str())
+                    } else {
+                        regex.as_str().to_string()
+                    };
+                    regexes.0.push(pattern);
+                    regexes.1.push(index);
+                }
+                None if !rule.case_insensitive => {
+                    literals.0.push(rule.pattern.clone());
+                    literals.1.push(index);
+                }
+                // ASCII case folding is exact for ASCII patterns; anything else
+                // goes through the regex engine's Unicode case folding
+                None if rule.pattern.is_ascii() => {
+                    literals_ci.0.push(rule.pattern.clone());
+                    literals_ci.1.push(index);
+                }
+                None => {
+                    regexes
+                        .0
+                        .push(format!("(?i){}", regex::escape(&rule.pattern)));
+                    regexes.1.push(index);
+                }
+            }
         }
 
-        let pattern_colors = config.color_mappings.clone();
+        let literals = Self::build_automaton(literals, false);
+        let literals_ci = Self::build_automaton(literals_ci, true);
+        let regexes = Self::build_regex_set(regexes);
+        let named_groups = config
+            .rules
+            .iter()
+            .map(|rule| {
+                rule.regex()
+                    .is_some_and(|regex| regex.capture_names().flatten().next().is_some())
+            })
+            .collect();
+        let locate_spans = config.highlight_mode != HighlightMode::Line;
 
         Self {
             config,
-            literal_patterns,
-            regex_patterns,
-            pattern_colors,
+            literals,
+            literals_ci,
+            regexes,
+            fields,
+            named_groups,
+            locate_spans,
         }
     }
 
-    pub fn match_line(&self, line: &str) -> MatchResult {
-        if self.config.regex_patterns.is_empty() {
-            self.match_literal(line)
-        } else {
-            self.match_regex(line)
+    fn build_automaton(
+        (patterns, rules): (Vec<String>, Vec<usize>),
+        ascii_case_insensitive: bool,
+    ) -> Option<Engine<AhoCorasick>> {
+        if patterns.is_empty() {
+            return None;
         }
+
+        let searcher = AhoCorasick::builder()
+            .ascii_case_insensitive(ascii_case_insensitive)
+            .build(&patterns)
+            .expect("literal patterns always build");
+        Some(Engine { searcher, rules })
+    }
+
+    fn build_regex_set(
+        (patterns, rules): (Vec<String>, Vec<usize>),
+    ) -> Option<Engine<RegexEngine>> {
+        if patterns.is_empty() {
+            return None;
+        }
+
+        // Every pattern already compiled individually under the ReDoS limit,
+        // so the set gets the same budget per pattern
+        let limit = REGEX_SIZE_LIMIT.saturating_mul(patterns.len());
+        let set = RegexSetBuilder::new(&patterns)
+            .size_limit(limit)
+            .dfa_size_limit(limit)
+            .build()
+            .expect("patterns were validated when rules were compiled");
+        let regexes = patterns
+            .iter()
+            .map(|pattern| Config::compile_regex(pattern, false))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .expect("patterns were validated when rules were compiled");
+
+        Some(Engine {
+            searcher: RegexEngine { set, regexes },
+            rules,
+        })
     }
 
-    fn match_literal(&self, line: &str) -> MatchResult {
-        let search_line = if self.config.case_insensitive {
-            line.to_lowercase()
+    pub fn match_line(&self, line: &str) -> MatchResult {
+        self.match_record(line, None)
+    }
+
+    /// Match a line along with its decoded record. Text rules see the raw
+    /// line; field rules only match when a record is given.
+    pub fn match_record(&self, line: &str, record: Option<&Record>) -> MatchResult {
+        let indices = if self.config.all_matches {
+            self.all_matches(line, record)
         } else {
-            line.to_string()
+            self.first_match(line, record).into_iter().collect()
         };
 
-        for pattern in &self.literal_patterns {
-            let search_pattern = if self.config.case_insensitive {
-                pattern.to_lowercase()
-            } else {
-                pattern.clone()
-            };
-
-            if search_line.contains(&search_pattern) {
-                let color = self.pattern_colors.get(pattern).copied();
-                let should_notify = self.config.should_notify_for_pattern(pattern);
+        let Some(&first) = indices.first() else {
+            return MatchResult::default();
+        };
 
-                return MatchResult {
-                    matched: true,
-                    pattern: Some(pattern.clone()),
-                    color,
-                    should_notify,
-                };
+        let rule = &self.config.rules[first];
+        let mut result = self.result_for(rule);
+        // A severity decoded from the line beats one guessed from keywords
+        if let Some(severity) = record.and_then(Record::severity) {
+            result.severity = Some(severity);
+            if !rule.explicit_color {
+                result.color = Some(severity.color());
             }
         }
+        if indices.len() > 1 {
+            let rules: Vec<&Rule> = indices.iter().map(|&i| &self.config.rules[i]).collect();
+            result.patterns = rules.iter().map(|rule| rule.name.clone()).collect();
+            result.should_notify =
+                self.config.notify_enabled && rules.iter().any(|rule| rule.notify);
+        }
+        if self.locate_spans {
+            result.spans = self.spans(line);
+        }
+        if indices.iter().any(|&index| self.named_groups[index]) {
+            result.captures = self.captures(line, &indices);
+        }
+        result
+    }
+
+    /// Check if any pattern matches (for quiet mode filtering)
+    pub fn has_match(&self, line: &str) -> bool {
+        self.first_match(line, None).is_some()
+    }
+
+    /// Get the names of all rules that match a line, in rule order
+    pub fn get_all_matches(&self, line: &str) -> Vec<String> {
+        self.all_matches(line, None)
+            .into_iter()
+            .map(|index| self.config.rules[index].name.clone())
+            .collect()
+    }
 
+    fn result_for(&self, rule: &Rule) -> MatchResult {
         MatchResult {
-            matched: false,
-            pattern: None,
-            color: None,
-            should_notify: false,
+            matched: true,
+            pattern: Some(rule.name.clone()),
+            patterns: vec![rule.name.clone()],
+            color: rule.color,
+            should_notify: self.config.notify_enabled && rule.notify,
+            severity: rule.severity,
+            spans: Vec::new(),
+            captures: Vec::new(),
         }
     }
 
-    fn match_regex(&self, line: &str) -> MatchResult {
-        for (i, regex) in self.regex_patterns.iter().enumerate() {
-            if regex.is_match(line) {
-                let pattern = self.config.patterns.get(i).cloned().unwrap_or_default();
-                let color = self.pattern_colors.get(&pattern).copied();
-                let should_notify = self.config.should_notify_for_pattern(&pattern);
+    /// Index of the first rule (in rule order) that matches
+    fn first_match(&self, line: &str, record: Option<&Record>) -> Option<usize> {
+        let literal = [&self.literals, &self.literals_ci]
+            .into_iter()
+            .flatten()
+            .filter_map(|engine| {
+                engine
+                    .searcher
+                    .find_overlapping_iter(line)
+                    .map(|m| engine.rules[m.pattern().as_usize()])
+                    .min()
+            })
+            .min();
+
+        let regex = self.regexes.as_ref().and_then(|engine| {
+            engine
+                .searcher
+                .set
+                .matches(line)
+                .iter()
+                .next()
+                .map(|id| engine.rules[id])
+        });
+
+        let field = record.and_then(|record| self.field_matches(record).next());
+
+        literal.into_iter().chain(regex).chain(field).min()
+    }
 
-                return MatchResult {
-                    matched: true,
-                    pattern: Some(pattern),
-                    color,
-                    should_notify,
-                };
-            }
+    /// Indices of every matching rule, sorted and deduplicated
+    fn all_matches(&self, line: &str, record: Option<&Record>) -> Vec<usize> {
+        let mut indices = Vec::new();
+
+        if let Some(record) = record {
+            indices.extend(self.field_matches(record));
         }
 
-        MatchResult {
-            matched: false,
-            pattern: None,
-            color: None,
-            should_notify: false,
+        for engine in [&self.literals, &self.literals_ci].into_iter().flatten() {
+            indices.extend(
+                engine
+                    .searcher
+                    .find_overlapping_iter(line)
+                    .map(|m| engine.rules[m.pattern().as_usize()]),
+            );
+        }
+        if let Some(engine) = &self.regexes {
+            indices.extend(
+                engine
+                    .searcher
+                    .set
+                    .matches(line)
+                    .iter()
+                    .map(|id| engine.rules[id]),
+            );
         }
+
+        indices.sort_unstable();
+        indices.dedup();
+        indices
     }
 
-    /// Check if any pattern matches (for quiet mode filtering)
-    pub fn has_match(&self, line: &str) -> bool {
-        self.match_line(line).matched
+    /// Indices of field rules matching the record, in rule order
+    fn field_matches<'a>(&'a self, record: &'a Record) -> impl Iterator<Item = usize> + 'a {
+        self.fields
+            .iter()
+            .copied()
+            .filter(|&index| self.config.rules[index].matches_record(record))
     }
 
-    /// Get all patterns that match a line
-    pub fn get_all_matches(&self, line: &str) -> Vec<String> {
-        let mut matches = Vec::new();
+    /// Named groups from the first match of each listed rule
+    fn captures(&self, line: &str, indices: &[usize]) -> Vec<NamedCapture> {
+        let mut captures = Vec::new();
 
-        if self.config.regex_patterns.is_empty() {
-            let search_line = if self.config.case_insensitive {
-                line.to_lowercase()
-            } else {
-                line.to_string()
+        for &index in indices.iter().filter(|&&index| self.named_groups[index]) {
+            let rule = &self.config.rules[index];
+            let Some(regex) = rule.regex() else {
+                continue;
             };
+            let Some(groups) = regex.captures(line) else {
+                continue;
+            };
+            for name in regex.capture_names().flatten() {
+                if let Some(m) = groups.name(name) {
+                    captures.push(NamedCapture {
+                        pattern: rule.name.clone(),
+                        name: name.to_string(),
+                        value: m.as_str().to_string(),
+                    });
+                }
+            }
+        }
+
+        captures
+    }
 
-            for pattern in &self.literal_patterns {
-                let search_pattern = if self.config.case_insensitive {
-                    pattern.to_lowercase()
-                } else {
-                    pattern.clone()
-                };
+    /// Locate every match of every rule in the line
+    fn spans(&self, line: &str) -> Vec<MatchSpan> {
+        let mut spans: Vec<(usize, MatchSpan)> = Vec::new();
 
-                if search_line.contains(&search_pattern) {
-                    matches.push(pattern.clone());
-                }
+        for engine in [&self.literals, &self.literals_ci].into_iter().flatten() {
+            for m in engine.searcher.find_overlapping_iter(line) {
+                let index = engine.rules[m.pattern().as_usize()];
+                spans.push((index, self.span_for(index, m.start(), m.end())));
             }
-        } else {
-            for (i, regex) in self.regex_patterns.iter().enumerate() {
-                if regex.is_match(line) {
-                    if let Some(pattern) = self.config.patterns.get(i) {
-                        matches.push(pattern.clone());
+        }
+        if let Some(engine) = &self.regexes {
+            for id in engine.searcher.set.matches(line).iter() {
+                let index = engine.rules[id];
+                for m in engine.searcher.regexes[id].find_iter(line) {
+                    if !m.is_empty() {
+                        spans.push((index, self.span_for(index, m.start(), m.end())));
                     }
                 }
             }
         }
 
-        matches
+        spans.sort_by_key(|(index, span)| (*index, span.start, span.end));
+        spans.into_iter().map(|(_, span)| span).collect()
+    }
+
+    fn span_for(&self, index: usize, start: usize, end: usize) -> MatchSpan {
+        let rule = &self.config.rules[index];
+        MatchSpan {
+            start,
+            end,
+            pattern: rule.name.clone(),
+            color: rule.color,
+        }
     }
 }
 
@@ -153,26 +391,19 @@ impl Matcher {
 mod tests {
     use super::*;
     use crate::cli::Args;
+    use crate::highlighter::HighlightMode;
+    use crate::parser::{InputFormat, Parser};
+    use crate::rule::{MatchKind, RuleSpec};
+    use std::collections::HashMap;
     use std::path::PathBuf;
 
     fn create_test_config(patterns: &str, regex: bool, case_insensitive: bool) -> Config {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: patterns.to_string(),
             regex,
             case_insensitive,
-            color_map: None,
-            notify: true,
-            notify_patterns: None,
-            notify_throttle: 5,
-            dry_run: false,
-            quiet: false,
-            exclude: None,
-            no_color: false,
-            prefix_file: None,
-            poll_interval: 100,
-            buffer_size: 8192,
+            ..Default::default()
         };
         Config::from_args(&args).unwrap()
     }
@@ -288,4 +519,376 @@ mod tests {
         let no_matches = matcher.get_all_matches("INFO: Normal operation");
         assert!(no_matches.is_empty());
     }
+
+    #[test]
+    fn test_mixed_rule_kinds() {
+        let mut config = create_test_config("ERROR", false, false);
+
+        let mut literal = RuleSpec::new(MatchKind::Literal, "timeout");
+        literal.case_insensitive = Some(true);
+        let regex = RuleSpec::new(MatchKind::Regex, r"status=5\d\d");
+        let glob = RuleSpec::new(MatchKind::Glob, "*disk ? full*");
+
+        config.rules = [literal, regex, glob]
+            .iter()
+            .map(|spec| Rule::from_spec(spec, false, &HashMap::new()).unwrap())
+            .collect();
+        let matcher = Matcher::new(config);
+
+        assert_eq!(
+            matcher.match_line("Request TIMEOUT").pattern,
+            Some("timeout".to_string())
+        );
+        assert_eq!(
+            matcher.match_line("GET /api status=502").pattern,
+            Some(r"status=5\d\d".to_string())
+        );
+        assert_eq!(
+            matcher.match_line("warning: disk 1 full").pattern,
+            Some("*disk ? full*".to_string())
+        );
+        assert!(!matcher.match_line("status=404 Timeo").matched);
+    }
+
+    #[test]
+    fn test_match_result_carries_rule_settings() {
+        let mut config = create_test_config("ERROR", false, false);
+        let mut spec = RuleSpec::new(MatchKind::Literal, "declined");
+        spec.name = Some("payment-declined".to_string());
+        spec.notify = Some(false);
+        spec.severity = Some(Severity::Warning);
+        config.rules = vec![Rule::from_spec(&spec, false, &HashMap::new()).unwrap()];
+        let matcher = Matcher::new(config);
+
+        let result = matcher.match_line("card declined");
+        assert!(result.matched);
+        assert_eq!(result.pattern, Some("payment-declined".to_string()));
+        assert_eq!(result.severity, Some(Severity::Warning));
+        assert_eq!(result.color, Some(termcolor::Color::Yellow));
+        assert!(!result.should_notify);
+    }
+
+    #[test]
+    fn test_first_match_follows_rule_order() {
+        // "WARN" appears earlier in the line, but ERROR is the first rule
+        let config = create_test_config("ERROR,WARN", false, false);
+        let matcher = Matcher::new(config);
+        let result = matcher.match_line("WARN then ERROR");
+        assert_eq!(result.pattern, Some("ERROR".to_string()));
+
+        // Order is preserved across engines: a regex rule ahead of a literal
+        let mut config = create_test_config("ERROR", false, false);
+        config.rules = vec![
+            Rule::from_spec(
+                &RuleSpec::new(MatchKind::Regex, "time(out)?"),
+                false,
+                &HashMap::new(),
+            )
+            .unwrap(),
+            Rule::literal("timeout"),
+        ];
+        let matcher = Matcher::new(config);
+        assert_eq!(
+            matcher.match_line("timeout").pattern,
+            Some("time(out)?".to_string())
+        );
+    }
+
+    #[test]
+    fn test_overlapping_literals_all_reported() {
+        let config = create_test_config("connection,connection refused,refused", false, false);
+        let matcher = Matcher::new(config);
+
+        let matches = matcher.get_all_matches("db: connection refused");
+        assert_eq!(
+            matches,
+            vec![
+                "connection".to_string(),
+                "connection refused".to_string(),
+                "refused".to_string()
+            ]
+        );
+        assert_eq!(
+            matcher.match_line("connection refused").pattern,
+            Some("connection".to_string())
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_case_insensitive_literal() {
+        let config = create_test_config("ÉCHEC", false, true);
+        let matcher = Matcher::new(config);
+        assert!(matcher.has_match("tâche en échec"));
+        assert!(!matcher.has_match("tache en echec"));
+    }
+
+    #[test]
+    fn test_single_pass_agrees_with_per_rule_matching() {
+        let config = create_test_config("ERROR,warn,Timeout,db", false, true);
+        let rules = config.rules.clone();
+        let matcher = Matcher::new(config);
+
+        let lines = [
+            "ERROR: db Timeout",
+            "warning: disk low",
+            "nothing to see",
+            "DB reconnect",
+            "TIMEOUT then error",
+        ];
+        for line in lines {
+            let expected = rules
+                .iter()
+                .find(|rule| rule.is_match(line, None))
+                .map(|rule| rule.name.clone());
+            assert_eq!(matcher.match_line(line).pattern, expected, "line: {}", line);
+
+            let expected_all: Vec<String> = rules
+                .iter()
+                .filter(|rule| rule.is_match(line, None))
+                .map(|rule| rule.name.clone())
+                .collect();
+            assert_eq!(
+                matcher.get_all_matches(line),
+                expected_all,
+                "line: {}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn test_match_spans() {
+        let mut config = create_test_config("ERROR,timeout", false, true);
+        config.highlight_mode = HighlightMode::Span;
+        let matcher = Matcher::new(config);
+
+        let line = "error: upstream Timeout, ERROR again";
+        let result = matcher.match_line(line);
+        let spans: Vec<(&str, &str)> = result
+            .spans
+            .iter()
+            .map(|span| (span.pattern.as_str(), &line[span.start..span.end]))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("ERROR", "error"),
+                ("ERROR", "ERROR"),
+                ("timeout", "Timeout")
+            ]
+        );
+        assert_eq!(result.spans[0].color, Some(termcolor::Color::Red));
+    }
+
+    #[test]
+    fn test_overlapping_spans_from_several_rules() {
+        let mut config = create_test_config(r"connection refused,refused \(\d+\)", true, false);
+        config.highlight_mode = HighlightMode::Span;
+        let matcher = Matcher::new(config);
+
+        let line = "db connection refused (111)";
+        let result = matcher.match_line(line);
+        assert_eq!(result.spans.len(), 2);
+        assert_eq!(
+            &line[result.spans[0].start..result.spans[0].end],
+            "connection refused"
+        );
+        assert_eq!(
+            &line[result.spans[1].start..result.spans[1].end],
+            "refused (111)"
+        );
+        assert!(result.spans[1].start < result.spans[0].end);
+    }
+
+    #[test]
+    fn test_no_spans_without_match() {
+        let mut config = create_test_config("ERROR", false, false);
+        config.highlight_mode = HighlightMode::Span;
+        let matcher = Matcher::new(config);
+        assert!(matcher.match_line("all good").spans.is_empty());
+    }
+
+    #[test]
+    fn test_no_spans_in_line_mode() {
+        let config = create_test_config("ERROR", false, false);
+        let matcher = Matcher::new(config);
+        let result = matcher.match_line("ERROR: disk full");
+        assert!(result.matched);
+        assert!(result.spans.is_empty());
+    }
+
+    #[test]
+    fn test_first_match_mode_reports_one_pattern() {
+        let config = create_test_config("ERROR,timeout", false, false);
+        let matcher = Matcher::new(config);
+
+        let result = matcher.match_line("ERROR: upstream timeout");
+        assert_eq!(result.patterns, vec!["ERROR".to_string()]);
+    }
+
+    #[test]
+    fn test_all_matches_mode() {
+        let mut config = create_test_config("ERROR,WARN,timeout", false, false);
+        config.all_matches = true;
+        let matcher = Matcher::new(config);
+
+        let result = matcher.match_line("ERROR: upstream timeout");
+        assert!(result.matched);
+        assert_eq!(result.pattern, Some("ERROR".to_string()));
+        assert_eq!(
+            result.patterns,
+            vec!["ERROR".to_string(), "timeout".to_string()]
+        );
+
+        assert!(matcher.match_line("nothing here").patterns.is_empty());
+    }
+
+    #[test]
+    fn test_all_matches_should_notify_if_any_rule_notifies() {
+        let mut config = create_test_config("ERROR,timeout", false, false);
+        config.all_matches = true;
+        config.notify_enabled = true;
+        config.rules[0].notify = false;
+        let matcher = Matcher::new(config);
+
+        assert!(matcher.match_line("ERROR: upstream timeout").should_notify);
+        assert!(!matcher.match_line("ERROR only").should_notify);
+    }
+
+    #[test]
+    fn test_named_captures() {
+        let config = create_test_config(
+            r"(?P<user>\w+) failed from (?P<ip>\S+),timeout",
+            true,
+            false,
+        );
+        let matcher = Matcher::new(config);
+
+        let result = matcher.match_line("login: root failed from 10.0.0.7");
+        let captures: Vec<(&str, &str, &str)> = result
+            .captures
+            .iter()
+            .map(|c| (c.pattern.as_str(), c.name.as_str(), c.value.as_str()))
+            .collect();
+        let pattern = r"(?P<user>\w+) failed from (?P<ip>\S+)";
+        assert_eq!(
+            captures,
+            vec![(pattern, "user", "root"), (pattern, "ip", "10.0.0.7")]
+        );
+
+        // Rules without named groups contribute nothing
+        assert!(matcher.match_line("upstream timeout").captures.is_empty());
+    }
+
+    #[test]
+    fn test_named_captures_from_every_rule_in_all_matches_mode() {
+        let mut config = create_test_config(r"user=(?P<user>\w+),code=(?P<code>\d+)", true, false);
+        config.all_matches = true;
+        let matcher = Matcher::new(config);
+
+        let result = matcher.match_line("user=alice code=42");
+        let values: Vec<&str> = result.captures.iter().map(|c| c.value.as_str()).collect();
+        assert_eq!(values, vec!["alice", "42"]);
+
+        // First-match mode only keeps the winning rule's groups
+        let config = create_test_config(r"user=(?P<user>\w+),code=(?P<code>\d+)", true, false);
+        let result = Matcher::new(config).match_line("user=alice code=42");
+        assert_eq!(result.captures.len(), 1);
+        assert_eq!(result.captures[0].name, "user");
+    }
+
+    fn field_rule(expr: &str) -> Rule {
+        Rule::from_spec(
+            &RuleSpec::new(MatchKind::Field, expr),
+            false,
+            &HashMap::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_field_rules_match_records() {
+        let mut config = create_test_config("timeout", false, false);
+        config.rules.insert(0, field_rule("status >= 500"));
+        let matcher = Matcher::new(config);
+        let parser = Parser::new(InputFormat::Json);
+
+        let line = r#"{"status":503,"msg":"upstream timeout"}"#;
+        let result = matcher.match_record(line, parser.parse(line).as_ref());
+        assert_eq!(result.pattern, Some("status >= 500".to_string()));
+
+        // Text rules still see the raw line
+        let line = r#"{"status":200,"msg":"upstream timeout"}"#;
+        let result = matcher.match_record(line, parser.parse(line).as_ref());
+        assert_eq!(result.pattern, Some("timeout".to_string()));
+    }
+
+    #[test]
+    fn test_field_rules_need_a_record() {
+        let mut config = create_test_config("ERROR", false, false);
+        config.rules.push(field_rule("level == error"));
+        let matcher = Matcher::new(config);
+
+        // Unparsed lines fall back to text rules only
+        assert!(!matcher.match_record("level == error", None).matched);
+        assert!(matcher.match_record("ERROR plain", None).matched);
+    }
+
+    #[test]
+    fn test_all_matches_includes_field_rules() {
+        let mut config = create_test_config("ERROR", false, false);
+        config.all_matches = true;
+        config.rules.push(field_rule(r#"msg ~ /disk/"#));
+        let matcher = Matcher::new(config);
+        let parser = Parser::new(InputFormat::Json);
+
+        let line = r#"{"level":"ERROR","msg":"disk full"}"#;
+        let result = matcher.match_record(line, parser.parse(line).as_ref());
+        assert_eq!(
+            result.patterns,
+            vec!["ERROR".to_string(), "msg ~ /disk/".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parsed_severity_sets_default_color() {
+        let mut config = create_test_config("ERROR,sshd", false, false);
+        config
+            .rules
+            .push(field_rule("severity <= err and app = sshd"));
+        let matcher = Matcher::new(config);
+        let parser = Parser::new(InputFormat::Syslog);
+
+        // The keyword color (red for ERROR) gives way to the parsed severity
+        let line = "<14>Oct 11 22:14:15 web1 app[1]: ERROR retried ok";
+        let result = matcher.match_record(line, parser.parse(line).as_ref());
+        assert_eq!(result.pattern, Some("ERROR".to_string()));
+        assert_eq!(result.severity, Some(Severity::Info));
+        assert_eq!(result.color, Some(termcolor::Color::Green));
+
+        let line = "<34>Oct 11 22:14:15 web1 sshd[42]: Failed password for root";
+        let result = matcher.match_record(line, parser.parse(line).as_ref());
+        assert_eq!(result.color, Some(termcolor::Color::Red));
+
+        // Without a record the keyword color still applies
+        assert_eq!(
+            matcher.match_line("ERROR retried ok").color,
+            Some(termcolor::Color::Red)
+        );
+    }
+
+    #[test]
+    fn test_explicit_color_beats_parsed_severity() {
+        let mut spec = RuleSpec::new(MatchKind::Literal, "sshd");
+        spec.color = Some("magenta".to_string());
+        let mut config = create_test_config("", false, false);
+        config.rules = vec![Rule::from_spec(&spec, false, &HashMap::new()).unwrap()];
+        let matcher = Matcher::new(config);
+        let parser = Parser::new(InputFormat::Syslog);
+
+        let line = "<34>Oct 11 22:14:15 web1 sshd[42]: Failed password";
+        let result = matcher.match_record(line, parser.parse(line).as_ref());
+        assert_eq!(result.color, Some(termcolor::Color::Magenta));
+        assert_eq!(result.severity, Some(Severity::Critical));
+    }
 }
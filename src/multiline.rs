@@ -0,0 +1,295 @@
+//! Grouping the lines of multiline records, such as Java and Python stack
+//! traces, so that each record is matched, highlighted and notified as one
+//! unit. A line continues the record before it if it doesn't match the
+//! start pattern (`--multiline-start`) or if it is indented
+//! (`--multiline-indent`). Records are assembled separately per source, and
+//! a record with no new line for the flush timeout is complete.
+
+use crate::tail::LinePosition;
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Records longer than this are cut, so a start pattern that never matches
+/// can't hold on to a whole file
+const MAX_RECORD_LINES: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct MultilineRules {
+    /// Lines matching this start a new record; all others continue one
+    pub start: Option<Regex>,
+    /// Lines starting with whitespace continue the record before them
+    pub indent: bool,
+    /// How long a record waits for more lines before it is complete
+    pub timeout: Duration,
+}
+
+impl MultilineRules {
+    fn continues(&self, line: &str) -> bool {
+        let indented = self.indent && line.starts_with(char::is_whitespace);
+        let not_a_start = self
+            .start
+            .as_ref()
+            .is_some_and(|start| !start.is_match(line));
+        indented || not_a_start
+    }
+}
+
+#[derive(Debug)]
+pub struct RecordAssembler {
+    rules: MultilineRules,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    lines: Vec<String>,
+    /// Where the first line starts
+    position: LinePosition,
+    updated: Instant,
+}
+
+impl Pending {
+    fn record(self) -> (String, LinePosition) {
+        (self.lines.join("\n"), self.position)
+    }
+}
+
+impl RecordAssembler {
+    pub fn new(rules: MultilineRules) -> Self {
+        Self {
+            rules,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Add a line from a source, returning the record it completed, if any,
+    /// with the position of the record's first line
+    pub fn push(
+        &mut self,
+        source: &Path,
+        line: &str,
+        position: LinePosition,
+    ) -> Option<(String, LinePosition)> {
+        if self.rules.continues(line) {
+            if let Some(pending) = self.pending.get_mut(source) {
+                if pending.lines.len() < MAX_RECORD_LINES {
+                    pending.lines.push(line.to_string());
+                    pending.updated = Instant::now();
+                    return None;
+                }
+            }
+        }
+
+        let pending = Pending {
+            lines: vec![line.to_string()],
+            position,
+            updated: Instant::now(),
+        };
+        self.pending
+            .insert(source.to_path_buf(), pending)
+            .map(Pending::record)
+    }
+
+    /// Complete the record being assembled for a source, at the end of its
+    /// input or when the file changes under it
+    pub fn flush(&mut self, source: &Path) -> Option<(String, LinePosition)> {
+        self.pending.remove(source).map(Pending::record)
+    }
+
+    /// Where the record being assembled for a source starts, so nothing
+    /// past it is treated as read until the record is complete
+    pub fn pending_offset(&self, source: &Path) -> Option<u64> {
+        self.pending
+            .get(source)
+            .map(|pending| pending.position.offset)
+    }
+
+    /// Complete every record being assembled, in source order
+    pub fn flush_all(&mut self) -> Vec<(PathBuf, String, LinePosition)> {
+        let mut records: Vec<(PathBuf, String, LinePosition)> = self
+            .pending
+            .drain()
+            .map(|(source, pending)| {
+                let (record, position) = pending.record();
+                (source, record, position)
+            })
+            .collect();
+        records.sort();
+        records
+    }
+
+    /// Complete the records that have waited the flush timeout for more lines
+    pub fn flush_expired(&mut self, now: Instant) -> Vec<(PathBuf, String, LinePosition)> {
+        let expired: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| now >= pending.updated + self.rules.timeout)
+            .map(|(source, _)| source.clone())
+            .collect();
+        let mut records: Vec<(PathBuf, String, LinePosition)> = expired
+            .into_iter()
+            .filter_map(|source| {
+                let (record, position) = self.flush(&source)?;
+                Some((source, record, position))
+            })
+            .collect();
+        records.sort();
+        records
+    }
+
+    /// When the oldest waiting record times out
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending
+            .values()
+            .map(|pending| pending.updated + self.rules.timeout)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assembler(start: Option<&str>, indent: bool) -> RecordAssembler {
+        RecordAssembler::new(MultilineRules {
+            start: start.map(|start| Regex::new(start).unwrap()),
+            indent,
+            timeout: Duration::from_millis(100),
+        })
+    }
+
+    fn assemble(assembler: &mut RecordAssembler, lines: &[&str]) -> Vec<String> {
+        let source = Path::new("app.log");
+        let mut records: Vec<String> = lines
+            .iter()
+            .filter_map(|line| assembler.push(source, line, LinePosition::default()))
+            .map(|(record, _)| record)
+            .collect();
+        records.extend(assembler.flush(source).map(|(record, _)| record));
+        records
+    }
+
+    fn at_line(number: u64) -> LinePosition {
+        LinePosition {
+            offset: number * 10,
+            number: Some(number),
+        }
+    }
+
+    #[test]
+    fn test_java_stack_trace_by_indent() {
+        let mut assembler = assembler(None, true);
+        let records = assemble(
+            &mut assembler,
+            &[
+                "ERROR Request failed",
+                "java.lang.IllegalStateException: boom",
+                "\tat com.example.Service.run(Service.java:42)",
+                "\tat com.example.Main.main(Main.java:7)",
+                "INFO Next request",
+            ],
+        );
+        assert_eq!(
+            records,
+            vec![
+                "ERROR Request failed",
+                "java.lang.IllegalStateException: boom\n\tat com.example.Service.run(Service.java:42)\n\tat com.example.Main.main(Main.java:7)",
+                "INFO Next request",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_traceback_by_start_pattern() {
+        let mut assembler = assembler(Some(r"^\d{4}-\d{2}-\d{2} "), false);
+        let records = assemble(
+            &mut assembler,
+            &[
+                "2025-01-07 15:00:01 ERROR Unhandled exception",
+                "Traceback (most recent call last):",
+                "  File \"app.py\", line 3, in <module>",
+                "ValueError: bad input",
+                "2025-01-07 15:00:02 INFO Recovered",
+            ],
+        );
+        assert_eq!(records.len(), 2);
+        assert!(records[0].starts_with("2025-01-07 15:00:01 ERROR"));
+        assert!(records[0].ends_with("ValueError: bad input"));
+        assert_eq!(records[1], "2025-01-07 15:00:02 INFO Recovered");
+    }
+
+    #[test]
+    fn test_sources_are_assembled_separately() {
+        let mut assembler = assembler(None, true);
+        let (a, b) = (Path::new("a.log"), Path::new("b.log"));
+        assert_eq!(assembler.push(a, "first a", at_line(1)), None);
+        assert_eq!(assembler.push(b, "first b", at_line(1)), None);
+        assert_eq!(assembler.push(a, "  more a", at_line(2)), None);
+        assert_eq!(
+            assembler.flush_all(),
+            vec![
+                (a.to_path_buf(), "first a\n  more a".to_string(), at_line(1)),
+                (b.to_path_buf(), "first b".to_string(), at_line(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_flush_timeout() {
+        let mut assembler = assembler(None, true);
+        assert_eq!(assembler.next_deadline(), None);
+        assembler.push(Path::new("app.log"), "ERROR boom", at_line(3));
+
+        let deadline = assembler.next_deadline().unwrap();
+        assert!(assembler
+            .flush_expired(deadline - Duration::from_millis(1))
+            .is_empty());
+        assert_eq!(
+            assembler.flush_expired(deadline),
+            vec![(
+                PathBuf::from("app.log"),
+                "ERROR boom".to_string(),
+                at_line(3)
+            )]
+        );
+        assert_eq!(assembler.next_deadline(), None);
+    }
+
+    #[test]
+    fn test_pending_offset() {
+        let mut assembler = assembler(None, true);
+        let source = Path::new("app.log");
+        assert_eq!(assembler.pending_offset(source), None);
+
+        assembler.push(source, "ERROR failed", at_line(1));
+        assembler.push(source, "\tat Service.run", at_line(2));
+        assert_eq!(assembler.pending_offset(source), Some(10));
+
+        assembler.push(source, "INFO next", at_line(3));
+        assert_eq!(assembler.pending_offset(source), Some(30));
+
+        assembler.flush(source);
+        assert_eq!(assembler.pending_offset(source), None);
+    }
+
+    #[test]
+    fn test_records_are_capped() {
+        let mut assembler = assembler(None, true);
+        let source = Path::new("app.log");
+        assembler.push(source, "start", at_line(1));
+        for number in 1..MAX_RECORD_LINES {
+            let position = at_line(number as u64 + 1);
+            assert_eq!(assembler.push(source, "  more", position), None);
+        }
+        let position = at_line(MAX_RECORD_LINES as u64 + 1);
+        let (record, start) = assembler.push(source, "  one too many", position).unwrap();
+        assert_eq!(record.lines().count(), MAX_RECORD_LINES);
+        assert_eq!(start, at_line(1));
+        assert_eq!(
+            assembler.flush(source),
+            Some(("  one too many".to_string(), position))
+        );
+    }
+}
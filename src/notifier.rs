@@ -1,7 +1,11 @@
 use crate::config::Config;
+use crate::filter::value_to_string;
+use crate::matcher::NamedCapture;
+use crate::parser::Record;
 use anyhow::Result;
 #[cfg(not(target_os = "windows"))]
 use notify_rust::Notification;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
@@ -11,6 +15,8 @@ pub struct Notifier {
     config: Config,
     last_notification: Arc<Mutex<Instant>>,
     notification_count: Arc<Mutex<u32>>,
+    /// Per-rule throttle state: window start and count within the window
+    rule_windows: Arc<Mutex<HashMap<String, (Instant, u32)>>>,
     throttle_window: Duration,
 }
 
@@ -20,6 +26,7 @@ impl Notifier {
             config,
             last_notification: Arc::new(Mutex::new(Instant::now())),
             notification_count: Arc::new(Mutex::new(0)),
+            rule_windows: Arc::new(Mutex::new(HashMap::new())),
             throttle_window: Duration::from_secs(1),
         }
     }
@@ -29,43 +36,111 @@ impl Notifier {
         pattern: &str,
         line: &str,
         filename: Option<&str>,
-    ) -> Result<()> {
+    ) -> Result<bool> {
+        self.send_record_notification(&[pattern.to_string()], line, filename, None, &[])
+            .await
+    }
+
+    /// Send one notification for a line, naming every rule in `patterns`
+    /// that asks for one, and making named regex captures and the decoded
+    /// record's fields available to the title and body templates. Returns
+    /// whether a notification was sent rather than held back by
+    /// `--notify-patterns` or a throttle.
+    pub async fn send_record_notification(
+        &self,
+        patterns: &[String],
+        line: &str,
+        filename: Option<&str>,
+        record: Option<&Record>,
+        captures: &[NamedCapture],
+    ) -> Result<bool> {
         if !self.config.notify_enabled {
-            return Ok(());
+            return Ok(false);
         }
 
-        // Check if this pattern should trigger notifications
-        if !self.config.should_notify_for_pattern(pattern) {
-            return Ok(());
+        let mut notified = Vec::new();
+        for pattern in patterns {
+            // Check if this pattern should trigger notifications
+            if !self.config.should_notify_for_pattern(pattern) {
+                continue;
+            }
+            // Apply the rule's own throttle before the global one
+            if let Some(limit) = self.config.rule(pattern).and_then(|rule| rule.throttle) {
+                if !self.should_send_for_rule(pattern, limit).await {
+                    continue;
+                }
+            }
+            notified.push(pattern.as_str());
+        }
+        if notified.is_empty() {
+            return Ok(false);
         }
 
         // Throttle notifications
         if !self.should_send_notification().await {
-            return Ok(());
+            return Ok(false);
         }
 
-        // Truncate long lines
-        let truncated_line = if line.len() > 200 {
-            format!("{}...", &line[..197])
-        } else {
-            line.to_string()
-        };
-
-        // Create notification title
-        let title = if let Some(filename) = filename {
-            format!("{} detected in {}", pattern, filename)
-        } else {
-            format!("{} detected", pattern)
-        };
+        let (title, body) = self.format_notification(&notified, line, filename, record, captures);
 
         // Send notification
-        self.send_desktop_notification(&title, &truncated_line)
-            .await?;
+        self.send_desktop_notification(&title, &body).await?;
 
         // Update throttling state
         self.update_throttle_state().await;
 
-        Ok(())
+        Ok(true)
+    }
+
+    /// Build the title and body, from the configured templates if any
+    fn format_notification(
+        &self,
+        patterns: &[&str],
+        line: &str,
+        filename: Option<&str>,
+        record: Option<&Record>,
+        captures: &[NamedCapture],
+    ) -> (String, String) {
+        let pattern = patterns.join(", ");
+        // The notified rules' groups win over same-named groups of other
+        // rules, in rule order
+        let capture = |name: &str| {
+            let named = |capture: &&NamedCapture| capture.name == name;
+            patterns
+                .iter()
+                .find_map(|pattern| {
+                    captures
+                        .iter()
+                        .filter(named)
+                        .find(|capture| capture.pattern == *pattern)
+                })
+                .or_else(|| captures.iter().find(named))
+                .map(|capture| capture.value.clone())
+        };
+        let lookup = |name: &str| match name {
+            "pattern" => Some(pattern.to_string()),
+            "file" => filename.map(str::to_string),
+            "line" => Some(line.to_string()),
+            field => capture(field).or_else(|| {
+                record
+                    .and_then(|record| record.get(field))
+                    .map(value_to_string)
+            }),
+        };
+
+        let title = match &self.config.notify_title {
+            Some(template) => template.render(lookup),
+            None => match filename {
+                Some(filename) => format!("{} detected in {}", pattern, filename),
+                None => format!("{} detected", pattern),
+            },
+        };
+        let body = match &self.config.notify_body {
+            Some(template) => template.render(lookup),
+            None => line.to_string(),
+        };
+
+        (title, truncate(&body, 200))
     }
 
     async fn should_send_notification(&self) -> bool {
@@ -89,6 +164,24 @@ impl Notifier {
         }
     }
 
+    async fn should_send_for_rule(&self, rule: &str, limit: u32) -> bool {
+        let mut windows = self.rule_windows.lock().await;
+        let now = Instant::now();
+        let (window_start, count) = windows.entry(rule.to_string()).or_insert((now, 0));
+
+        if now.duration_since(*window_start) >= self.throttle_window {
+            *window_start = now;
+            *count = 0;
+        }
+
+        if *count < limit {
+            *count += 1;
+            true
+        } else {
+            false
+        }
+    }
+
     async fn update_throttle_state(&self) {
         let _count = self.notification_count.lock().await;
         // The count was already updated in should_send_notification
@@ -135,7 +228,8 @@ impl Notifier {
 
     pub async fn test_notification(&self) -> Result<()> {
         self.send_notification("TEST", "LogWatcher notification test", Some("test.log"))
-            .await
+            .await?;
+        Ok(())
     }
 
     pub fn get_notification_count(&self) -> Arc<Mutex<u32>> {
@@ -143,30 +237,31 @@ impl Notifier {
     }
 }
 
+/// Shorten text to at most `max` characters, ending in "..." when cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max.saturating_sub(3)).collect();
+    format!("{}...", kept)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::cli::Args;
+    use crate::parser::{InputFormat, Parser};
+    use crate::rule::{MatchKind, Rule, RuleSpec};
+    use crate::template::Template;
     use std::path::PathBuf;
 
     fn create_test_config(notify_enabled: bool, throttle: u32) -> Config {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: notify_enabled,
-            notify_patterns: None,
             notify_throttle: throttle,
-            dry_run: false,
-            quiet: false,
-            exclude: None,
-            no_color: false,
-            prefix_file: None,
-            poll_interval: 100,
-            buffer_size: 8192,
+            ..Default::default()
         };
         Config::from_args(&args).unwrap()
     }
@@ -260,21 +355,11 @@ mod tests {
         // Create config with specific notification patterns that exclude INFO
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
-            notify: true,
             notify_patterns: Some("ERROR,WARN".to_string()),
-            quiet: false,
-            dry_run: false,
-            exclude: None,
             poll_interval: 1000,
             buffer_size: 1024,
-            notify_throttle: 5,
-            no_color: false,
-            prefix_file: None,
+            ..Default::default()
         };
         let config = Config::from_args(&args).unwrap();
         let notifier = Notifier::new(config);
@@ -306,21 +391,10 @@ mod tests {
         // Create a notifier with a very short throttle window to test reset logic
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
-            notify: true,
-            notify_patterns: None,
-            quiet: false,
-            dry_run: false,
-            exclude: None,
             poll_interval: 1000,
             buffer_size: 1024,
-            notify_throttle: 5,
-            no_color: false,
-            prefix_file: None,
+            ..Default::default()
         };
         let config = Config::from_args(&args).unwrap();
         let notifier = Notifier::new(config);
@@ -364,4 +438,104 @@ mod tests {
         // We don't assert the result since it depends on the platform
         let _ = result;
     }
+
+    #[tokio::test]
+    async fn test_rule_throttle() {
+        let mut config = create_test_config(true, 10);
+        let mut spec = RuleSpec::new(MatchKind::Literal, "ERROR");
+        spec.throttle = Some(1);
+        config.rules = vec![Rule::from_spec(&spec, false, &HashMap::new()).unwrap()];
+        let notifier = Notifier::new(config);
+
+        assert!(notifier.should_send_for_rule("ERROR", 1).await);
+        assert!(!notifier.should_send_for_rule("ERROR", 1).await);
+        // Other rules have their own window
+        assert!(notifier.should_send_for_rule("WARN", 1).await);
+    }
+
+    #[test]
+    fn test_default_notification_text() {
+        let notifier = Notifier::new(create_test_config(true, 5));
+
+        let (title, body) =
+            notifier.format_notification(&["ERROR"], "boom", Some("app.log"), None, &[]);
+        assert_eq!(title, "ERROR detected in app.log");
+        assert_eq!(body, "boom");
+
+        let (_, body) = notifier.format_notification(&["ERROR"], &"é".repeat(250), None, None, &[]);
+        assert_eq!(body.chars().count(), 200);
+        assert!(body.ends_with("..."));
+    }
+
+    #[test]
+    fn test_notification_templates_use_record_fields() {
+        let mut config = create_test_config(true, 5);
+        config.notify_title = Some(Template::parse("{status} on {file}").unwrap());
+        config.notify_body = Some(Template::parse("{method} {path} ({pattern})").unwrap());
+        let notifier = Notifier::new(config);
+
+        let line = r#"10.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET /api HTTP/1.1" 503 12"#;
+        let record = Parser::new(InputFormat::Access).parse(line);
+        let (title, body) =
+            notifier.format_notification(&["5xx"], line, Some("access.log"), record.as_ref(), &[]);
+        assert_eq!(title, "503 on access.log");
+        assert_eq!(body, "GET /api (5xx)");
+    }
+
+    #[test]
+    fn test_notification_templates_use_captures() {
+        let mut config = create_test_config(true, 5);
+        config.notify_title = Some(Template::parse("Login failure for {user}").unwrap());
+        config.notify_body = Some(Template::parse("{user} from {ip}").unwrap());
+        let notifier = Notifier::new(config);
+
+        let capture = |pattern: &str, name: &str, value: &str| NamedCapture {
+            pattern: pattern.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+        let captures = [
+            capture("sudo", "user", "root"),
+            capture("sshd", "user", "alice"),
+            capture("sshd", "ip", "10.0.0.7"),
+        ];
+        let (title, body) = notifier.format_notification(&["sshd"], "line", None, None, &captures);
+        assert_eq!(title, "Login failure for alice");
+        assert_eq!(body, "alice from 10.0.0.7");
+    }
+
+    #[test]
+    fn test_notification_names_every_rule() {
+        let notifier = Notifier::new(create_test_config(true, 5));
+
+        let (title, _) = notifier.format_notification(
+            &["ERROR", "timeout"],
+            "ERROR: upstream timeout",
+            Some("app.log"),
+            None,
+            &[],
+        );
+        assert_eq!(title, "ERROR, timeout detected in app.log");
+    }
+
+    #[tokio::test]
+    async fn test_held_back_notifications_are_not_sent() {
+        let notifier = Notifier::new(create_test_config(false, 5));
+        assert!(!notifier
+            .send_notification("ERROR", "Test message", None)
+            .await
+            .unwrap());
+
+        // INFO isn't a notifying rule, and the global throttle allows none
+        let notifier = Notifier::new(create_test_config(true, 0));
+        let patterns = ["INFO".to_string()];
+        assert!(!notifier
+            .send_record_notification(&patterns, "INFO", None, None, &[])
+            .await
+            .unwrap());
+        assert!(!notifier
+            .send_notification("ERROR", "Test message", None)
+            .await
+            .unwrap());
+    }
 }
@@ -0,0 +1,409 @@
+//! Matched lines recorded to a file (`--output-file`), for servers where
+//! nobody watches the terminal. The file is written next to the terminal
+//! output, as text or JSON, and rotates itself like logrotate: once it
+//! grows past a size or an age it is renamed to `matches.log.1`, older
+//! generations move up one (`.1` to `.2`, ...) and the oldest beyond the
+//! number kept is overwritten.
+
+use crate::highlighter::{format_value, line_event, OutputFormat};
+use crate::matcher::MatchResult;
+use crate::parser::Record;
+use crate::tail::LinePosition;
+use crate::template::Template;
+use anyhow::{anyhow, Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Rotated generations kept unless `--output-file-keep` says otherwise
+pub const DEFAULT_KEEP: usize = 5;
+
+/// Where and how lines are recorded
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputFileOptions {
+    pub path: PathBuf,
+    pub format: OutputFormat,
+    /// Record every line read, not only matches (`--output-file-all`)
+    pub all_lines: bool,
+    /// Rotate before the file grows past this many bytes
+    pub max_size: Option<u64>,
+    /// Rotate once the file is this old
+    pub max_age: Option<Duration>,
+    /// Rotated generations kept; 0 starts the file over instead
+    pub keep: usize,
+}
+
+#[derive(Debug)]
+pub struct OutputFile {
+    options: OutputFileOptions,
+    /// Layout of text records (`--format`)
+    template: Option<Template>,
+    /// Start text records with `[file] `
+    prefix_files: bool,
+    file: File,
+    size: u64,
+    /// When the current file was started
+    created: SystemTime,
+}
+
+impl OutputFile {
+    /// Open the file for appending, creating it if needed. An existing file
+    /// keeps its size and age towards the next rotation.
+    pub fn open(
+        options: OutputFileOptions,
+        template: Option<Template>,
+        prefix_files: bool,
+    ) -> Result<Self> {
+        let (file, size, created) = open_append(&options.path)?;
+        Ok(Self {
+            options,
+            template,
+            prefix_files,
+            file,
+            size,
+            created,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.options.path
+    }
+
+    /// Record a line read from `file_path` if it matched, or whatever it is
+    /// with `--output-file-all`
+    pub fn record(
+        &mut self,
+        file_path: &Path,
+        position: LinePosition,
+        line: &str,
+        record: Option<&Record>,
+        match_result: &MatchResult,
+    ) -> Result<()> {
+        if !match_result.matched && !self.options.all_lines {
+            return Ok(());
+        }
+
+        let mut text = match self.options.format {
+            OutputFormat::Json => {
+                line_event(file_path, position, line, record, match_result).to_string()
+            }
+            OutputFormat::Text => match &self.template {
+                Some(template) => template.render(|name| {
+                    format_value(name, file_path, position, line, record, match_result)
+                }),
+                None if self.prefix_files => {
+                    let filename = file_path
+                        .file_name()
+                        .map(|name| name.to_string_lossy())
+                        .unwrap_or_else(|| file_path.to_string_lossy());
+                    format!("[{}] {}", filename, line)
+                }
+                None => line.to_string(),
+            },
+        };
+        text.push('\n');
+        self.write(&text, SystemTime::now())
+    }
+
+    /// Append a record, rotating first if it is due. A record larger than
+    /// `max_size` still goes into a file of its own.
+    fn write(&mut self, text: &str, now: SystemTime) -> Result<()> {
+        let len = text.len() as u64;
+        let too_big = self
+            .options
+            .max_size
+            .is_some_and(|max_size| self.size + len > max_size);
+        let too_old = self.options.max_age.is_some_and(|max_age| {
+            now.duration_since(self.created)
+                .is_ok_and(|age| age >= max_age)
+        });
+        if self.size > 0 && (too_big || too_old) {
+            self.rotate()?;
+        }
+
+        self.file
+            .write_all(text.as_bytes())
+            .with_context(|| format!("Failed to write to {}", self.options.path.display()))?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Move the file and its generations up one and start a new file
+    fn rotate(&mut self) -> Result<()> {
+        let path = &self.options.path;
+        let keep = self.options.keep;
+        if keep == 0 {
+            remove_if_exists(path)?;
+        } else {
+            for generation in (1..keep).rev() {
+                rename_if_exists(
+                    &rotated_path(path, generation),
+                    &rotated_path(path, generation + 1),
+                )?;
+            }
+            rename_if_exists(path, &rotated_path(path, 1))?;
+        }
+
+        let (file, size, created) = open_append(path)?;
+        self.file = file;
+        self.size = size;
+        self.created = created;
+        Ok(())
+    }
+}
+
+/// `matches.log.2` for generation 2 of `matches.log`
+pub fn rotated_path(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", generation));
+    PathBuf::from(name)
+}
+
+/// A size in bytes, with an optional K, M or G suffix (powers of 1024), as
+/// in `10M`
+pub fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match value[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(anyhow!("expected a size like 10M, got '{}'", value)),
+    };
+    let size: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("expected a size like 10M, got '{}'", value))?;
+    match size.checked_mul(multiplier) {
+        Some(0) => Err(anyhow!("the size must be more than 0")),
+        Some(size) => Ok(size),
+        None => Err(anyhow!("'{}' is too large", value)),
+    }
+}
+
+fn open_append(path: &Path) -> Result<(File, u64, SystemTime)> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open output file: {}", path.display()))?;
+    let metadata = file.metadata()?;
+    // Not every filesystem records when a file was created
+    let created = if metadata.len() > 0 {
+        metadata.created().unwrap_or_else(|_| SystemTime::now())
+    } else {
+        SystemTime::now()
+    };
+    Ok((file, metadata.len(), created))
+}
+
+fn rename_if_exists(from: &Path, to: &Path) -> Result<()> {
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e)
+            .with_context(|| format!("Failed to rotate {} to {}", from.display(), to.display())),
+        _ => Ok(()),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn options(dir: &TempDir) -> OutputFileOptions {
+        OutputFileOptions {
+            path: dir.path().join("matches.log"),
+            format: OutputFormat::Text,
+            all_lines: false,
+            max_size: None,
+            max_age: None,
+            keep: DEFAULT_KEEP,
+        }
+    }
+
+    fn matched(pattern: &str) -> MatchResult {
+        MatchResult {
+            matched: true,
+            pattern: Some(pattern.to_string()),
+            patterns: vec![pattern.to_string()],
+            ..MatchResult::default()
+        }
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap_or_default()
+    }
+
+    #[test]
+    fn test_records_matches_only() {
+        let dir = TempDir::new().unwrap();
+        let mut output = OutputFile::open(options(&dir), None, true).unwrap();
+        let source = Path::new("/var/log/app.log");
+        let position = LinePosition::start();
+        output
+            .record(source, position, "ERROR disk full", None, &matched("ERROR"))
+            .unwrap();
+        output
+            .record(source, position, "all good", None, &MatchResult::default())
+            .unwrap();
+        assert_eq!(read(output.path()), "[app.log] ERROR disk full\n");
+    }
+
+    #[test]
+    fn test_records_all_lines_as_json() {
+        let dir = TempDir::new().unwrap();
+        let options = OutputFileOptions {
+            format: OutputFormat::Json,
+            all_lines: true,
+            ..options(&dir)
+        };
+        let mut output = OutputFile::open(options, None, false).unwrap();
+        let source = Path::new("app.log");
+        output
+            .record(
+                source,
+                LinePosition::start(),
+                "ERROR x",
+                None,
+                &matched("ERROR"),
+            )
+            .unwrap();
+        output
+            .record(
+                source,
+                LinePosition::start().next(8),
+                "ok",
+                None,
+                &MatchResult::default(),
+            )
+            .unwrap();
+
+        let events: Vec<serde_json::Value> = read(output.path())
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["rules"], serde_json::json!(["ERROR"]));
+        assert_eq!(events[1]["matched"], false);
+        assert_eq!(events[1]["line_number"], 2);
+    }
+
+    #[test]
+    fn test_records_with_template() {
+        let dir = TempDir::new().unwrap();
+        let template = Template::parse("{rule}: {line}").unwrap();
+        let mut output = OutputFile::open(options(&dir), Some(template), true).unwrap();
+        output
+            .record(
+                Path::new("app.log"),
+                LinePosition::start(),
+                "WARN slow",
+                None,
+                &matched("WARN"),
+            )
+            .unwrap();
+        assert_eq!(read(output.path()), "WARN: WARN slow\n");
+    }
+
+    #[test]
+    fn test_rotates_by_size_and_keeps_generations() {
+        let dir = TempDir::new().unwrap();
+        let options = OutputFileOptions {
+            max_size: Some(10),
+            keep: 2,
+            ..options(&dir)
+        };
+        let path = options.path.clone();
+        let mut output = OutputFile::open(options, None, false).unwrap();
+        let now = SystemTime::now();
+        for line in ["one\n", "two\n", "three\n", "four\n", "five\n"] {
+            output.write(line, now).unwrap();
+        }
+
+        assert_eq!(read(&path), "four\nfive\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "three\n");
+        assert_eq!(read(&rotated_path(&path, 2)), "one\ntwo\n");
+        assert!(!rotated_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn test_oversized_record_gets_its_own_file() {
+        let dir = TempDir::new().unwrap();
+        let options = OutputFileOptions {
+            max_size: Some(4),
+            ..options(&dir)
+        };
+        let path = options.path.clone();
+        let mut output = OutputFile::open(options, None, false).unwrap();
+        let now = SystemTime::now();
+        output.write("a long line\n", now).unwrap();
+        output.write("b\n", now).unwrap();
+
+        assert_eq!(read(&path), "b\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "a long line\n");
+    }
+
+    #[test]
+    fn test_rotates_by_age() {
+        let dir = TempDir::new().unwrap();
+        let options = OutputFileOptions {
+            max_age: Some(Duration::from_secs(3600)),
+            keep: 0,
+            ..options(&dir)
+        };
+        let path = options.path.clone();
+        let mut output = OutputFile::open(options, None, false).unwrap();
+        let now = SystemTime::now();
+        output.write("old\n", now).unwrap();
+        output
+            .write("recent\n", now + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(read(&path), "old\nrecent\n");
+
+        // Nothing is kept with keep = 0; the file starts over
+        output
+            .write("new\n", now + Duration::from_secs(7200))
+            .unwrap();
+        assert_eq!(read(&path), "new\n");
+        assert!(!rotated_path(&path, 1).exists());
+    }
+
+    #[test]
+    fn test_appends_to_existing_file() {
+        let dir = TempDir::new().unwrap();
+        let options = OutputFileOptions {
+            max_size: Some(10),
+            ..options(&dir)
+        };
+        std::fs::write(&options.path, "earlier\n").unwrap();
+        let path = options.path.clone();
+        let mut output = OutputFile::open(options, None, false).unwrap();
+        output.write("later\n", SystemTime::now()).unwrap();
+
+        assert_eq!(read(&path), "later\n");
+        assert_eq!(read(&rotated_path(&path, 1)), "earlier\n");
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("64k").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("10M").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("1GB").unwrap(), 1 << 30);
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("99999999999G").is_err());
+    }
+}
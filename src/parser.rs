@@ -0,0 +1,321 @@
+use crate::access_log::AccessLogFormat;
+use crate::rule::Severity;
+use crate::syslog;
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// How log lines are decoded before matching
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    /// Plain text; no structured fields
+    #[default]
+    Raw,
+    /// JSON objects, one per line
+    Json,
+    /// `key=value` pairs, as written by logfmt loggers
+    Logfmt,
+    /// Syslog lines (RFC 5424 or RFC 3164)
+    Syslog,
+    /// Web server access logs (Combined/Common Log Format or `--log-format`)
+    Access,
+    /// Detect the format of each line
+    Auto,
+}
+
+/// A log line decoded into named fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Format the line was decoded from
+    pub format: InputFormat,
+    /// Fields in the order they appeared in the line
+    pub fields: Map<String, Value>,
+}
+
+impl Record {
+    /// Look up a field. An exact key wins; otherwise dots descend into
+    /// nested objects (`http.status`).
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        if let Some(value) = self.fields.get(path) {
+            return Some(value);
+        }
+
+        let mut parts = path.split('.');
+        let mut current = self.fields.get(parts.next()?)?;
+        for part in parts {
+            current = current.as_object()?.get(part)?;
+        }
+        Some(current)
+    }
+
+    /// Severity carried by the record's `severity` or `level` field
+    pub fn severity(&self) -> Option<Severity> {
+        ["severity", "level"]
+            .into_iter()
+            .find_map(|key| self.fields.get(key)?.as_str()?.parse().ok())
+    }
+}
+
+/// Decodes lines into records according to the configured input format
+#[derive(Debug, Clone)]
+pub struct Parser {
+    format: InputFormat,
+    /// Access log formats to try, in order
+    access_formats: Vec<AccessLogFormat>,
+}
+
+impl Parser {
+    pub fn new(format: InputFormat) -> Self {
+        Self {
+            format,
+            access_formats: vec![AccessLogFormat::combined(), AccessLogFormat::common()],
+        }
+    }
+
+    /// Decode access logs with a custom format instead of Combined/Common
+    pub fn with_log_format(mut self, log_format: Option<AccessLogFormat>) -> Self {
+        if let Some(log_format) = log_format {
+            self.access_formats = vec![log_format];
+        }
+        self
+    }
+
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+
+    /// Decode a line; `None` means it should be matched as raw text
+    pub fn parse(&self, line: &str) -> Option<Record> {
+        match self.format {
+            InputFormat::Raw => None,
+            InputFormat::Json => parse_json(line),
+            InputFormat::Logfmt => parse_logfmt(line, true),
+            InputFormat::Syslog => parse_syslog(line),
+            InputFormat::Access => self.parse_access(line),
+            // Plain text is valid logfmt made of bare keys, so detection only
+            // accepts lines where every key has a value
+            InputFormat::Auto => parse_json(line)
+                .or_else(|| parse_syslog(line))
+                .or_else(|| self.parse_access(line))
+                .or_else(|| parse_logfmt(line, false)),
+        }
+    }
+
+    fn parse_access(&self, line: &str) -> Option<Record> {
+        self.access_formats
+            .iter()
+            .find_map(|format| format.decode(line))
+            .map(|fields| Record {
+                format: InputFormat::Access,
+                fields,
+            })
+    }
+}
+
+fn parse_json(line: &str) -> Option<Record> {
+    let trimmed = line.trim_start();
+    if !trimmed.starts_with('{') {
+        return None;
+    }
+
+    match serde_json::from_str::<Value>(trimmed) {
+        Ok(Value::Object(fields)) => Some(Record {
+            format: InputFormat::Json,
+            fields,
+        }),
+        _ => None,
+    }
+}
+
+fn parse_syslog(line: &str) -> Option<Record> {
+    syslog::parse(line).map(|fields| Record {
+        format: InputFormat::Syslog,
+        fields,
+    })
+}
+
+/// Decode a logfmt line. Values stay strings (field comparisons parse
+/// numbers on demand); a key without `=` is a flag and decodes to `true`.
+fn parse_logfmt(line: &str, allow_bare_keys: bool) -> Option<Record> {
+    let mut fields = Map::new();
+    let mut has_pair = false;
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut key = String::new();
+        while let Some(c) = chars.next_if(|&c| !c.is_whitespace() && c != '=') {
+            if c == '"' {
+                return None;
+            }
+            key.push(c);
+        }
+        if key.is_empty() {
+            return None;
+        }
+
+        if chars.next_if_eq(&'=').is_none() {
+            if !allow_bare_keys {
+                return None;
+            }
+            fields.insert(key, Value::Bool(true));
+            continue;
+        }
+
+        let mut value = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        'n' => value.push('\n'),
+                        't' => value.push('\t'),
+                        'r' => value.push('\r'),
+                        other => value.push(other),
+                    },
+                    c => value.push(c),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                value.push(c);
+            }
+        }
+
+        has_pair = true;
+        fields.insert(key, Value::String(value));
+    }
+
+    has_pair.then_some(Record {
+        format: InputFormat::Logfmt,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_json_line() {
+        let parser = Parser::new(InputFormat::Json);
+        let record = parser
+            .parse(r#"{"level":"error","status":503,"http":{"path":"/api"}}"#)
+            .unwrap();
+
+        assert_eq!(record.format, InputFormat::Json);
+        assert_eq!(record.get("level"), Some(&json!("error")));
+        assert_eq!(record.get("status"), Some(&json!(503)));
+        assert_eq!(record.get("http.path"), Some(&json!("/api")));
+        assert_eq!(record.get("http.method"), None);
+    }
+
+    #[test]
+    fn test_dotted_key_wins_over_nesting() {
+        let parser = Parser::new(InputFormat::Json);
+        let record = parser
+            .parse(r#"{"http.path":"flat","http":{"path":"nested"}}"#)
+            .unwrap();
+        assert_eq!(record.get("http.path"), Some(&json!("flat")));
+    }
+
+    #[test]
+    fn test_unparseable_lines_fall_back() {
+        let parser = Parser::new(InputFormat::Json);
+        assert!(parser.parse("ERROR plain text").is_none());
+        assert!(parser.parse(r#"{"truncated": "#).is_none());
+        // Valid JSON that isn't an object has no fields to match on
+        assert!(parser.parse("[1, 2, 3]").is_none());
+    }
+
+    #[test]
+    fn test_raw_never_parses() {
+        let parser = Parser::new(InputFormat::Raw);
+        assert!(parser.parse(r#"{"level":"error"}"#).is_none());
+    }
+
+    #[test]
+    fn test_parse_logfmt_line() {
+        let parser = Parser::new(InputFormat::Logfmt);
+        let record = parser
+            .parse(r#"level=warn msg="disk low" pct=93 path="C:\\tmp" empty= debug"#)
+            .unwrap();
+
+        assert_eq!(record.format, InputFormat::Logfmt);
+        assert_eq!(record.get("level"), Some(&json!("warn")));
+        assert_eq!(record.get("msg"), Some(&json!("disk low")));
+        assert_eq!(record.get("pct"), Some(&json!("93")));
+        assert_eq!(record.get("path"), Some(&json!(r"C:\tmp")));
+        assert_eq!(record.get("empty"), Some(&json!("")));
+        assert_eq!(record.get("debug"), Some(&json!(true)));
+
+        let keys: Vec<&str> = record.fields.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["level", "msg", "pct", "path", "empty", "debug"]);
+    }
+
+    #[test]
+    fn test_invalid_logfmt() {
+        let parser = Parser::new(InputFormat::Logfmt);
+        assert!(parser.parse(r#"msg="unterminated"#).is_none());
+        assert!(parser.parse("=value").is_none());
+        assert!(parser.parse("just some words").is_none());
+        assert!(parser.parse("").is_none());
+    }
+
+    #[test]
+    fn test_auto_detects_format() {
+        let parser = Parser::new(InputFormat::Auto);
+
+        let json = parser.parse(r#"{"level":"error"}"#).unwrap();
+        assert_eq!(json.format, InputFormat::Json);
+
+        let logfmt = parser.parse("level=error status=503").unwrap();
+        assert_eq!(logfmt.format, InputFormat::Logfmt);
+
+        let syslog = parser
+            .parse("<34>Oct 11 22:14:15 web1 sshd[42]: Failed password")
+            .unwrap();
+        assert_eq!(syslog.format, InputFormat::Syslog);
+        assert_eq!(syslog.severity(), Some(Severity::Critical));
+
+        let access = parser
+            .parse(r#"10.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 502 12"#)
+            .unwrap();
+        assert_eq!(access.format, InputFormat::Access);
+        assert_eq!(access.get("status"), Some(&json!(502)));
+
+        // Plain text with the odd `key=value` in it stays raw
+        assert!(parser.parse("ERROR request failed id=42").is_none());
+        assert!(parser.parse("ERROR plain text").is_none());
+    }
+
+    #[test]
+    fn test_record_severity() {
+        let parser = Parser::new(InputFormat::Auto);
+        let severity = |line: &str| parser.parse(line).unwrap().severity();
+
+        assert_eq!(severity(r#"{"level":"warn"}"#), Some(Severity::Warning));
+        assert_eq!(severity("level=err msg=x"), Some(Severity::Error));
+        assert_eq!(severity(r#"{"level":30}"#), None);
+        assert_eq!(severity(r#"{"msg":"x"}"#), None);
+    }
+
+    #[test]
+    fn test_custom_log_format() {
+        let format = AccessLogFormat::parse("$remote_addr $status $request_time").unwrap();
+        let parser = Parser::new(InputFormat::Access).with_log_format(Some(format));
+
+        let record = parser.parse("10.0.0.1 504 2.5").unwrap();
+        assert_eq!(record.get("request_time"), Some(&json!(2.5)));
+        // The custom format replaces the built-in ones
+        assert!(parser
+            .parse(r#"10.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.1" 502 12"#)
+            .is_none());
+    }
+}
@@ -0,0 +1,480 @@
+use crate::config::Config;
+use crate::filter::Expr;
+use crate::parser::Record;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use termcolor::Color;
+
+/// How a rule's pattern is compared against a line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    /// Plain substring match
+    #[default]
+    Literal,
+    /// Regular expression, matched anywhere in the line
+    Regex,
+    /// Shell-style wildcard (`*`, `?`, `[...]`) matched against the whole line
+    Glob,
+    /// Field expression evaluated against structured records (`status >= 500`)
+    Field,
+}
+
+/// Log severity, ordered from most to least severe (syslog levels 0-7)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Severity {
+    Emergency,
+    Alert,
+    Critical,
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+impl Severity {
+    /// Default highlight color for this severity
+    pub fn color(self) -> Color {
+        match self {
+            Severity::Emergency | Severity::Alert | Severity::Critical | Severity::Error => {
+                Color::Red
+            }
+            Severity::Warning => Color::Yellow,
+            Severity::Notice => Color::Blue,
+            Severity::Info => Color::Green,
+            Severity::Debug => Color::Cyan,
+        }
+    }
+
+    /// Syslog severity level, 0 (emergency) to 7 (debug)
+    pub fn rank(self) -> u8 {
+        self as u8
+    }
+
+    /// Severity for a syslog level; values above 7 saturate to debug
+    pub fn from_rank(rank: u8) -> Self {
+        match rank {
+            0 => Severity::Emergency,
+            1 => Severity::Alert,
+            2 => Severity::Critical,
+            3 => Severity::Error,
+            4 => Severity::Warning,
+            5 => Severity::Notice,
+            6 => Severity::Info,
+            _ => Severity::Debug,
+        }
+    }
+
+    /// Canonical lowercase name
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Emergency => "emergency",
+            Severity::Alert => "alert",
+            Severity::Critical => "critical",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Notice => "notice",
+            Severity::Info => "info",
+            Severity::Debug => "debug",
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "emerg" | "emergency" | "panic" => Ok(Severity::Emergency),
+            "alert" => Ok(Severity::Alert),
+            "crit" | "critical" | "fatal" => Ok(Severity::Critical),
+            "err" | "error" => Ok(Severity::Error),
+            "warn" | "warning" => Ok(Severity::Warning),
+            "notice" => Ok(Severity::Notice),
+            "info" | "informational" => Ok(Severity::Info),
+            "debug" | "trace" => Ok(Severity::Debug),
+            _ => Err(anyhow::anyhow!("Unknown severity: {}", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Severity {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Declarative rule definition, as written in a `[[rules]]` table of the
+/// config file or derived from a `--pattern` entry
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleSpec {
+    /// Display name; defaults to the pattern itself
+    pub name: Option<String>,
+    #[serde(default)]
+    pub kind: MatchKind,
+    pub pattern: String,
+    /// Defaults to the global `--case-insensitive` setting
+    pub case_insensitive: Option<bool>,
+    pub color: Option<String>,
+    /// Whether matches trigger desktop notifications (default: true)
+    pub notify: Option<bool>,
+    /// Maximum notifications per second for this rule
+    pub throttle: Option<u32>,
+    pub severity: Option<Severity>,
+}
+
+impl RuleSpec {
+    pub fn new(kind: MatchKind, pattern: &str) -> Self {
+        Self {
+            name: None,
+            kind,
+            pattern: pattern.to_string(),
+            case_insensitive: None,
+            color: None,
+            notify: None,
+            throttle: None,
+            severity: None,
+        }
+    }
+}
+
+/// A compiled matching rule
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub kind: MatchKind,
+    pub pattern: String,
+    pub case_insensitive: bool,
+    pub color: Option<Color>,
+    /// Whether `color` was set by the rule or a color mapping, rather than
+    /// derived from a level keyword or the rule's severity
+    pub explicit_color: bool,
+    pub notify: bool,
+    pub throttle: Option<u32>,
+    pub severity: Option<Severity>,
+    /// Lowercased pattern for case-insensitive literal matching
+    pattern_lowercase: String,
+    /// Compiled expression for regex and glob rules
+    regex: Option<Regex>,
+    /// Parsed expression for field rules
+    expr: Option<Expr>,
+}
+
+impl Rule {
+    /// Compile a rule from its spec.
+    ///
+    /// `case_insensitive` is the global default, and `color_mappings` supplies
+    /// colors for rules that don't set one explicitly. Rules without either
+    /// fall back to the built-in keyword colors, then to their severity.
+    pub fn from_spec(
+        spec: &RuleSpec,
+        case_insensitive: bool,
+        color_mappings: &HashMap<String, Color>,
+    ) -> Result<Self> {
+        let name = spec.name.clone().unwrap_or_else(|| spec.pattern.clone());
+        let case_insensitive = spec.case_insensitive.unwrap_or(case_insensitive);
+
+        let regex = match spec.kind {
+            MatchKind::Literal | MatchKind::Field => None,
+            MatchKind::Regex => Some(Config::compile_regex(&spec.pattern, case_insensitive)?),
+            MatchKind::Glob => Some(
+                Config::compile_regex(&glob_to_regex(&spec.pattern), case_insensitive)
+                    .with_context(|| format!("Invalid glob pattern: {}", spec.pattern))?,
+            ),
+        };
+
+        let expr = match spec.kind {
+            MatchKind::Field => Some(
+                Expr::parse(&spec.pattern)
+                    .with_context(|| format!("Invalid field rule: {}", spec.pattern))?,
+            ),
+            _ => None,
+        };
+
+        let explicit = match &spec.color {
+            Some(color_name) => Some(Config::parse_color(color_name)?),
+            None => color_mappings
+                .get(&name)
+                .or_else(|| color_mappings.get(&spec.pattern))
+                .copied(),
+        };
+        let color = explicit.or_else(|| {
+            Config::default_color(&name)
+                .or_else(|| Config::default_color(&spec.pattern))
+                .or_else(|| spec.severity.map(Severity::color))
+        });
+
+        Ok(Self {
+            name,
+            kind: spec.kind,
+            pattern: spec.pattern.clone(),
+            case_insensitive,
+            color,
+            explicit_color: explicit.is_some(),
+            notify: spec.notify.unwrap_or(true),
+            throttle: spec.throttle,
+            severity: spec.severity,
+            pattern_lowercase: spec.pattern.to_lowercase(),
+            regex,
+            expr,
+        })
+    }
+
+    /// Convenience constructor for a case-sensitive literal rule
+    pub fn literal(pattern: &str) -> Self {
+        Self::from_spec(
+            &RuleSpec::new(MatchKind::Literal, pattern),
+            false,
+            &HashMap::new(),
+        )
+        .expect("literal rules always compile")
+    }
+
+    /// Check the rule against a line. `line_lowercase` must be the lowercased
+    /// line when the caller has one; it is computed on demand otherwise.
+    /// Field rules never match raw text.
+    pub fn is_match(&self, line: &str, line_lowercase: Option<&str>) -> bool {
+        if self.kind == MatchKind::Field {
+            return false;
+        }
+        match &self.regex {
+            Some(regex) => regex.is_match(line),
+            None if self.case_insensitive => match line_lowercase {
+                Some(lowered) => lowered.contains(&self.pattern_lowercase),
+                None => line.to_lowercase().contains(&self.pattern_lowercase),
+            },
+            None => line.contains(self.pattern.as_str()),
+        }
+    }
+
+    /// Compiled expression for regex and glob rules
+    pub fn regex(&self) -> Option<&Regex> {
+        self.regex.as_ref()
+    }
+
+    /// Evaluate a field rule against a decoded record
+    pub fn matches_record(&self, record: &Record) -> bool {
+        self.expr.as_ref().is_some_and(|expr| expr.evaluate(record))
+    }
+}
+
+/// Translate a shell-style glob into an anchored regular expression
+pub(crate) fn glob_to_regex(glob: &str) -> String {
+    // `(?s)` lets `*` span embedded newlines, like a shell glob would
+    let mut regex = String::from("(?s)^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                // Copy a character class through, translating `[!...]` negation
+                let mut class = String::from("[");
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    class.push('^');
+                }
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        class.push('\\');
+                    }
+                    class.push(c);
+                }
+                if closed {
+                    class.push(']');
+                    regex.push_str(&class);
+                } else {
+                    // Unterminated class: treat the bracket literally
+                    regex.push_str(&regex::escape(&format!("[{}", &class[1..])));
+                }
+            }
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex.push('$');
+    regex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{InputFormat, Parser};
+
+    fn compile(spec: RuleSpec) -> Rule {
+        Rule::from_spec(&spec, false, &HashMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_literal_rule() {
+        let rule = Rule::literal("ERROR");
+        assert_eq!(rule.name, "ERROR");
+        assert!(rule.notify);
+        assert!(rule.is_match("an ERROR here", None));
+        assert!(!rule.is_match("an error here", None));
+    }
+
+    #[test]
+    fn test_case_insensitive_literal_rule() {
+        let mut spec = RuleSpec::new(MatchKind::Literal, "Timeout");
+        spec.case_insensitive = Some(true);
+        let rule = compile(spec);
+
+        assert!(rule.is_match("request TIMEOUT", None));
+        assert!(rule.is_match("request TIMEOUT", Some("request timeout")));
+    }
+
+    #[test]
+    fn test_regex_rule() {
+        let rule = compile(RuleSpec::new(MatchKind::Regex, r"status=5\d\d"));
+        assert!(rule.is_match("GET / status=503", None));
+        assert!(!rule.is_match("GET / status=404", None));
+    }
+
+    #[test]
+    fn test_invalid_regex_rule() {
+        let result = Rule::from_spec(
+            &RuleSpec::new(MatchKind::Regex, "[invalid"),
+            false,
+            &HashMap::new(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_glob_rule() {
+        let rule = compile(RuleSpec::new(MatchKind::Glob, "*connection ref?sed*"));
+        assert!(rule.is_match("db: connection refused (retry 3)", None));
+        assert!(!rule.is_match("db: connection reset", None));
+
+        let anchored = compile(RuleSpec::new(MatchKind::Glob, "WARN*"));
+        assert!(anchored.is_match("WARN disk low", None));
+        assert!(!anchored.is_match("[x] WARN disk low", None));
+    }
+
+    #[test]
+    fn test_field_rule() {
+        let rule = compile(RuleSpec::new(MatchKind::Field, "status >= 500"));
+        let parser = Parser::new(InputFormat::Json);
+
+        assert!(rule.matches_record(&parser.parse(r#"{"status":503}"#).unwrap()));
+        assert!(!rule.matches_record(&parser.parse(r#"{"status":200}"#).unwrap()));
+        assert!(!rule.is_match("status >= 500", None));
+
+        let invalid = Rule::from_spec(
+            &RuleSpec::new(MatchKind::Field, "status >="),
+            false,
+            &HashMap::new(),
+        );
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert_eq!(glob_to_regex("a*b?c"), "(?s)^a.*b.c$");
+        assert_eq!(glob_to_regex("[!0-9]x"), "(?s)^[^0-9]x$");
+        assert_eq!(glob_to_regex("a.b"), r"(?s)^a\.b$");
+        assert_eq!(glob_to_regex("[abc"), r"(?s)^\[abc$");
+    }
+
+    #[test]
+    fn test_rule_color_resolution() {
+        let mut mappings = HashMap::new();
+        mappings.insert("ERROR".to_string(), Color::Red);
+
+        let rule = Rule::from_spec(
+            &RuleSpec::new(MatchKind::Literal, "ERROR"),
+            false,
+            &mappings,
+        )
+        .unwrap();
+        assert_eq!(rule.color, Some(Color::Red));
+
+        let mut spec = RuleSpec::new(MatchKind::Literal, "ERROR");
+        spec.color = Some("blue".to_string());
+        let rule = Rule::from_spec(&spec, false, &mappings).unwrap();
+        assert_eq!(rule.color, Some(Color::Blue));
+
+        let mut spec = RuleSpec::new(MatchKind::Literal, "disk low");
+        spec.severity = Some(Severity::Warning);
+        let rule = Rule::from_spec(&spec, false, &mappings).unwrap();
+        assert_eq!(rule.color, Some(Color::Yellow));
+        assert!(!rule.explicit_color);
+    }
+
+    #[test]
+    fn test_keyword_colors_are_not_explicit() {
+        let rule = Rule::literal("WARN");
+        assert_eq!(rule.color, Some(Color::Yellow));
+        assert!(!rule.explicit_color);
+
+        let mut mappings = HashMap::new();
+        mappings.insert("WARN".to_string(), Color::Magenta);
+        let rule =
+            Rule::from_spec(&RuleSpec::new(MatchKind::Literal, "WARN"), false, &mappings).unwrap();
+        assert_eq!(rule.color, Some(Color::Magenta));
+        assert!(rule.explicit_color);
+    }
+
+    #[test]
+    fn test_severity_parsing_and_order() {
+        assert_eq!("err".parse::<Severity>().unwrap(), Severity::Error);
+        assert_eq!("WARN".parse::<Severity>().unwrap(), Severity::Warning);
+        assert_eq!("fatal".parse::<Severity>().unwrap(), Severity::Critical);
+        assert!("loud".parse::<Severity>().is_err());
+
+        assert!(Severity::Critical < Severity::Error);
+        assert!(Severity::Debug > Severity::Info);
+        assert_eq!(Severity::Warning.to_string(), "warning");
+        assert_eq!(Severity::Error.rank(), 3);
+        assert_eq!(Severity::from_rank(0), Severity::Emergency);
+        assert_eq!(Severity::from_rank(9), Severity::Debug);
+    }
+
+    #[test]
+    fn test_rule_spec_deserialize() {
+        let spec: RuleSpec = toml::from_str(
+            r#"
+name = "db-timeout"
+kind = "regex"
+pattern = 'timeout after \d+ms'
+case_insensitive = true
+color = "magenta"
+notify = false
+throttle = 1
+severity = "error"
+"#,
+        )
+        .unwrap();
+
+        let rule = compile(spec);
+        assert_eq!(rule.name, "db-timeout");
+        assert_eq!(rule.kind, MatchKind::Regex);
+        assert_eq!(rule.color, Some(Color::Magenta));
+        assert!(!rule.notify);
+        assert_eq!(rule.throttle, Some(1));
+        assert_eq!(rule.severity, Some(Severity::Error));
+        assert!(rule.is_match("Timeout After 250ms", None));
+    }
+}
@@ -0,0 +1,107 @@
+//! Line sources other than regular files: standard input (`--file -`) and
+//! the output of a command started with `--exec`.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use tokio::process::{Child, Command};
+
+/// The `--file` value that reads standard input
+pub const STDIN: &str = "-";
+
+/// Whether a `--file` value means standard input
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN
+}
+
+/// Name shown for lines read from standard input
+pub fn stdin_label() -> PathBuf {
+    PathBuf::from("stdin")
+}
+
+/// Name shown for lines printed by a command: its program name
+pub fn command_label(command: &str) -> PathBuf {
+    let program = command.split_whitespace().next().unwrap_or(command);
+    Path::new(program)
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(program))
+}
+
+/// Start a command through the shell with its standard output piped. The
+/// child is killed if its handle is dropped before it exits.
+pub fn spawn_command(command: &str) -> Result<Child> {
+    shell(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run command: {}", command))
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+/// How a command ended, for the shutdown summary
+pub fn describe_exit(status: ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exited with code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("was killed by signal {}", signal);
+        }
+    }
+    "was terminated".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    #[test]
+    fn test_is_stdin() {
+        assert!(is_stdin(Path::new("-")));
+        assert!(!is_stdin(Path::new("./-")));
+        assert!(!is_stdin(Path::new("app.log")));
+    }
+
+    #[test]
+    fn test_command_label() {
+        assert_eq!(command_label("journalctl -f"), PathBuf::from("journalctl"));
+        assert_eq!(
+            command_label("/usr/bin/kubectl logs -f web"),
+            PathBuf::from("kubectl")
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawn_command() {
+        let mut child = spawn_command("printf 'one\\ntwo\\n'; exit 3").unwrap();
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+
+        let mut output = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            output.push(line);
+        }
+        let status = child.wait().await.unwrap();
+
+        assert_eq!(output, vec!["one", "two"]);
+        assert_eq!(describe_exit(status), "exited with code 3");
+    }
+}
@@ -0,0 +1,199 @@
+//! Read checkpoints kept in a state file (`--state-file`), so that tailing
+//! resumes where it stopped after a restart instead of at the end of each
+//! file. Like Filebeat's registry, each file is recorded by path together
+//! with its device, inode and the offset read up to; a checkpoint only
+//! applies while the path still names the same file.
+
+use crate::tail::FileId;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Bumped if the file layout changes incompatibly
+const STATE_VERSION: u32 = 1;
+
+/// How far a file has been read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(flatten)]
+    pub id: FileId,
+    pub offset: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateData {
+    version: u32,
+    files: BTreeMap<PathBuf, Checkpoint>,
+}
+
+#[derive(Debug)]
+pub struct StateFile {
+    path: PathBuf,
+    checkpoints: BTreeMap<PathBuf, Checkpoint>,
+    /// Whether there are checkpoints not yet saved
+    dirty: bool,
+}
+
+impl StateFile {
+    /// A state file with no checkpoints, created on the first save
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            checkpoints: BTreeMap::new(),
+            dirty: false,
+        }
+    }
+
+    /// Load the checkpoints saved by an earlier run. A missing file has none;
+    /// checkpoints of files that no longer exist are dropped.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut state = Self::new(path);
+        let content = match std::fs::read_to_string(&state.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(state),
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to read state file: {}", state.path.display())
+                })
+            }
+        };
+
+        let data: StateData = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid state file: {}", state.path.display()))?;
+        if data.version != STATE_VERSION {
+            anyhow::bail!(
+                "Unsupported state file version {} in {}",
+                data.version,
+                state.path.display()
+            );
+        }
+
+        let count = data.files.len();
+        state.checkpoints = data
+            .files
+            .into_iter()
+            .filter(|(file, _)| file.exists())
+            .collect();
+        state.dirty = state.checkpoints.len() != count;
+        Ok(state)
+    }
+
+    /// Where to start reading a file: the saved offset if the path still
+    /// names the file it was recorded for, the start if it names a new file
+    /// (rotated while stopped), or `None` if the file was never read
+    pub fn resume_offset(&self, file: &Path) -> Option<u64> {
+        let checkpoint = self.checkpoints.get(&key(file))?;
+        if FileId::of_path(file) == Some(checkpoint.id) {
+            Some(checkpoint.offset)
+        } else {
+            Some(0)
+        }
+    }
+
+    pub fn record(&mut self, file: &Path, checkpoint: Checkpoint) {
+        let previous = self.checkpoints.insert(key(file), checkpoint);
+        self.dirty |= previous != Some(checkpoint);
+    }
+
+    /// Forget a file that is no longer watched
+    pub fn remove(&mut self, file: &Path) {
+        self.dirty |= self.checkpoints.remove(&key(file)).is_some();
+    }
+
+    /// Write the checkpoints if any changed since the last save. The file is
+    /// replaced atomically, so a crash mid-write leaves the old one intact.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let data = StateData {
+            version: STATE_VERSION,
+            files: self.checkpoints.clone(),
+        };
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(&data)?)
+            .and_then(|()| std::fs::rename(&temp, &self.path))
+            .with_context(|| format!("Failed to write state file: {}", self.path.display()))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Files are recorded by absolute path, so the working directory doesn't
+/// matter on restart
+fn key(file: &Path) -> PathBuf {
+    std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn checkpoint(file: &Path, offset: u64) -> Checkpoint {
+        Checkpoint {
+            id: FileId::of_path(file).unwrap(),
+            offset,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("app.log");
+        std::fs::write(&log, "one\ntwo\n").unwrap();
+        let path = dir.path().join("state.json");
+
+        let mut state = StateFile::load(&path).unwrap();
+        assert_eq!(state.resume_offset(&log), None);
+        state.record(&log, checkpoint(&log, 4));
+        state.save().unwrap();
+
+        let state = StateFile::load(&path).unwrap();
+        assert_eq!(state.resume_offset(&log), Some(4));
+    }
+
+    #[test]
+    fn test_replaced_file_is_read_from_start() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("app.log");
+        std::fs::write(&log, "old\n").unwrap();
+        let mut state = StateFile::new(dir.path().join("state.json"));
+        state.record(&log, checkpoint(&log, 4));
+
+        // Rotated while stopped: the path now names another file
+        std::fs::rename(&log, dir.path().join("app.log.1")).unwrap();
+        std::fs::write(&log, "new\n").unwrap();
+        assert_eq!(state.resume_offset(&log), Some(0));
+    }
+
+    #[test]
+    fn test_removed_files_are_dropped() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("app.log");
+        std::fs::write(&log, "line\n").unwrap();
+        let path = dir.path().join("state.json");
+
+        let mut state = StateFile::new(&path);
+        state.record(&log, checkpoint(&log, 5));
+        state.save().unwrap();
+        std::fs::remove_file(&log).unwrap();
+
+        let mut state = StateFile::load(&path).unwrap();
+        assert!(state.checkpoints.is_empty());
+        state.save().unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("app.log"));
+    }
+
+    #[test]
+    fn test_invalid_state_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        std::fs::write(&path, "not json").unwrap();
+        assert!(StateFile::load(&path).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! Syslog line decoding (RFC 5424 and the BSD format of RFC 3164).
+//!
+//! ```text
+//! <34>1 2003-10-11T22:14:15.003Z mymachine su - ID47 [origin ip="10.0.0.1"] 'su root' failed
+//! <38>Oct 11 22:14:15 mymachine sshd[4123]: Accepted publickey for deploy
+//! Oct 11 22:14:15 mymachine sshd[4123]: Accepted publickey for deploy
+//! ```
+//!
+//! Decoded fields: `priority`, `facility`, `severity` (when the line carries
+//! a `<PRI>`), `version`, `timestamp`, `hostname`, `app`, `procid`, `msgid`,
+//! `sd.<id>.<param>` (structured data) and `msg`. Nil (`-`) values are left
+//! out.
+
+use crate::rule::Severity;
+use serde_json::{Map, Value};
+
+const FACILITIES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Decode a syslog line. Lines without a `<PRI>` header (as written to
+/// `/var/log/syslog`) must have a timestamp, hostname and `app[pid]:` tag to
+/// be recognized.
+pub fn parse(line: &str) -> Option<Map<String, Value>> {
+    let mut fields = Map::new();
+    let rest = line.trim_start();
+
+    let Some(after) = rest.strip_prefix('<') else {
+        return parse_bsd(rest, fields, false);
+    };
+
+    let end = after.find('>')?;
+    let priority: u8 = match &after[..end] {
+        digits if (1..=3).contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_digit()) => {
+            digits.parse().ok().filter(|&priority| priority <= 191)?
+        }
+        _ => return None,
+    };
+    fields.insert("priority".to_string(), Value::from(priority));
+    fields.insert(
+        "facility".to_string(),
+        Value::from(FACILITIES[usize::from(priority / 8)]),
+    );
+    fields.insert(
+        "severity".to_string(),
+        Value::from(Severity::from_rank(priority % 8).as_str()),
+    );
+
+    let rest = &after[end + 1..];
+    match rest.split_once(' ') {
+        Some((version, tail))
+            if (1..=2).contains(&version.len()) && version.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            fields.insert(
+                "version".to_string(),
+                Value::from(version.parse::<u8>().ok()?),
+            );
+            parse_5424(tail, fields)
+        }
+        _ => parse_bsd(rest, fields, true),
+    }
+}
+
+/// RFC 5424: `TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA [MSG]`
+fn parse_5424(rest: &str, mut fields: Map<String, Value>) -> Option<Map<String, Value>> {
+    let mut rest = rest;
+    for key in ["timestamp", "hostname", "app", "procid", "msgid"] {
+        let (value, tail) = rest.split_once(' ').unwrap_or((rest, ""));
+        if value.is_empty() {
+            return None;
+        }
+        if value != "-" {
+            fields.insert(key.to_string(), Value::from(value));
+        }
+        rest = tail;
+    }
+
+    let rest = match rest.strip_prefix('-') {
+        Some(tail) => tail,
+        None if rest.starts_with('[') => {
+            let (data, tail) = parse_structured_data(rest)?;
+            fields.insert("sd".to_string(), Value::Object(data));
+            tail
+        }
+        None if rest.is_empty() => rest,
+        None => return None,
+    };
+
+    let msg = rest.strip_prefix(' ').unwrap_or(rest);
+    let msg = msg.strip_prefix('\u{feff}').unwrap_or(msg);
+    if !msg.is_empty() {
+        fields.insert("msg".to_string(), Value::from(msg));
+    }
+
+    Some(fields)
+}
+
+/// Parse `[id name="value" ...]` elements into `{id: {name: value}}`,
+/// returning the text after the last element
+fn parse_structured_data(input: &str) -> Option<(Map<String, Value>, &str)> {
+    let mut data = Map::new();
+    let mut rest = input;
+
+    while let Some(element) = rest.strip_prefix('[') {
+        let id_end = element.find([' ', ']'])?;
+        let id = &element[..id_end];
+        let mut params = Map::new();
+        let mut tail = &element[id_end..];
+
+        loop {
+            if let Some(after) = tail.strip_prefix(']') {
+                tail = after;
+                break;
+            }
+            let param = tail.strip_prefix(' ')?;
+            let (name, quoted) = param.split_once("=\"")?;
+
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let consumed = loop {
+                match chars.next()? {
+                    (i, '"') => break i + 1,
+                    (_, '\\') => value.push(chars.next()?.1),
+                    (_, c) => value.push(c),
+                }
+            };
+
+            params.insert(name.to_string(), Value::from(value));
+            tail = &quoted[consumed..];
+        }
+
+        data.insert(id.to_string(), Value::Object(params));
+        rest = tail;
+    }
+
+    Some((data, rest))
+}
+
+/// RFC 3164: `TIMESTAMP HOSTNAME TAG: MSG`. With a `<PRI>` header anything
+/// goes; without one the timestamp, hostname and tag are required.
+fn parse_bsd(
+    rest: &str,
+    mut fields: Map<String, Value>,
+    has_priority: bool,
+) -> Option<Map<String, Value>> {
+    let header = split_timestamp(rest).and_then(|(timestamp, tail)| {
+        let (hostname, tail) = tail.trim_start().split_once(' ')?;
+        let (tag, msg) = tail.trim_start().split_once(':')?;
+        // Tags are a single word, optionally followed by `[pid]`
+        (!tag.is_empty() && !tag.contains(' ')).then_some((timestamp, hostname, tag, msg))
+    });
+
+    let Some((timestamp, hostname, tag, msg)) = header else {
+        if !has_priority {
+            return None;
+        }
+        if !rest.is_empty() {
+            fields.insert("msg".to_string(), Value::from(rest));
+        }
+        return Some(fields);
+    };
+
+    fields.insert("timestamp".to_string(), Value::from(timestamp));
+    fields.insert("hostname".to_string(), Value::from(hostname));
+    match tag.strip_suffix(']').and_then(|tag| tag.split_once('[')) {
+        Some((app, procid)) => {
+            fields.insert("app".to_string(), Value::from(app));
+            fields.insert("procid".to_string(), Value::from(procid));
+        }
+        None => {
+            fields.insert("app".to_string(), Value::from(tag));
+        }
+    }
+    let msg = msg.strip_prefix(' ').unwrap_or(msg);
+    if !msg.is_empty() {
+        fields.insert("msg".to_string(), Value::from(msg));
+    }
+
+    Some(fields)
+}
+
+/// Split off a leading `Mmm dd hh:mm:ss` or RFC 3339 timestamp
+fn split_timestamp(input: &str) -> Option<(&str, &str)> {
+    if MONTHS.iter().any(|month| input.starts_with(month)) {
+        let after_month = input[3..].strip_prefix(' ')?.trim_start_matches(' ');
+        let (day, after_day) = after_month.split_once(' ')?;
+        if day.is_empty() || day.len() > 2 || !day.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let time = after_day.get(..8)?;
+        let is_time = time.bytes().enumerate().all(|(i, b)| match i {
+            2 | 5 => b == b':',
+            _ => b.is_ascii_digit(),
+        });
+        if !is_time {
+            return None;
+        }
+        let end = input.len() - after_day.len() + 8;
+        return Some((&input[..end], &input[end..]));
+    }
+
+    // 2024-01-15T10:00:00.123456+00:00, as written by rsyslog's default template
+    let (timestamp, tail) = input.split_once(' ')?;
+    let bytes = timestamp.as_bytes();
+    let is_rfc3339 = bytes.len() >= 19
+        && bytes[..4].iter().all(u8::is_ascii_digit)
+        && bytes[4] == b'-'
+        && bytes[10] == b'T';
+    is_rfc3339.then_some((timestamp, tail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_rfc5424() {
+        let fields = parse(
+            r#"<34>1 2003-10-11T22:14:15.003Z mymachine.example.com su - ID47 [exampleSDID@32473 iut="3" eventSource="Application \"x\""][meta seq="1"] 'su root' failed"#,
+        )
+        .unwrap();
+
+        assert_eq!(fields["priority"], json!(34));
+        assert_eq!(fields["facility"], json!("auth"));
+        assert_eq!(fields["severity"], json!("critical"));
+        assert_eq!(fields["version"], json!(1));
+        assert_eq!(fields["timestamp"], json!("2003-10-11T22:14:15.003Z"));
+        assert_eq!(fields["hostname"], json!("mymachine.example.com"));
+        assert_eq!(fields["app"], json!("su"));
+        assert!(!fields.contains_key("procid"));
+        assert_eq!(fields["msgid"], json!("ID47"));
+        assert_eq!(fields["sd"]["exampleSDID@32473"]["iut"], json!("3"));
+        assert_eq!(
+            fields["sd"]["exampleSDID@32473"]["eventSource"],
+            json!(r#"Application "x""#)
+        );
+        assert_eq!(fields["sd"]["meta"]["seq"], json!("1"));
+        assert_eq!(fields["msg"], json!("'su root' failed"));
+    }
+
+    #[test]
+    fn test_parse_rfc5424_without_structured_data() {
+        let fields =
+            parse("<165>1 2003-08-24T05:14:15.000003-07:00 192.0.2.1 myproc 8710 - - %% It's time")
+                .unwrap();
+        assert_eq!(fields["facility"], json!("local4"));
+        assert_eq!(fields["severity"], json!("notice"));
+        assert_eq!(fields["procid"], json!("8710"));
+        assert!(!fields.contains_key("sd"));
+        assert_eq!(fields["msg"], json!("%% It's time"));
+    }
+
+    #[test]
+    fn test_parse_rfc3164() {
+        let fields = parse("<38>Oct  9 22:14:15 mymachine sshd[4123]: Accepted publickey").unwrap();
+        assert_eq!(fields["facility"], json!("auth"));
+        assert_eq!(fields["severity"], json!("info"));
+        assert_eq!(fields["timestamp"], json!("Oct  9 22:14:15"));
+        assert_eq!(fields["hostname"], json!("mymachine"));
+        assert_eq!(fields["app"], json!("sshd"));
+        assert_eq!(fields["procid"], json!("4123"));
+        assert_eq!(fields["msg"], json!("Accepted publickey"));
+    }
+
+    #[test]
+    fn test_parse_syslog_file_lines() {
+        let fields = parse("Oct 11 22:14:15 web1 kernel: [ 12.5] eth0: link up").unwrap();
+        assert!(!fields.contains_key("severity"));
+        assert_eq!(fields["app"], json!("kernel"));
+        assert_eq!(fields["msg"], json!("[ 12.5] eth0: link up"));
+
+        let fields =
+            parse("2024-01-15T10:00:00.123456+00:00 web1 CRON[881]: (root) CMD (run-parts)")
+                .unwrap();
+        assert_eq!(
+            fields["timestamp"],
+            json!("2024-01-15T10:00:00.123456+00:00")
+        );
+        assert_eq!(fields["app"], json!("CRON"));
+        assert_eq!(fields["procid"], json!("881"));
+    }
+
+    #[test]
+    fn test_priority_only_lines() {
+        let fields = parse("<11>something broke").unwrap();
+        assert_eq!(fields["facility"], json!("user"));
+        assert_eq!(fields["severity"], json!("error"));
+        assert_eq!(fields["msg"], json!("something broke"));
+    }
+
+    #[test]
+    fn test_non_syslog_lines() {
+        assert!(parse("ERROR plain text").is_none());
+        assert!(parse("Oct 11 22:14:15 no tag here").is_none());
+        assert!(parse("<999>1 too high").is_none());
+        assert!(parse("<abc>nope").is_none());
+        assert!(parse(r#"<34>1 2003-10-11T22:14:15Z host app - - [unterminated x="1"#).is_none());
+    }
+}
@@ -0,0 +1,611 @@
+//! Following a file across rotations, with `tail -F` semantics.
+//!
+//! A [`FileTail`] keeps the watched file open and remembers which file it
+//! has open by device and inode. Each poll first drains whatever was
+//! appended to the open file, then checks whether the path now names a
+//! different file (logrotate's rename-and-create, or delete-and-recreate)
+//! and if so switches to it, reading it from the start. A file that shrinks
+//! below the read offset was truncated in place (copytruncate) and is read
+//! again from the start.
+//!
+//! A last line without its newline is still being written, so it is held
+//! back until the rest arrives, or emitted as it is once it hasn't grown
+//! for the partial-line timeout.
+
+use crate::encoding::TextEncoding;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, Metadata};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// What a poll found, in the order it happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TailEvent {
+    /// A new line exactly as written, without its `\n` or `\r\n` ending;
+    /// bytes invalid in the file's encoding are replaced with U+FFFD
+    Line(String, LinePosition),
+    /// The file shrank and is being read again from the start
+    Truncated,
+    /// The path now names a different file, which is being read from the start
+    Rotated,
+    /// The path no longer exists; the old file stays open until it reappears
+    Missing,
+}
+
+/// Where a line starts in its file or stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinePosition {
+    /// Byte offset of the line's first byte
+    pub offset: u64,
+    /// 1-based line number, known when reading began at the start
+    pub number: Option<u64>,
+}
+
+impl LinePosition {
+    /// The first line of a file or stream
+    pub fn start() -> Self {
+        Self {
+            offset: 0,
+            number: Some(1),
+        }
+    }
+
+    /// The line after this one, which took `len` bytes
+    pub fn next(self, len: usize) -> Self {
+        Self {
+            offset: self.offset + len as u64,
+            number: self.number.map(|number| number + 1),
+        }
+    }
+}
+
+/// Identity of a file, stable across renames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FileId {
+    dev: u64,
+    ino: u64,
+}
+
+impl FileId {
+    /// Identity of the file a path currently names
+    pub fn of_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Self::of(&std::fs::metadata(path).ok()?)
+    }
+
+    #[cfg(unix)]
+    fn of(metadata: &Metadata) -> Option<Self> {
+        use std::os::unix::fs::MetadataExt;
+        Some(Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        })
+    }
+
+    /// Without inode numbers only truncation can be detected
+    #[cfg(not(unix))]
+    fn of(_metadata: &Metadata) -> Option<Self> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub struct FileTail {
+    path: PathBuf,
+    file: File,
+    id: Option<FileId>,
+    /// Bytes of the open file consumed so far
+    offset: u64,
+    /// Whether the last byte consumed ended a line
+    at_line_start: bool,
+    /// Number of the next line, if reading began at the start of the file
+    line_number: Option<u64>,
+    missing: bool,
+    buffer_size: usize,
+    encoding: TextEncoding,
+    /// The unfinished last line, and when it last grew
+    partial: Vec<u8>,
+    partial_updated: Instant,
+    /// How long an unfinished line waits for the rest; `None` waits forever
+    partial_timeout: Option<Duration>,
+}
+
+impl FileTail {
+    /// Start following a file from its current end
+    pub fn open<P: AsRef<Path>>(path: P, buffer_size: usize) -> Result<Self> {
+        let mut tail = Self::open_at(path, 0, buffer_size)?;
+        tail.skip_to(tail.file.metadata()?.len())?;
+        Ok(tail)
+    }
+
+    /// Start following a file from a byte offset
+    pub fn open_at<P: AsRef<Path>>(path: P, offset: u64, buffer_size: usize) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)
+            .with_context(|| format!("Failed to open file: {}", path.display()))?;
+        let id = FileId::of(&file.metadata()?);
+
+        let mut tail = Self {
+            path,
+            file,
+            id,
+            offset: 0,
+            at_line_start: true,
+            line_number: None,
+            missing: false,
+            buffer_size,
+            encoding: TextEncoding::default(),
+            partial: Vec::new(),
+            partial_updated: Instant::now(),
+            partial_timeout: None,
+        };
+        tail.skip_to(offset)?;
+        Ok(tail)
+    }
+
+    /// Read the file in `encoding` instead of UTF-8
+    pub fn with_encoding(mut self, encoding: TextEncoding) -> Result<Self> {
+        self.encoding = encoding;
+        // Stay on a code unit boundary
+        let offset = self.offset - self.offset % encoding.unit_len() as u64;
+        self.skip_to(offset)?;
+        Ok(self)
+    }
+
+    /// Emit an unfinished last line once it hasn't grown for `timeout`
+    pub fn with_partial_timeout(mut self, timeout: Duration) -> Self {
+        self.partial_timeout = Some(timeout);
+        self
+    }
+
+    /// Move the read offset, which may fall after a partial line
+    fn skip_to(&mut self, offset: u64) -> Result<()> {
+        self.partial.clear();
+        self.offset = offset;
+        self.line_number = (offset == 0).then_some(1);
+        self.at_line_start = true;
+        self.at_line_start = !self.rewritten()?;
+        Ok(())
+    }
+
+    /// Bytes of the current file read so far, up to the start of an
+    /// unfinished line
+    pub fn offset(&self) -> u64 {
+        self.offset - self.partial.len() as u64
+    }
+
+    /// When the unfinished last line, if any, is due to be emitted
+    pub fn partial_deadline(&self) -> Option<Instant> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        self.partial_timeout
+            .map(|timeout| self.partial_updated + timeout)
+    }
+
+    /// Identity of the file currently open
+    pub fn id(&self) -> Option<FileId> {
+        self.id
+    }
+
+    /// Whether the path was gone at the last poll. The open file is still
+    /// read: it may have been renamed and still be written to.
+    pub fn is_missing(&self) -> bool {
+        self.missing
+    }
+
+    /// Read everything appended since the last poll, following the path to
+    /// a new file if it was rotated
+    pub fn poll(&mut self) -> Result<Vec<TailEvent>> {
+        let mut events = Vec::new();
+
+        // Whatever was written to the old file before the switch comes first
+        self.drain(&mut events)?;
+
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) => {
+                let id = FileId::of(&metadata);
+                if id.is_some() && id != self.id {
+                    match File::open(&self.path) {
+                        Ok(file) => {
+                            // The old file won't finish its last line now
+                            self.flush_partial(&mut events);
+                            self.id = FileId::of(&file.metadata()?);
+                            self.file = file;
+                            self.offset = 0;
+                            self.at_line_start = true;
+                            self.line_number = Some(1);
+                            self.missing = false;
+                            events.push(TailEvent::Rotated);
+                            self.drain(&mut events)?;
+                        }
+                        // Renamed away again before it could be opened
+                        Err(e) if e.kind() == ErrorKind::NotFound => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                } else {
+                    self.missing = false;
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if !self.missing {
+                    self.missing = true;
+                    events.push(TailEvent::Missing);
+                }
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        if self
+            .partial_deadline()
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.flush_partial(&mut events);
+        }
+
+        Ok(events)
+    }
+
+    /// Read the open file from the offset to its end
+    fn drain(&mut self, events: &mut Vec<TailEvent>) -> Result<()> {
+        let len = self.file.metadata()?.len();
+        if len < self.offset || self.rewritten()? {
+            self.flush_partial(events);
+            self.offset = 0;
+            self.at_line_start = true;
+            self.line_number = Some(1);
+            events.push(TailEvent::Truncated);
+        }
+        if len == self.offset {
+            return Ok(());
+        }
+
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut reader = BufReader::with_capacity(self.buffer_size, &self.file);
+
+        loop {
+            let read = self.encoding.read_line(&mut reader, &mut self.partial)?;
+            if read == 0 {
+                break;
+            }
+            self.offset += read as u64;
+            self.at_line_start = self.encoding.ends_line(&self.partial);
+            if self.at_line_start {
+                let line = self.encoding.decode(&self.partial);
+                events.push(TailEvent::Line(line, self.line_position()));
+                self.partial.clear();
+                self.line_number = self.line_number.map(|number| number + 1);
+            } else {
+                self.partial_updated = Instant::now();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Emit the unfinished last line as it is. The rest of it, if it comes,
+    /// keeps its line number.
+    fn flush_partial(&mut self, events: &mut Vec<TailEvent>) {
+        if !self.partial.is_empty() {
+            let line = self.encoding.decode(&self.partial);
+            events.push(TailEvent::Line(line, self.line_position()));
+            self.partial.clear();
+        }
+    }
+
+    /// Position of the line being read
+    fn line_position(&self) -> LinePosition {
+        LinePosition {
+            offset: self.offset(),
+            number: self.line_number,
+        }
+    }
+
+    /// A file truncated and written past the old offset between two polls
+    /// doesn't shrink, but the byte before the offset is then unlikely to
+    /// still end a line
+    fn rewritten(&mut self) -> Result<bool> {
+        let unit = self.encoding.unit_len() as u64;
+        if self.offset < unit || !self.at_line_start {
+            return Ok(false);
+        }
+        let mut bytes = vec![0u8; unit as usize];
+        self.file.seek(SeekFrom::Start(self.offset - unit))?;
+        match self.file.read_exact(&mut bytes) {
+            Ok(()) => Ok(!self.encoding.ends_line(&bytes)),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use tempfile::{NamedTempFile, TempDir};
+
+    fn lines(events: &[TailEvent]) -> Vec<&str> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                TailEvent::Line(line, _) => Some(line.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn line(text: &str) -> TailEvent {
+        TailEvent::Line(text.to_string(), LinePosition::default())
+    }
+
+    fn without_positions(events: Vec<TailEvent>) -> Vec<TailEvent> {
+        events
+            .into_iter()
+            .map(|event| match event {
+                TailEvent::Line(text, _) => TailEvent::Line(text, LinePosition::default()),
+                event => event,
+            })
+            .collect()
+    }
+
+    fn append(path: &Path, text: &str) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_new_lines() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "line 1").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut tail = FileTail::open(temp_file.path(), 1024).unwrap();
+        let start = tail.offset();
+        writeln!(temp_file, "line 2").unwrap();
+        writeln!(temp_file, "line 3").unwrap();
+        temp_file.flush().unwrap();
+
+        let events = tail.poll().unwrap();
+        assert_eq!(lines(&events), vec!["line 2", "line 3"]);
+        assert!(tail.offset() > start);
+    }
+
+    #[test]
+    fn test_lines_are_kept_exactly() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let mut tail = FileTail::open(temp_file.path(), 1024).unwrap();
+        write!(
+            temp_file,
+            "Exception \r\n\tat Main.run\n\n   \nkey: value\r\n"
+        )
+        .unwrap();
+        temp_file.flush().unwrap();
+
+        let events = tail.poll().unwrap();
+        assert_eq!(
+            lines(&events),
+            vec!["Exception ", "\tat Main.run", "", "   ", "key: value"]
+        );
+    }
+
+    #[test]
+    fn test_no_new_content() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "line 1").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut tail = FileTail::open(temp_file.path(), 1024).unwrap();
+        let start = tail.offset();
+        assert!(tail.poll().unwrap().is_empty());
+        assert_eq!(tail.offset(), start);
+    }
+
+    #[test]
+    fn test_open_at_offset() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ERROR: Test error").unwrap();
+        writeln!(temp_file, "INFO: Normal operation").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut tail = FileTail::open_at(temp_file.path(), 0, 1024).unwrap();
+        let events = tail.poll().unwrap();
+        assert_eq!(
+            lines(&events),
+            vec!["ERROR: Test error", "INFO: Normal operation"]
+        );
+
+        let mut tail = FileTail::open_at(temp_file.path(), 18, 1024).unwrap();
+        assert_eq!(lines(&tail.poll().unwrap()), vec!["INFO: Normal operation"]);
+    }
+
+    #[test]
+    fn test_offset_after_partial_line() {
+        // Neither opening at the end nor resuming there mistakes the
+        // unfinished line for a truncation
+        for resume in [false, true] {
+            let mut temp_file = NamedTempFile::new().unwrap();
+            write!(temp_file, "ERROR: partial").unwrap();
+            temp_file.flush().unwrap();
+
+            let mut tail = if resume {
+                FileTail::open_at(temp_file.path(), 14, 1024).unwrap()
+            } else {
+                FileTail::open(temp_file.path(), 1024).unwrap()
+            };
+            append(temp_file.path(), " line\n");
+            assert_eq!(without_positions(tail.poll().unwrap()), vec![line(" line")]);
+        }
+    }
+
+    #[test]
+    fn test_partial_line_waits_for_newline() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut tail = FileTail::open(temp_file.path(), 1024).unwrap();
+        append(temp_file.path(), "ERROR: part");
+        assert!(tail.poll().unwrap().is_empty());
+        // A checkpoint here resumes at the start of the line
+        assert_eq!(tail.offset(), 0);
+        assert_eq!(tail.partial_deadline(), None);
+
+        append(temp_file.path(), "ial\nnext");
+        assert_eq!(
+            tail.poll().unwrap(),
+            vec![TailEvent::Line(
+                "ERROR: partial".to_string(),
+                LinePosition::start()
+            )]
+        );
+        assert_eq!(tail.offset(), 15);
+    }
+
+    #[test]
+    fn test_partial_line_timeout() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut tail = FileTail::open(temp_file.path(), 1024)
+            .unwrap()
+            .with_partial_timeout(Duration::from_millis(50));
+        append(temp_file.path(), "Password: ");
+        assert!(tail.poll().unwrap().is_empty());
+        assert!(tail.partial_deadline().is_some());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(
+            without_positions(tail.poll().unwrap()),
+            vec![line("Password: ")]
+        );
+        append(temp_file.path(), "rest\n");
+        assert_eq!(without_positions(tail.poll().unwrap()), vec![line("rest")]);
+    }
+
+    #[test]
+    fn test_invalid_utf8_is_not_fatal() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut tail = FileTail::open(temp_file.path(), 1024).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(temp_file.path())
+            .unwrap();
+        file.write_all(b"ERROR \xc3\x28 bad byte\nafter\n").unwrap();
+
+        let events = tail.poll().unwrap();
+        assert_eq!(lines(&events), vec!["ERROR \u{fffd}( bad byte", "after"]);
+    }
+
+    #[test]
+    fn test_utf16_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let utf16 = TextEncoding::for_label("utf-16le").unwrap();
+        let mut tail = FileTail::open(temp_file.path(), 1024)
+            .unwrap()
+            .with_encoding(utf16)
+            .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(temp_file.path())
+            .unwrap();
+        let bytes: Vec<u8> = "\u{feff}ERROR: Dienst gestoppt\r\nWeiter\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        file.write_all(&bytes).unwrap();
+
+        let events = tail.poll().unwrap();
+        assert_eq!(lines(&events), vec!["ERROR: Dienst gestoppt", "Weiter"]);
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(FileTail::open("/non/existent/file.log", 1024).is_err());
+        assert!(FileTail::open_at("/non/existent/file.log", 0, 1024).is_err());
+    }
+
+    #[test]
+    fn test_copytruncate() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "old line").unwrap();
+        temp_file.flush().unwrap();
+        let mut tail = FileTail::open(temp_file.path(), 1024).unwrap();
+
+        temp_file.as_file_mut().set_len(0).unwrap();
+        append(temp_file.path(), "new\n");
+
+        let events = tail.poll().unwrap();
+        assert_eq!(
+            events,
+            vec![
+                TailEvent::Truncated,
+                TailEvent::Line("new".to_string(), LinePosition::start())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated_and_rewritten_past_offset() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "short").unwrap();
+        temp_file.flush().unwrap();
+        let mut tail = FileTail::open(temp_file.path(), 1024).unwrap();
+
+        temp_file.as_file_mut().set_len(0).unwrap();
+        append(temp_file.path(), "a much longer first line\n");
+
+        let events = tail.poll().unwrap();
+        assert_eq!(events[0], TailEvent::Truncated);
+        assert_eq!(lines(&events), vec!["a much longer first line"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rename_and_create() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "before\n").unwrap();
+        let mut tail = FileTail::open(&path, 1024).unwrap();
+
+        // Written after the last poll but before rotation: must not be lost
+        append(&path, "late old line\n");
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        // The new file is bigger than the old offset, so size alone can't tell
+        std::fs::write(&path, "first new line\nsecond new line\n").unwrap();
+
+        // Line numbers are known once the new file is read from its start
+        let events = tail.poll().unwrap();
+        let old = LinePosition {
+            offset: 7,
+            number: None,
+        };
+        let first = LinePosition::start();
+        assert_eq!(
+            events,
+            vec![
+                TailEvent::Line("late old line".to_string(), old),
+                TailEvent::Rotated,
+                TailEvent::Line("first new line".to_string(), first),
+                TailEvent::Line("second new line".to_string(), first.next(15)),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_missing_then_recreated() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "before\n").unwrap();
+        let mut tail = FileTail::open(&path, 1024).unwrap();
+
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        assert_eq!(tail.poll().unwrap(), vec![TailEvent::Missing]);
+        // Reported once, not on every poll
+        assert!(tail.poll().unwrap().is_empty());
+
+        std::fs::write(&path, "back\n").unwrap();
+        assert_eq!(
+            without_positions(tail.poll().unwrap()),
+            vec![TailEvent::Rotated, line("back")]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use crate::config::Config;
+use anyhow::Result;
+use termcolor::{Color, ColorSpec};
+
+/// Text with `{name}` placeholders, filled in per line. `{{` and `}}` stand
+/// for literal braces.
+///
+/// A placeholder can format its value like Rust's `format!`, with an
+/// alignment, a width and a maximum width after a colon (`{file:<20}`,
+/// `{rule:>5}`, `{line:.80}`), and style it with `|` modifiers
+/// (`{time|dim}`, `{rule|match|bold}`). Styles only apply where the output
+/// is colored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    name: String,
+    align: Align,
+    /// Shorter values are padded to this many characters
+    width: Option<usize>,
+    /// Longer values are cut to this many characters
+    max_width: Option<usize>,
+    style: Style,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Align {
+    #[default]
+    Left,
+    Right,
+    Center,
+}
+
+/// How a placeholder's value is colored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub color: Option<Color>,
+    /// Use the color of the rule that matched the line (`|match`)
+    pub match_color: bool,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    /// The terminal colors for this style, given the matched rule's color
+    pub fn spec(&self, match_color: Option<Color>) -> ColorSpec {
+        let mut spec = ColorSpec::new();
+        let color = self.color.or(match_color.filter(|_| self.match_color));
+        spec.set_fg(color)
+            .set_bold(self.bold)
+            .set_dimmed(self.dimmed)
+            .set_italic(self.italic)
+            .set_underline(self.underline);
+        spec
+    }
+
+    fn add(&mut self, modifier: &str) -> Result<()> {
+        match modifier {
+            "match" => self.match_color = true,
+            "bold" => self.bold = true,
+            "dim" => self.dimmed = true,
+            "italic" => self.italic = true,
+            "underline" => self.underline = true,
+            color => self.color = Some(Config::parse_color(color)?),
+        }
+        Ok(())
+    }
+}
+
+impl Field {
+    /// Parse `name[:spec][|modifier...]`
+    fn parse(placeholder: &str) -> Result<Self> {
+        let mut modifiers = placeholder.split('|');
+        let head = modifiers.next().unwrap_or_default();
+        let (name, spec) = match head.split_once(':') {
+            Some((name, spec)) => (name.trim(), Some(spec.trim())),
+            None => (head.trim(), None),
+        };
+        if name.is_empty() {
+            anyhow::bail!("Empty placeholder");
+        }
+
+        let mut field = Self {
+            name: name.to_string(),
+            align: Align::default(),
+            width: None,
+            max_width: None,
+            style: Style::default(),
+        };
+        if let Some(spec) = spec {
+            field.parse_spec(spec)?;
+        }
+        for modifier in modifiers {
+            field
+                .style
+                .add(modifier.trim())
+                .map_err(|_| anyhow::anyhow!("Unknown style '{}'", modifier.trim()))?;
+        }
+        Ok(field)
+    }
+
+    /// Parse `[<>^][width][.max_width]`
+    fn parse_spec(&mut self, spec: &str) -> Result<()> {
+        let invalid = || anyhow::anyhow!("Invalid format '{}'", spec);
+        let mut rest = spec;
+        if let Some(align) = rest.chars().next() {
+            let align = match align {
+                '<' => Some(Align::Left),
+                '>' => Some(Align::Right),
+                '^' => Some(Align::Center),
+                _ => None,
+            };
+            if let Some(align) = align {
+                self.align = align;
+                rest = &rest[1..];
+            }
+        }
+        let (width, max_width) = match rest.split_once('.') {
+            Some((width, max_width)) => (width, Some(max_width)),
+            None => (rest, None),
+        };
+        if !width.is_empty() {
+            self.width = Some(width.parse().map_err(|_| invalid())?);
+        }
+        if let Some(max_width) = max_width {
+            self.max_width = Some(max_width.parse().map_err(|_| invalid())?);
+        }
+        Ok(())
+    }
+
+    /// The value cut to the maximum width and padded to the width
+    fn format(&self, value: &str) -> String {
+        let value = match self.max_width {
+            Some(max) => match value.char_indices().nth(max) {
+                Some((end, _)) => &value[..end],
+                None => value,
+            },
+            None => value,
+        };
+        let padding = self
+            .width
+            .unwrap_or(0)
+            .saturating_sub(value.chars().count());
+        let (before, after) = match self.align {
+            Align::Left => (0, padding),
+            Align::Right => (padding, 0),
+            Align::Center => (padding / 2, padding - padding / 2),
+        };
+        format!("{:before$}{}{:after$}", "", value, "")
+    }
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(anyhow::anyhow!(
+                                    "Unclosed '{{' in template: {}",
+                                    template
+                                ))
+                            }
+                        }
+                    }
+                    let field = Field::parse(&name)
+                        .map_err(|e| anyhow::anyhow!("{} in template: {}", e, template))?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => {
+                    return Err(anyhow::anyhow!(
+                        "Unmatched '}}' in template (use '}}}}' for a literal brace): {}",
+                        template
+                    ))
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Fill in the placeholders. Names the lookup doesn't know render empty.
+    pub fn render<F>(&self, lookup: F) -> String
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        self.render_styled(lookup)
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect()
+    }
+
+    /// Fill in the placeholders, keeping the style of each piece of the
+    /// output. Literal text has the default style.
+    pub fn render_styled<F>(&self, lookup: F) -> Vec<(String, Style)>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => (text.clone(), Style::default()),
+                Part::Field(field) => {
+                    let value = lookup(&field.name).unwrap_or_default();
+                    (field.format(&value), field.style)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "status" => Some("503".to_string()),
+            "path" => Some("/api".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let template = Template::parse("{status} on { path } ({missing})").unwrap();
+        assert_eq!(template.render(lookup), "503 on /api ()");
+    }
+
+    #[test]
+    fn test_escaped_braces() {
+        let template = Template::parse("{{literal}} {status}}}").unwrap();
+        assert_eq!(template.render(lookup), "{literal} 503}");
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(Template::parse("{status").is_err());
+        assert!(Template::parse("status}").is_err());
+        assert!(Template::parse("{}").is_err());
+        assert!(Template::parse("{status:>x}").is_err());
+        assert!(Template::parse("{status|sparkly}").is_err());
+    }
+
+    #[test]
+    fn test_padding_and_truncation() {
+        let template = Template::parse("[{status:>5}] [{path:<6}] [{path:^7}]").unwrap();
+        assert_eq!(template.render(lookup), "[  503] [/api  ] [ /api  ]");
+
+        let template = Template::parse("{path:.2}|{path:<4.3}|{status:.10}").unwrap();
+        assert_eq!(template.render(lookup), "/a|/ap |503");
+
+        // Widths count characters, not bytes
+        let template = Template::parse("{name:.3}|{name:>8}").unwrap();
+        let rendered = template.render(|_| Some("Müller".to_string()));
+        assert_eq!(rendered, "Mül|  Müller");
+    }
+
+    #[test]
+    fn test_styles() {
+        let template = Template::parse("{status|red|bold} {path|match}").unwrap();
+        let pieces = template.render_styled(lookup);
+        assert_eq!(pieces.len(), 3);
+        assert_eq!(pieces[0].0, "503");
+        assert_eq!(pieces[0].1.spec(Some(Color::Green)).fg(), Some(&Color::Red));
+        assert!(pieces[0].1.bold);
+        assert_eq!(pieces[1], (" ".to_string(), Style::default()));
+        assert_eq!(
+            pieces[2].1.spec(Some(Color::Green)).fg(),
+            Some(&Color::Green)
+        );
+        assert_eq!(pieces[2].1.spec(None).fg(), None);
+        // Styles don't change the plain text
+        assert_eq!(template.render(lookup), "503 /api");
+    }
+}
@@ -0,0 +1,373 @@
+//! Timestamps of log lines, for starting at a point in time (`--since`).
+//!
+//! A line's time comes from a decoded `timestamp`/`time`/`ts` field when it
+//! has one, or else from a timestamp at the start of the line or inside its
+//! first `[...]`: RFC 3339 and ISO 8601 (`2026-10-17T09:00:00Z`,
+//! `2026-10-17 09:00:00,123`), syslog (`Oct 17 09:00:00`) and access log
+//! (`17/Oct/2026:09:00:00 +0000`) times. Times without an offset are local.
+
+use crate::encoding::TextEncoding;
+use crate::parser::Record;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// Decoded fields holding a record's time, in order of preference
+const TIME_FIELDS: &[&str] = &[
+    "timestamp",
+    "@timestamp",
+    "time",
+    "ts",
+    "datetime",
+    "time_local",
+];
+
+/// Date and time layouts without an offset, tried in order
+const NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S,%3f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parse a `--since` value: a duration before now such as `15m` or `2h 30m`,
+/// or a date and time such as `2026-10-17T09:00` or `2026-10-17`
+pub fn parse_since(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(duration) = humantime::parse_duration(value) {
+        let duration = chrono::Duration::from_std(duration).context("--since is too far back")?;
+        return Ok(Utc::now() - duration);
+    }
+    if let Some((time, rest)) = leading_timestamp(value.trim(), Local::now().year()) {
+        if rest.trim().is_empty() {
+            return Ok(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+        if let Some(time) = local(date.and_hms_opt(0, 0, 0).unwrap_or_default()) {
+            return Ok(time);
+        }
+    }
+    Err(anyhow!(
+        "expected a duration like 15m or a time like 2026-10-17T09:00, got '{}'",
+        value
+    ))
+}
+
+/// When a line was logged, if it says
+pub fn line_timestamp(line: &str, record: Option<&Record>) -> Option<DateTime<Utc>> {
+    let year = Local::now().year();
+    if let Some(record) = record {
+        for field in TIME_FIELDS {
+            if let Some(time) = record
+                .get(field)
+                .and_then(|value| field_timestamp(value, year))
+            {
+                return Some(time);
+            }
+        }
+    }
+
+    let line = line.trim_start();
+    if let Some((time, _)) = leading_timestamp(line, year) {
+        return Some(time);
+    }
+    // Access logs: `host - user [10/Oct/2000:13:55:36 -0700] ...`
+    let (_, bracketed) = line.split_once('[')?;
+    leading_timestamp(bracketed, year).map(|(time, _)| time)
+}
+
+/// Byte offset of the first line logged at or after `since`, or the end of
+/// the file if there is none. Lines are assumed to be in time order, so the
+/// file is binary searched; lines without a timestamp go with the line
+/// before them.
+pub fn offset_since<P, F>(
+    path: P,
+    since: DateTime<Utc>,
+    encoding: TextEncoding,
+    timestamp_of: F,
+) -> Result<u64>
+where
+    P: AsRef<Path>,
+    F: Fn(&str) -> Option<DateTime<Utc>>,
+{
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let len = reader.get_ref().metadata()?.len();
+
+    // Every timestamped line starting before `low` is older than `since`;
+    // the answer is the first timestamped line starting at or after it
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match first_timestamped_line(&mut reader, middle, high, encoding, &timestamp_of)? {
+            Some((_, end, time)) if time < since => low = end,
+            Some((start, _, _)) => high = start,
+            None => high = middle,
+        }
+    }
+
+    match first_timestamped_line(&mut reader, low, len, encoding, &timestamp_of)? {
+        Some((start, _, time)) if time >= since => Ok(start),
+        Some((_, end, _)) => offset_since_from(&mut reader, end, since, encoding, &timestamp_of),
+        None => Ok(len),
+    }
+}
+
+/// Scan forward for the first line at or after `since`, for files whose
+/// times go backwards where the search landed
+fn offset_since_from<F>(
+    reader: &mut BufReader<File>,
+    mut offset: u64,
+    since: DateTime<Utc>,
+    encoding: TextEncoding,
+    timestamp_of: &F,
+) -> Result<u64>
+where
+    F: Fn(&str) -> Option<DateTime<Utc>>,
+{
+    let len = reader.get_ref().metadata()?.len();
+    while let Some((start, end, time)) =
+        first_timestamped_line(reader, offset, len, encoding, timestamp_of)?
+    {
+        if time >= since {
+            return Ok(start);
+        }
+        offset = end;
+    }
+    Ok(len)
+}
+
+/// The first line with a timestamp that starts at or after `from` and
+/// before `until`, as its start offset, end offset and time
+fn first_timestamped_line<F>(
+    reader: &mut BufReader<File>,
+    from: u64,
+    until: u64,
+    encoding: TextEncoding,
+    timestamp_of: &F,
+) -> Result<Option<(u64, u64, DateTime<Utc>)>>
+where
+    F: Fn(&str) -> Option<DateTime<Utc>>,
+{
+    let mut buffer = Vec::new();
+    let unit = encoding.unit_len() as u64;
+    let from = from - from % unit;
+    let mut offset = from;
+
+    // Start at the beginning of the next whole line
+    if from > 0 {
+        reader.seek(SeekFrom::Start(from - unit))?;
+        offset = from - unit + encoding.read_line(reader, &mut buffer)? as u64;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+
+    while offset < until {
+        buffer.clear();
+        let read = encoding.read_line(reader, &mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        let line = encoding.decode(&buffer);
+        if let Some(time) = timestamp_of(&line) {
+            return Ok(Some((offset, offset + read as u64, time)));
+        }
+        offset += read as u64;
+    }
+
+    Ok(None)
+}
+
+/// A decoded time field: a timestamp string, or seconds or milliseconds
+/// since the epoch
+fn field_timestamp(value: &Value, year: i32) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(text) => leading_timestamp(text, year).map(|(time, _)| time),
+        Value::Number(number) => {
+            let seconds = number.as_f64()?;
+            // Milliseconds, if taken as seconds, would be far in the future
+            let seconds = if seconds > 1e11 {
+                seconds / 1000.0
+            } else {
+                seconds
+            };
+            DateTime::from_timestamp_millis((seconds * 1000.0) as i64)
+        }
+        _ => None,
+    }
+}
+
+/// A timestamp at the start of `text`, and the text after it. Syslog times
+/// have no year; `year` is used unless that puts them in the future.
+fn leading_timestamp(text: &str, year: i32) -> Option<(DateTime<Utc>, &str)> {
+    let text = text.trim_start();
+    // RFC 3339 is the only layout here with a `Z` or `+hh:mm` offset
+    let token = text.split_whitespace().next()?;
+    if let Ok(time) = DateTime::parse_from_rfc3339(token) {
+        return Some((time.with_timezone(&Utc), &text[token.len()..]));
+    }
+    if let Ok((time, rest)) = DateTime::parse_and_remainder(text, "%d/%b/%Y:%H:%M:%S %z") {
+        return Some((time.with_timezone(&Utc), rest));
+    }
+    for format in NAIVE_FORMATS {
+        if let Ok((time, rest)) = NaiveDateTime::parse_and_remainder(text, format) {
+            return Some((local(time)?, rest));
+        }
+    }
+
+    if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let with_year = format!("{} {}", year, text);
+    let (time, rest) = NaiveDateTime::parse_and_remainder(&with_year, "%Y %b %e %H:%M:%S").ok()?;
+    let rest = &text[text.len() - rest.len()..];
+    let time = local(time)?;
+    if time > Utc::now() + chrono::Duration::days(1) {
+        // Logged last year, e.g. a December line read in January
+        let time = time.with_timezone(&Local).with_year(year - 1)?;
+        return Some((time.with_timezone(&Utc), rest));
+    }
+    Some((time, rest))
+}
+
+fn local(time: NaiveDateTime) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{InputFormat, Parser};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_line_timestamps() {
+        assert_eq!(
+            line_timestamp("2026-10-17T09:00:00Z ERROR boom", None),
+            Some(utc("2026-10-17T09:00:00Z"))
+        );
+        assert_eq!(
+            line_timestamp(
+                r#"127.0.0.1 - - [17/Oct/2026:09:00:00 +0200] "GET / HTTP/1.1" 200 5"#,
+                None
+            ),
+            Some(utc("2026-10-17T07:00:00Z"))
+        );
+        let local_time = line_timestamp("2026-10-17 09:00:00,123 INFO ready", None).unwrap();
+        assert_eq!(
+            local_time.with_timezone(&Local).naive_local(),
+            NaiveDateTime::parse_from_str("2026-10-17 09:00:00.123", "%Y-%m-%d %H:%M:%S%.f")
+                .unwrap()
+        );
+        assert!(line_timestamp("Oct  9 22:14:15 host sshd[42]: accepted", None).is_some());
+        assert_eq!(line_timestamp("ERROR no time here", None), None);
+        assert_eq!(line_timestamp("\tat com.example.Main", None), None);
+    }
+
+    #[test]
+    fn test_record_time_fields() {
+        let parser = Parser::new(InputFormat::Json);
+        let record = parser.parse(r#"{"ts": 1792227600, "msg": "ready"}"#);
+        assert_eq!(
+            line_timestamp("", record.as_ref()),
+            Some(utc("2026-10-17T09:00:00Z"))
+        );
+        let record = parser.parse(r#"{"time": "2026-10-17T09:00:00.5Z"}"#);
+        assert_eq!(
+            line_timestamp("", record.as_ref()),
+            Some(utc("2026-10-17T09:00:00.5Z"))
+        );
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let since = parse_since("15m").unwrap();
+        let expected = Utc::now() - chrono::Duration::minutes(15);
+        assert!((since - expected).num_seconds().abs() <= 1);
+
+        assert_eq!(
+            parse_since("2026-10-17T09:00:00Z").unwrap(),
+            utc("2026-10-17T09:00:00Z")
+        );
+        assert!(parse_since("2026-10-17T09:00").is_ok());
+        assert!(parse_since("2026-10-17").is_ok());
+        assert!(parse_since("yesterday-ish").is_err());
+    }
+
+    #[test]
+    fn test_offset_since() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        for minute in 0..60 {
+            let line = format!("2026-10-17T09:{:02}:00Z INFO tick {}\n", minute, minute);
+            let continuation = "\tcontinued\n";
+            offsets.push(offset);
+            offset += (line.len() + continuation.len()) as u64;
+            write!(temp_file, "{}{}", line, continuation).unwrap();
+        }
+        temp_file.flush().unwrap();
+        let timestamp_of = |line: &str| line_timestamp(line, None);
+        let utf8 = TextEncoding::default();
+
+        for minute in [0, 1, 30, 59] {
+            let since = utc(&format!("2026-10-17T09:{:02}:00Z", minute));
+            assert_eq!(
+                offset_since(temp_file.path(), since, utf8, timestamp_of).unwrap(),
+                offsets[minute]
+            );
+        }
+        // Between two lines: start at the later one
+        let since = utc("2026-10-17T09:30:30Z");
+        assert_eq!(
+            offset_since(temp_file.path(), since, utf8, timestamp_of).unwrap(),
+            offsets[31]
+        );
+        // Before the first line and after the last
+        let since = utc("2026-10-17T08:00:00Z");
+        assert_eq!(
+            offset_since(temp_file.path(), since, utf8, timestamp_of).unwrap(),
+            0
+        );
+        let since = utc("2026-10-17T10:00:00Z");
+        assert_eq!(
+            offset_since(temp_file.path(), since, utf8, timestamp_of).unwrap(),
+            offset
+        );
+    }
+
+    #[test]
+    fn test_offset_since_in_utf16() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let text: String = (0..30)
+            .map(|minute| format!("2026-10-17T09:{:02}:00Z INFO tick\n", minute))
+            .collect();
+        let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        temp_file.write_all(&bytes).unwrap();
+        temp_file.flush().unwrap();
+
+        let utf16 = TextEncoding::for_label("utf-16le").unwrap();
+        let since = utc("2026-10-17T09:17:00Z");
+        let timestamp_of = |line: &str| line_timestamp(line, None);
+        let offset = offset_since(temp_file.path(), since, utf16, timestamp_of).unwrap();
+        let line_len = "2026-10-17T09:00:00Z INFO tick\n".len() as u64 * 2;
+        assert_eq!(offset, 17 * line_len);
+    }
+}
@@ -0,0 +1,818 @@
+//! Full-screen live view of tail mode (`--tui`). Lines from every source are
+//! kept in a scrollable history, shown merged or one file at a time, under
+//! a status bar with the watcher's counters and a sparkline of recent
+//! matches per rule. While paused, new lines are buffered and added when
+//! the view resumes; past the history limit the oldest are dropped.
+
+use crate::highlighter::WatcherStats;
+use crate::matcher::MatchResult;
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute, queue};
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Lines kept for scrolling back; older ones are dropped
+const MAX_LINES: usize = 10_000;
+
+/// Seconds of match history in each rule's sparkline
+const SPARKLINE_WIDTH: usize = 30;
+
+const SPARKLINE_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Rules shown with a sparkline; the rest are still counted in the status bar
+const MAX_RULE_ROWS: usize = 5;
+
+/// How often the screen is redrawn
+pub const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+const HELP: &str = "q quit  space pause  m quiet  / filter  \\ drop filter  n/N next/prev match  tab view  ↑↓ PgUp PgDn g G scroll";
+
+/// Whether the terminal belongs to the TUI, so nothing else may write to it
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+struct TuiLine {
+    /// Increasing, so lines can be found again after older ones are dropped
+    id: u64,
+    source: usize,
+    text: String,
+    matched: bool,
+    color: Option<Color>,
+}
+
+/// A view filter typed with `/`: lines must contain the text, or with a
+/// leading `!` must not
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Filter {
+    text: String,
+    exclude: bool,
+}
+
+impl Filter {
+    fn parse(input: &str) -> Self {
+        match input.strip_prefix('!') {
+            Some(text) => Self {
+                text: text.to_string(),
+                exclude: true,
+            },
+            None => Self {
+                text: input.to_string(),
+                exclude: false,
+            },
+        }
+    }
+
+    fn allows(&self, line: &str) -> bool {
+        line.contains(&self.text) != self.exclude
+    }
+}
+
+#[derive(Debug)]
+struct RuleActivity {
+    name: String,
+    color: Option<Color>,
+    /// Matches per second, oldest first; the last bucket is the current one
+    buckets: VecDeque<usize>,
+    total: usize,
+}
+
+/// Everything shown on screen, apart from the terminal itself
+#[derive(Debug)]
+pub struct TuiState {
+    sources: Vec<PathBuf>,
+    lines: VecDeque<TuiLine>,
+    next_id: u64,
+    paused: bool,
+    /// Lines that arrived while paused, at most `MAX_LINES` of them
+    pending: VecDeque<TuiLine>,
+    /// Lines dropped from `pending` during this pause
+    dropped: usize,
+    /// The source shown alone, or `None` for all of them merged
+    view: Option<usize>,
+    /// Show only matching lines
+    quiet: bool,
+    filters: Vec<Filter>,
+    /// Id of the top line when scrolled back; `None` follows new lines
+    scroll: Option<u64>,
+    /// The match jumped to with `n` or `N`
+    selected: Option<u64>,
+    /// A filter being typed
+    input: Option<String>,
+    message: Option<String>,
+    rules: Vec<RuleActivity>,
+    bucket_started: Instant,
+    /// Rows of the log area at the last redraw, for paging
+    page: usize,
+}
+
+impl TuiState {
+    /// `rules` are the rule names and colors, in rule order
+    pub fn new(rules: &[(String, Option<termcolor::Color>)], quiet: bool) -> Self {
+        let rules = rules
+            .iter()
+            .map(|(name, color)| RuleActivity {
+                name: name.clone(),
+                color: color.map(term_color),
+                buckets: VecDeque::from([0]),
+                total: 0,
+            })
+            .collect();
+        Self {
+            sources: Vec::new(),
+            lines: VecDeque::new(),
+            next_id: 0,
+            paused: false,
+            pending: VecDeque::new(),
+            dropped: 0,
+            view: None,
+            quiet,
+            filters: Vec::new(),
+            scroll: None,
+            selected: None,
+            input: None,
+            message: None,
+            rules,
+            bucket_started: Instant::now(),
+            page: 1,
+        }
+    }
+
+    /// Add a line read from a source, counting its matches per rule
+    pub fn push(&mut self, source: &Path, line: &str, match_result: &MatchResult) {
+        for pattern in &match_result.patterns {
+            if let Some(rule) = self.rules.iter_mut().find(|rule| &rule.name == pattern) {
+                rule.total += 1;
+                if let Some(bucket) = rule.buckets.back_mut() {
+                    *bucket += 1;
+                }
+            }
+        }
+
+        let source = match self.sources.iter().position(|known| known == source) {
+            Some(index) => index,
+            None => {
+                self.sources.push(source.to_path_buf());
+                self.sources.len() - 1
+            }
+        };
+        let color = match_result.color.map(term_color);
+        let line = TuiLine {
+            id: self.next_id,
+            source,
+            text: printable(line),
+            matched: match_result.matched,
+            color,
+        };
+        self.next_id += 1;
+
+        if self.paused {
+            self.pending.push_back(line);
+            if self.pending.len() > MAX_LINES {
+                self.pending.pop_front();
+                self.dropped += 1;
+            }
+        } else {
+            self.lines.push_back(line);
+            self.trim();
+        }
+    }
+
+    /// Show a message from the watcher, such as a rotation, until the next
+    /// one or the next key
+    pub fn show_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /// Start new sparkline buckets for the seconds that have passed
+    pub fn tick(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.bucket_started).as_secs();
+        if elapsed == 0 {
+            return;
+        }
+        self.bucket_started += Duration::from_secs(elapsed);
+        for rule in &mut self.rules {
+            for _ in 0..elapsed.min(SPARKLINE_WIDTH as u64) {
+                rule.buckets.push_back(0);
+                if rule.buckets.len() > SPARKLINE_WIDTH {
+                    rule.buckets.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Act on a terminal event. Returns whether to quit.
+    pub fn handle_event(&mut self, event: Event) -> bool {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => self.handle_key(key),
+            _ => false,
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return true;
+        }
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Enter => {
+                    if !input.is_empty() {
+                        self.filters.push(Filter::parse(input));
+                    }
+                    self.input = None;
+                }
+                KeyCode::Esc => self.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return false;
+        }
+
+        self.message = None;
+        let page = self.page.max(1) as isize;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Char(' ') | KeyCode::Char('p') => self.toggle_pause(),
+            KeyCode::Char('m') => self.quiet = !self.quiet,
+            KeyCode::Char('/') => self.input = Some(String::new()),
+            KeyCode::Char('\\') => {
+                self.filters.pop();
+            }
+            KeyCode::Char('n') => self.jump_to_match(true),
+            KeyCode::Char('N') => self.jump_to_match(false),
+            KeyCode::Tab => self.cycle_view(true),
+            KeyCode::BackTab => self.cycle_view(false),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::PageUp => self.scroll_by(-page),
+            KeyCode::PageDown => self.scroll_by(page),
+            KeyCode::Home | KeyCode::Char('g') => {
+                self.scroll = self.visible().first().map(|l| l.id)
+            }
+            KeyCode::End | KeyCode::Char('G') => {
+                self.scroll = None;
+                self.selected = None;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if !self.paused {
+            self.lines.extend(self.pending.drain(..));
+            self.dropped = 0;
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    fn cycle_view(&mut self, forward: bool) {
+        let count = self.sources.len();
+        if count == 0 {
+            return;
+        }
+        self.view = match (self.view, forward) {
+            (None, true) => Some(0),
+            (None, false) => Some(count - 1),
+            (Some(i), true) if i + 1 < count => Some(i + 1),
+            (Some(i), false) if i > 0 => Some(i - 1),
+            _ => None,
+        };
+        self.scroll = None;
+        self.selected = None;
+    }
+
+    fn shows(&self, line: &TuiLine) -> bool {
+        self.view.is_none_or(|view| line.source == view)
+            && (line.matched || !self.quiet)
+            && self.filters.iter().all(|filter| filter.allows(&line.text))
+    }
+
+    fn visible(&self) -> Vec<&TuiLine> {
+        self.lines.iter().filter(|line| self.shows(line)).collect()
+    }
+
+    /// Index of the top visible line, given how many lines fit
+    fn top(&self, visible: &[&TuiLine], rows: usize) -> usize {
+        let last_page = visible.len().saturating_sub(rows);
+        match self.scroll {
+            Some(id) => visible.partition_point(|line| line.id < id).min(last_page),
+            None => last_page,
+        }
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        let visible = self.visible();
+        let last_page = visible.len().saturating_sub(self.page);
+        let top = self.top(&visible, self.page);
+        let top = top.saturating_add_signed(delta).min(last_page);
+        // Scrolling down to the end follows new lines again
+        self.scroll = if delta > 0 && top == last_page {
+            None
+        } else {
+            visible.get(top).map(|line| line.id)
+        };
+    }
+
+    /// Select the next (or previous) matching line and scroll to it
+    fn jump_to_match(&mut self, forward: bool) {
+        let visible = self.visible();
+        let top = self.top(&visible, self.page);
+        let found = match (self.selected, forward) {
+            (Some(selected), true) => visible.iter().find(|l| l.matched && l.id > selected),
+            (Some(selected), false) => visible.iter().rev().find(|l| l.matched && l.id < selected),
+            (None, true) => visible[top..].iter().find(|l| l.matched),
+            (None, false) => {
+                let bottom = (top + self.page).min(visible.len());
+                visible[..bottom].iter().rev().find(|l| l.matched)
+            }
+        };
+        let Some(found) = found.map(|line| line.id) else {
+            self.message = Some("No more matches".to_string());
+            return;
+        };
+        let index = visible.partition_point(|line| line.id < found);
+        if index < top || index >= top + self.page {
+            self.scroll = Some(found);
+        }
+        self.selected = Some(found);
+    }
+
+    /// The status bar: view, counters and modes
+    fn status(&self, stats: &WatcherStats) -> String {
+        let view = match self.view {
+            Some(view) => display_name(&self.sources[view]),
+            None => "all files".to_string(),
+        };
+        let mut status = format!(
+            " {} | files {} | lines {} | excluded {} | matches {} | notified {}",
+            view,
+            (stats.files_watched + stats.files_added).saturating_sub(stats.files_removed),
+            stats.lines_processed,
+            stats.lines_excluded,
+            stats.matches_found,
+            stats.notifications_sent,
+        );
+        if self.paused {
+            status.push_str(&format!(" | PAUSED ({} buffered", self.pending.len()));
+            if self.dropped > 0 {
+                status.push_str(&format!(", {} dropped", self.dropped));
+            }
+            status.push(')');
+        }
+        if self.quiet {
+            status.push_str(" | quiet");
+        }
+        if !self.filters.is_empty() {
+            let filters: Vec<String> = self
+                .filters
+                .iter()
+                .map(|filter| {
+                    let negation = if filter.exclude { "!" } else { "" };
+                    format!("{}{}", negation, filter.text)
+                })
+                .collect();
+            status.push_str(&format!(" | filters: {}", filters.join(", ")));
+        }
+        if self.scroll.is_some() {
+            status.push_str(" | scrolled (G to follow)");
+        }
+        status
+    }
+
+    /// Draw the whole screen
+    pub fn render<W: Write>(
+        &mut self,
+        out: &mut W,
+        stats: &WatcherStats,
+        width: u16,
+        height: u16,
+    ) -> io::Result<()> {
+        let width = usize::from(width);
+        let height = usize::from(height);
+        let rule_rows = self.rules.len().min(MAX_RULE_ROWS);
+        let log_rows = height.saturating_sub(rule_rows + 2);
+        self.page = log_rows;
+
+        let visible = self.visible();
+        let top = self.top(&visible, log_rows);
+        let prefix_files = self.view.is_none() && self.sources.len() > 1;
+        let mut row: u16 = 0;
+
+        for i in 0..log_rows {
+            queue!(out, cursor::MoveTo(0, row))?;
+            if let Some(line) = visible.get(top + i) {
+                let mut text = String::new();
+                if prefix_files {
+                    text.push_str(&format!("[{}] ", display_name(&self.sources[line.source])));
+                }
+                text.push_str(&line.text);
+                if Some(line.id) == self.selected {
+                    queue!(out, SetAttribute(Attribute::Reverse))?;
+                }
+                if line.matched {
+                    match line.color {
+                        Some(color) => queue!(out, SetForegroundColor(color))?,
+                        None => queue!(out, SetAttribute(Attribute::Bold))?,
+                    }
+                }
+                queue!(
+                    out,
+                    Print(truncate(&text, width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            }
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+            row += 1;
+        }
+
+        for rule in self.rules.iter().take(rule_rows) {
+            if usize::from(row) >= height {
+                break;
+            }
+            let name = truncate(&rule.name, 16);
+            queue!(out, cursor::MoveTo(0, row), Print(format!("{:<16} ", name)))?;
+            if let Some(color) = rule.color {
+                queue!(out, SetForegroundColor(color))?;
+            }
+            queue!(
+                out,
+                Print(sparkline(&rule.buckets)),
+                SetAttribute(Attribute::Reset),
+                Print(format!(" {}", rule.total)),
+                Clear(ClearType::UntilNewLine)
+            )?;
+            row += 1;
+        }
+
+        if usize::from(row) < height {
+            let status = format!("{:<width$}", truncate(&self.status(stats), width));
+            queue!(
+                out,
+                cursor::MoveTo(0, row),
+                SetAttribute(Attribute::Reverse),
+                Print(status),
+                SetAttribute(Attribute::Reset)
+            )?;
+            row += 1;
+        }
+
+        if usize::from(row) < height {
+            let bottom = match (&self.input, &self.message) {
+                (Some(input), _) => format!("Filter (!text to hide): {}", input),
+                (None, Some(message)) => message.clone(),
+                (None, None) => HELP.to_string(),
+            };
+            queue!(
+                out,
+                cursor::MoveTo(0, row),
+                Print(truncate(&bottom, width)),
+                Clear(ClearType::UntilNewLine)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The terminal while the TUI owns it: raw mode on the alternate screen,
+/// restored when dropped
+#[derive(Debug)]
+pub struct Tui {
+    pub state: TuiState,
+    /// Stops the thread reading terminal events
+    stop: Arc<AtomicBool>,
+}
+
+impl Tui {
+    /// Take over the terminal. Key presses and resizes arrive on the
+    /// returned channel.
+    pub fn start(state: TuiState) -> Result<(Self, mpsc::Receiver<Event>)> {
+        if !io::stdout().is_terminal() {
+            anyhow::bail!("--tui needs a terminal");
+        }
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        ACTIVE.store(true, Ordering::Relaxed);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel(64);
+        let stop_reading = stop.clone();
+        std::thread::spawn(move || {
+            while !stop_reading.load(Ordering::Relaxed) {
+                match event::poll(FRAME_INTERVAL) {
+                    Ok(true) => match event::read() {
+                        Ok(event) => {
+                            if tx.blocking_send(event).is_err() {
+                                break;
+                            }
+                        }
+                        Err(_) => break,
+                    },
+                    Ok(false) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok((Self { state, stop }, rx))
+    }
+
+    pub fn draw(&mut self, stats: &WatcherStats) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        // Drawn off screen first, so the terminal never shows half a frame
+        let mut frame = Vec::new();
+        self.state.render(&mut frame, stats, width, height)?;
+        let mut stdout = io::stdout();
+        stdout.write_all(&frame)?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+        let _ = terminal::disable_raw_mode();
+        ACTIVE.store(false, Ordering::Relaxed);
+    }
+}
+
+/// One bar per second, scaled to the busiest second; quiet seconds are blank
+fn sparkline(buckets: &VecDeque<usize>) -> String {
+    let max = buckets.iter().copied().max().unwrap_or(0);
+    let bars: String = buckets
+        .iter()
+        .map(|&count| match count {
+            0 => ' ',
+            count => {
+                let level = (count * SPARKLINE_BLOCKS.len()).div_ceil(max);
+                SPARKLINE_BLOCKS[level.clamp(1, SPARKLINE_BLOCKS.len()) - 1]
+            }
+        })
+        .collect();
+    format!("{:>width$}", bars, width = SPARKLINE_WIDTH)
+}
+
+/// A line as it can be shown on one row: tabs and the newlines of
+/// multiline records become spaces
+fn printable(line: &str) -> String {
+    line.chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+fn truncate(text: &str, width: usize) -> &str {
+    match text.char_indices().nth(width) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// The crossterm color for a rule's color
+fn term_color(color: termcolor::Color) -> Color {
+    match color {
+        termcolor::Color::Black => Color::Black,
+        termcolor::Color::Red => Color::DarkRed,
+        termcolor::Color::Green => Color::DarkGreen,
+        termcolor::Color::Yellow => Color::DarkYellow,
+        termcolor::Color::Blue => Color::DarkBlue,
+        termcolor::Color::Magenta => Color::DarkMagenta,
+        termcolor::Color::Cyan => Color::DarkCyan,
+        termcolor::Color::White => Color::Grey,
+        termcolor::Color::Ansi256(value) => Color::AnsiValue(value),
+        termcolor::Color::Rgb(r, g, b) => Color::Rgb { r, g, b },
+        _ => Color::Reset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> TuiState {
+        let rules = vec![
+            ("ERROR".to_string(), Some(termcolor::Color::Red)),
+            ("WARN".to_string(), None),
+        ];
+        TuiState::new(&rules, false)
+    }
+
+    fn matched(rule: &str) -> MatchResult {
+        MatchResult {
+            matched: true,
+            pattern: Some(rule.to_string()),
+            patterns: vec![rule.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn shown(state: &TuiState) -> Vec<&str> {
+        state
+            .visible()
+            .iter()
+            .map(|line| line.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_pause_buffers_lines() {
+        let mut state = state();
+        let source = Path::new("app.log");
+        state.push(source, "one", &MatchResult::default());
+        state.handle_event(key(KeyCode::Char(' ')));
+        state.push(source, "two", &MatchResult::default());
+        state.push(source, "ERROR three", &matched("ERROR"));
+        assert_eq!(shown(&state), vec!["one"]);
+        assert!(state
+            .status(&WatcherStats::default())
+            .contains("PAUSED (2 buffered)"));
+        // Matches are counted while paused
+        assert_eq!(state.rules[0].total, 1);
+
+        state.handle_event(key(KeyCode::Char(' ')));
+        assert_eq!(shown(&state), vec!["one", "two", "ERROR three"]);
+    }
+
+    #[test]
+    fn test_pause_buffer_is_capped() {
+        let mut state = state();
+        let source = Path::new("app.log");
+        state.handle_event(key(KeyCode::Char(' ')));
+        for i in 0..MAX_LINES + 5 {
+            state.push(source, &format!("line {}", i), &MatchResult::default());
+        }
+        assert!(state
+            .status(&WatcherStats::default())
+            .contains(&format!("PAUSED ({} buffered, 5 dropped)", MAX_LINES)));
+
+        state.handle_event(key(KeyCode::Char(' ')));
+        assert_eq!(state.lines.len(), MAX_LINES);
+        assert_eq!(state.lines.front().unwrap().text, "line 5");
+
+        // The count starts again with the next pause
+        state.handle_event(key(KeyCode::Char(' ')));
+        assert!(state
+            .status(&WatcherStats::default())
+            .contains("PAUSED (0 buffered)"));
+    }
+
+    #[test]
+    fn test_quiet_filters_and_views() {
+        let mut state = state();
+        state.push(Path::new("a.log"), "INFO a", &MatchResult::default());
+        state.push(Path::new("b.log"), "ERROR b", &matched("ERROR"));
+        state.push(Path::new("a.log"), "WARN a", &matched("WARN"));
+
+        state.handle_event(key(KeyCode::Char('m')));
+        assert_eq!(shown(&state), vec!["ERROR b", "WARN a"]);
+        state.handle_event(key(KeyCode::Char('m')));
+
+        state.handle_event(key(KeyCode::Char('/')));
+        for c in "!INFO".chars() {
+            state.handle_event(key(KeyCode::Char(c)));
+        }
+        state.handle_event(key(KeyCode::Enter));
+        assert_eq!(shown(&state), vec!["ERROR b", "WARN a"]);
+        state.handle_event(key(KeyCode::Char('\\')));
+        assert_eq!(shown(&state).len(), 3);
+
+        state.handle_event(key(KeyCode::Tab));
+        assert_eq!(shown(&state), vec!["INFO a", "WARN a"]);
+        state.handle_event(key(KeyCode::Tab));
+        assert_eq!(shown(&state), vec!["ERROR b"]);
+        state.handle_event(key(KeyCode::Tab));
+        assert_eq!(shown(&state).len(), 3);
+    }
+
+    #[test]
+    fn test_jump_between_matches() {
+        let mut state = state();
+        let source = Path::new("app.log");
+        for i in 0..20 {
+            if i % 5 == 0 {
+                state.push(source, &format!("ERROR {}", i), &matched("ERROR"));
+            } else {
+                state.push(source, &format!("line {}", i), &MatchResult::default());
+            }
+        }
+        state.page = 6;
+        // From the top of the last page, the next match is line 15
+        state.handle_event(key(KeyCode::Char('n')));
+        assert_eq!(state.selected, Some(15));
+        state.handle_event(key(KeyCode::Char('N')));
+        assert_eq!(state.selected, Some(10));
+        assert_eq!(state.scroll, Some(10));
+        state.handle_event(key(KeyCode::Char('N')));
+        state.handle_event(key(KeyCode::Char('N')));
+        state.handle_event(key(KeyCode::Char('N')));
+        assert_eq!(state.selected, Some(0));
+        assert_eq!(state.message.as_deref(), Some("No more matches"));
+
+        state.handle_event(key(KeyCode::Char('G')));
+        assert_eq!(state.scroll, None);
+        assert!(state.handle_event(key(KeyCode::Char('q'))));
+    }
+
+    #[test]
+    fn test_scrolling() {
+        let mut state = state();
+        for i in 0..10 {
+            state.push(
+                Path::new("app.log"),
+                &i.to_string(),
+                &MatchResult::default(),
+            );
+        }
+        state.page = 3;
+        state.handle_event(key(KeyCode::Up));
+        assert_eq!(state.scroll, Some(6));
+        state.handle_event(key(KeyCode::PageUp));
+        assert_eq!(state.scroll, Some(3));
+        state.handle_event(key(KeyCode::Home));
+        assert_eq!(state.scroll, Some(0));
+        state.handle_event(key(KeyCode::PageUp));
+        assert_eq!(state.scroll, Some(0));
+        // Reaching the end follows new lines again
+        for _ in 0..3 {
+            state.handle_event(key(KeyCode::PageDown));
+        }
+        assert_eq!(state.scroll, None);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        let buckets = VecDeque::from([0, 1, 4, 8, 2]);
+        let bars = sparkline(&buckets);
+        assert_eq!(bars.chars().count(), SPARKLINE_WIDTH);
+        assert!(bars.ends_with(" ▁▄█▂"));
+    }
+
+    #[test]
+    fn test_tick_starts_new_buckets() {
+        let mut state = state();
+        let start = state.bucket_started;
+        state.push(Path::new("app.log"), "ERROR", &matched("ERROR"));
+        state.tick(start + Duration::from_millis(2500));
+        assert_eq!(state.rules[0].buckets, VecDeque::from([1, 0, 0]));
+        state.tick(start + Duration::from_secs(3600));
+        assert_eq!(state.rules[0].buckets.len(), SPARKLINE_WIDTH);
+        assert!(state.rules[0].buckets.iter().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut state = state();
+        state.push(
+            Path::new("a.log"),
+            "INFO\tstarting",
+            &MatchResult::default(),
+        );
+        state.push(Path::new("b.log"), "ERROR boom", &matched("ERROR"));
+        let mut frame = Vec::new();
+        state
+            .render(&mut frame, &WatcherStats::default(), 60, 8)
+            .unwrap();
+        let frame = String::from_utf8(frame).unwrap();
+        assert!(frame.contains("[a.log] INFO starting"));
+        assert!(frame.contains("[b.log] ERROR boom"));
+        assert!(frame.contains(" all files | files 0"));
+        assert_eq!(state.page, 4);
+
+        // Too small to show anything but must not fail
+        state
+            .render(&mut Vec::new(), &WatcherStats::default(), 1, 1)
+            .unwrap();
+    }
+}
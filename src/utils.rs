@@ -1,26 +1,77 @@
+use crate::encoding::TextEncoding;
 use anyhow::{Context, Result};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 
-/// Read all lines from a file
-pub fn read_file_from_end<P: AsRef<Path>>(path: P, _buffer_size: usize) -> Result<Vec<String>> {
-    let file = File::open(&path)
+/// Read the last `count` lines of a UTF-8 file, blank ones included,
+/// without reading the lines before them
+pub fn read_file_from_end<P: AsRef<Path>>(
+    path: P,
+    count: usize,
+    buffer_size: usize,
+) -> Result<Vec<String>> {
+    let encoding = TextEncoding::default();
+    let offset = offset_of_last_lines(&path, count, buffer_size, encoding)?;
+    let mut file = File::open(&path)
         .with_context(|| format!("Failed to open file: {}", path.as_ref().display()))?;
+    file.seek(SeekFrom::Start(offset))?;
 
-    let reader = BufReader::new(file);
+    let mut reader = BufReader::with_capacity(buffer_size.max(1), file);
     let mut lines = Vec::new();
+    let mut line = Vec::new();
 
-    for line_result in reader.lines() {
-        let line = line_result?;
-        if !line.trim().is_empty() {
-            lines.push(line.trim().to_string());
-        }
+    while encoding.read_line(&mut reader, &mut line)? > 0 {
+        lines.push(encoding.decode(&line));
+        line.clear();
     }
 
     Ok(lines)
 }
 
+/// Byte offset where the last `count` lines of a file begin, found by
+/// reading backwards from the end in `buffer_size` chunks
+pub fn offset_of_last_lines<P: AsRef<Path>>(
+    path: P,
+    count: usize,
+    buffer_size: usize,
+    encoding: TextEncoding,
+) -> Result<u64> {
+    let mut file = File::open(&path)
+        .with_context(|| format!("Failed to open file: {}", path.as_ref().display()))?;
+    // Chunks and the end are kept on code unit boundaries
+    let unit = encoding.unit_len();
+    let len = file.metadata()?.len();
+    let len = len - len % unit as u64;
+    if count == 0 {
+        return Ok(len);
+    }
+
+    let mut buffer = vec![0u8; buffer_size.max(unit) / unit * unit];
+    let mut position = len;
+    let mut found = 0;
+
+    while position > 0 {
+        let chunk = (buffer.len() as u64).min(position) as usize;
+        position -= chunk as u64;
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut buffer[..chunk])?;
+
+        for index in (0..chunk).step_by(unit).rev() {
+            let next = position + (index + unit) as u64;
+            // The newline ending the last line doesn't start another one
+            if encoding.ends_line(&buffer[index..index + unit]) && next < len {
+                found += 1;
+                if found == count {
+                    return Ok(next);
+                }
+            }
+        }
+    }
+
+    Ok(0)
+}
+
 /// Check if a file exists and is readable
 pub fn is_file_readable<P: AsRef<Path>>(path: P) -> bool {
     File::open(path).is_ok()
@@ -68,6 +119,59 @@ pub fn validate_files<P: AsRef<Path> + Clone>(files: &[P]) -> Result<Vec<P>> {
     Ok(valid_files)
 }
 
+/// Filesystems where inotify misses writes made by other machines
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "virtiofs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "afs",
+    "fuse.sshfs",
+    "fuse.glusterfs",
+    "fuse.grpcfuse",
+    "fuse.osxfs",
+];
+
+/// Check if a file lives on a network or shared filesystem, where change
+/// events can't be relied on
+#[cfg(target_os = "linux")]
+pub fn is_network_filesystem<P: AsRef<Path>>(path: P) -> bool {
+    let Ok(path) = path.as_ref().canonicalize() else {
+        return false;
+    };
+    let Ok(mounts) = std::fs::read_to_string("/proc/self/mounts") else {
+        return false;
+    };
+    mount_fs_type(&mounts, &path).is_some_and(|fs_type| NETWORK_FILESYSTEMS.contains(&fs_type))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_network_filesystem<P: AsRef<Path>>(_path: P) -> bool {
+    false
+}
+
+/// Filesystem type of the mount containing `path`, from a `/proc/mounts` table
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn mount_fs_type<'a>(mounts: &'a str, path: &Path) -> Option<&'a str> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            Some((mount_point, fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, fs_type)| fs_type)
+}
+
 /// Format file size in human-readable format
 pub fn format_file_size(size: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
@@ -115,6 +219,7 @@ pub fn resolve_symlink<P: AsRef<Path>>(path: P) -> Result<std::path::PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
+
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -159,7 +264,7 @@ mod tests {
         writeln!(temp_file, "line 3").unwrap();
         temp_file.flush().unwrap();
 
-        let lines = read_file_from_end(temp_file.path(), 1024).unwrap();
+        let lines = read_file_from_end(temp_file.path(), 3, 1024).unwrap();
         assert_eq!(lines.len(), 3);
         assert_eq!(lines[0], "line 1");
         assert_eq!(lines[1], "line 2");
@@ -175,15 +280,55 @@ mod tests {
         writeln!(temp_file, "line 2").unwrap();
         temp_file.flush().unwrap();
 
-        let lines = read_file_from_end(temp_file.path(), 1024).unwrap();
-        assert_eq!(lines.len(), 2); // Empty lines should be filtered out
-        assert_eq!(lines[0], "line 1");
-        assert_eq!(lines[1], "line 2");
+        let lines = read_file_from_end(temp_file.path(), 3, 1024).unwrap();
+        assert_eq!(lines, vec!["", "   ", "line 2"]);
+    }
+
+    #[test]
+    fn test_read_last_lines_backwards() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        for i in 1..=100 {
+            writeln!(temp_file, "line {}", i).unwrap();
+        }
+        writeln!(temp_file).unwrap();
+        write!(temp_file, "partial").unwrap();
+        temp_file.flush().unwrap();
+
+        // A small buffer makes lines cross chunk boundaries
+        let lines = read_file_from_end(temp_file.path(), 3, 4).unwrap();
+        assert_eq!(lines, vec!["line 100", "", "partial"]);
+
+        let utf8 = TextEncoding::default();
+        let offset = offset_of_last_lines(temp_file.path(), 1, 4, utf8).unwrap();
+        let len = std::fs::metadata(temp_file.path()).unwrap().len();
+        assert_eq!(offset, len - "partial".len() as u64);
+        assert_eq!(
+            offset_of_last_lines(temp_file.path(), 0, 4, utf8).unwrap(),
+            len
+        );
+        assert_eq!(
+            offset_of_last_lines(temp_file.path(), 500, 4, utf8).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_last_lines_of_utf16_file() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        // U+0A0A has a newline byte in both halves
+        let text = "first\nERROR \u{0a0a}\nlast\n";
+        let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        temp_file.write_all(&bytes).unwrap();
+        temp_file.flush().unwrap();
+
+        let utf16 = TextEncoding::for_label("utf-16le").unwrap();
+        let offset = offset_of_last_lines(temp_file.path(), 2, 3, utf16).unwrap();
+        assert_eq!(offset, "first\n".len() as u64 * 2);
     }
 
     #[test]
     fn test_read_file_from_end_with_nonexistent_file() {
-        let result = read_file_from_end("/nonexistent/file.log", 1024);
+        let result = read_file_from_end("/nonexistent/file.log", 3, 1024);
         assert!(result.is_err());
         assert!(result
             .unwrap_err()
@@ -348,7 +493,7 @@ mod tests {
         temp_file.flush().unwrap();
 
         // Test read_file_from_end to cover line 11 (BufReader::new)
-        let result = read_file_from_end(temp_file.path(), 1024);
+        let result = read_file_from_end(temp_file.path(), 3, 1024);
         assert!(result.is_ok());
         let lines = result.unwrap();
         assert_eq!(lines.len(), 3);
@@ -403,4 +548,29 @@ mod tests {
         let error_msg = result.unwrap_err().to_string();
         assert!(error_msg.contains("Failed to read symlink"));
     }
+
+    #[test]
+    fn test_mount_fs_type() {
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n\
+                      server:/logs /mnt/logs nfs4 rw 0 0\n\
+                      //nas/share /mnt/my\\040share cifs rw 0 0\n";
+
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/var/log/app.log")),
+            Some("ext4")
+        );
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/mnt/logs/app.log")),
+            Some("nfs4")
+        );
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/mnt/my share/app.log")),
+            Some("cifs")
+        );
+        // Component-wise: /mnt/logsearch is not under /mnt/logs
+        assert_eq!(
+            mount_fs_type(mounts, Path::new("/mnt/logsearch/app.log")),
+            Some("ext4")
+        );
+    }
 }
@@ -1,55 +1,140 @@
+use crate::archive::{rotation_set, Compression};
 use crate::config::Config;
+use crate::context::{ContextLines, ContextOutput};
+use crate::encoding::TextEncoding;
+use crate::file_set::FileSet;
 use crate::highlighter::{Highlighter, WatcherStats};
-use crate::matcher::Matcher;
+use crate::matcher::{MatchResult, Matcher};
+use crate::multiline::RecordAssembler;
 use crate::notifier::Notifier;
-use crate::utils::{get_file_size, validate_files};
+use crate::output_file::OutputFile;
+use crate::parser::{Parser, Record};
+use crate::source::{command_label, is_stdin, spawn_command, stdin_label};
+use crate::state::{Checkpoint, StateFile};
+use crate::tail::{FileId, FileTail, LinePosition, TailEvent};
+use crate::timestamp::{line_timestamp, offset_since};
+use crate::tui::{Tui, TuiState, FRAME_INTERVAL};
+use crate::utils::{is_network_filesystem, offset_of_last_lines, validate_files};
 use anyhow::Result;
+use notify::event::{ModifyKind, RenameMode};
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::time::Duration;
-use tokio::sync::mpsc;
-use tokio::time::sleep;
-use tracing::{error, info};
+use std::process::ExitStatus;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncRead;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, sleep_until, timeout, timeout_at};
+use tracing::info;
+
+/// How often read checkpoints are saved to the `--state-file`
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Shortest interval between rescans of directories that can't be watched
+/// for new files
+const MIN_RESCAN_INTERVAL_MS: u64 = 1000;
+
+/// How long a discovered file whose path is gone is still read before it is
+/// released, so that a rename-and-create rotation switches to the new file
+/// without losing what is still written to the old one
+const MISSING_GRACE: Duration = Duration::from_secs(1);
 
 #[derive(Debug)]
 pub struct LogWatcher {
     config: Config,
     matcher: Matcher,
+    parser: Parser,
     highlighter: Highlighter,
     notifier: Notifier,
+    /// Records matched lines when `--output-file` is used
+    output_file: Option<OutputFile>,
+    /// Joins multiline records when `--multiline-*` is used
+    assembler: Option<RecordAssembler>,
+    /// Checkpoints saved short of where a file was read, because a record
+    /// was still being assembled there
+    held_checkpoints: HashMap<PathBuf, Checkpoint>,
+    /// Lines around matches when `-A`, `-B` or `-C` is used
+    context: Option<ContextLines>,
+    /// The full-screen view, while tail mode runs with `--tui`
+    tui: Option<Tui>,
     stats: WatcherStats,
 }
 
 impl LogWatcher {
     pub fn new(config: Config) -> Self {
         let matcher = Matcher::new(config.clone());
+        let parser = Parser::new(config.input_format).with_log_format(config.log_format.clone());
         let highlighter = Highlighter::new(config.clone());
         let notifier = Notifier::new(config.clone());
+        let assembler = config.multiline.clone().map(RecordAssembler::new);
+        let (before, after) = (config.before_context, config.after_context);
+        let context = (before > 0 || after > 0).then(|| ContextLines::new(before, after));
 
         Self {
             config,
             matcher,
+            parser,
             highlighter,
             notifier,
+            output_file: None,
+            assembler,
+            held_checkpoints: HashMap::new(),
+            context,
+            tui: None,
             stats: WatcherStats::default(),
         }
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        // Validate files
-        let valid_files = validate_files(&self.config.files)?;
-        self.stats.files_watched = valid_files.len();
+        // `-` reads standard input; everything else must be a readable file
+        let (stdin, paths): (Vec<PathBuf>, Vec<PathBuf>) = self
+            .config
+            .files
+            .iter()
+            .cloned()
+            .partition(|path| is_stdin(path));
+
+        // Directories and globs stand for the files they match now, and are
+        // watched for more in tail mode
+        let file_set = FileSet::new(&paths)?;
+        let files = file_set.expand();
+        let valid_files = if files.is_empty() {
+            files
+        } else {
+            validate_files(&files)?
+        };
+        self.stats.files_watched = valid_files.len() + usize::from(!stdin.is_empty());
+
+        if let Some(options) = &self.config.output_file {
+            self.output_file = Some(OutputFile::open(
+                options.clone(),
+                self.config.format.clone(),
+                self.config.prefix_files,
+            )?);
+        }
 
         // Print startup information
-        self.highlighter.print_startup_info()?;
+        self.highlighter
+            .print_startup_info(&self.stats, &file_set.patterns())?;
+
+        let sources = Sources {
+            stdin: !stdin.is_empty(),
+            command: self.config.exec.clone(),
+            patterns: file_set.is_dynamic().then_some(file_set),
+        };
 
         if self.config.dry_run {
-            self.run_dry_mode(&valid_files).await?;
+            self.run_dry_mode(&valid_files, &sources).await?;
         } else {
-            self.run_tail_mode(&valid_files).await?;
+            let result = self.run_tail_mode(&valid_files, &sources).await;
+            // The terminal is given back before anything else is printed
+            self.close_tui();
+            result?;
         }
 
         // Print shutdown summary
@@ -58,12 +143,27 @@ impl LogWatcher {
         Ok(())
     }
 
-    async fn run_dry_mode(&mut self, files: &[PathBuf]) -> Result<()> {
+    async fn run_dry_mode(&mut self, files: &[PathBuf], sources: &Sources) -> Result<()> {
         info!("Running in dry-run mode");
 
         let mut pattern_counts: HashMap<String, usize> = HashMap::new();
 
+        let mut scanned = Vec::new();
         for file_path in files {
+            if self.config.include_rotated {
+                match rotation_set(file_path) {
+                    Ok(set) => scanned.extend(set),
+                    Err(e) => {
+                        self.highlighter.print_warning(&format!("{:#}", e))?;
+                        scanned.push(file_path.clone());
+                    }
+                }
+            } else {
+                scanned.push(file_path.clone());
+            }
+        }
+
+        for file_path in &scanned {
             match self.process_existing_file(file_path).await {
                 Ok(matches) => {
                     for (pattern, count) in matches {
@@ -77,27 +177,96 @@ impl LogWatcher {
             }
         }
 
+        // Streams are read to the end, so a command runs until it exits
+        if sources.stdin {
+            self.process_stream(&stdin_label(), tokio::io::stdin(), &mut pattern_counts)
+                .await?;
+        }
+        if let Some(command) = &sources.command {
+            let mut child = spawn_command(command)?;
+            if let Some(stdout) = child.stdout.take() {
+                self.process_stream(&command_label(command), stdout, &mut pattern_counts)
+                    .await?;
+            }
+            self.stats.command_status = Some(child.wait().await?);
+        }
+
         // Print summary
         let summary: Vec<(String, usize)> = pattern_counts.into_iter().collect();
-        self.highlighter.print_dry_run_summary(&summary)?;
+        self.highlighter
+            .print_dry_run_summary(&summary, &self.stats)?;
 
         Ok(())
     }
 
-    async fn run_tail_mode(&mut self, files: &[PathBuf]) -> Result<()> {
+    async fn run_tail_mode(&mut self, files: &[PathBuf], sources: &Sources) -> Result<()> {
         info!("Running in tail mode");
 
+        // Take over the terminal first, so that messages from here on are
+        // shown in the view
+        let mut terminal_events = None;
+        if self.config.tui {
+            let rules: Vec<_> = self
+                .config
+                .rules
+                .iter()
+                .map(|rule| (rule.name.clone(), rule.color))
+                .collect();
+            let (tui, events) = Tui::start(TuiState::new(&rules, self.config.quiet))?;
+            self.tui = Some(tui);
+            self.highlighter.capture_messages(true);
+            terminal_events = Some(events);
+        }
+        let mut frame_timer = tokio::time::interval(FRAME_INTERVAL);
+
         // Create channels for file events
         let (tx, mut rx) = mpsc::channel::<FileEvent>(100);
+        let mut changes = ChangeNotifier::new(tx.clone());
+        let mut watched = WatchedFiles::default();
+        let mut state = self.load_state()?;
+        let mut save_timer = tokio::time::interval_at(
+            tokio::time::Instant::now() + STATE_SAVE_INTERVAL,
+            STATE_SAVE_INTERVAL,
+        );
+
+        // Watch directories and globs for new files, rescanning them where
+        // creation can't be watched
+        let mut rescan = false;
+        if let Some(patterns) = &sources.patterns {
+            for (root, recursive) in patterns.roots() {
+                let polled = self.config.force_poll || is_network_filesystem(root);
+                if polled || !changes.watch_root(root, recursive) {
+                    info!("Rescanning {} for new files", root.display());
+                    rescan = true;
+                }
+            }
+        }
+        let mut rescan_timer = tokio::time::interval(Duration::from_millis(
+            self.config.poll_interval.max(MIN_RESCAN_INTERVAL_MS),
+        ));
 
-        // Start file watchers
-        let mut watchers = Vec::new();
+        // Start file watchers, all woken by one filesystem watcher
         for file_path in files {
-            let tx_clone = tx.clone();
-            let file_path_clone = file_path.clone();
-
-            match self.start_file_watcher(file_path_clone, tx_clone).await {
-                Ok(watcher) => watchers.push(watcher),
+            // Start where asked, or else where the last run stopped
+            let offset = match self.start_offset(file_path) {
+                Ok(Some(offset)) => Some(offset),
+                Ok(None) => state
+                    .as_ref()
+                    .and_then(|state| state.resume_offset(file_path)),
+                Err(e) => {
+                    self.highlighter
+                        .print_file_error(&file_path.display().to_string(), &e.to_string())?;
+                    continue;
+                }
+            };
+            if let Some(offset) = offset {
+                info!("Starting {} at byte {}", file_path.display(), offset);
+            }
+            match self
+                .start_file_watcher(file_path.clone(), tx.clone(), &mut changes, offset)
+                .await
+            {
+                Ok(task) => watched.add(file_path.clone(), task, false),
                 Err(e) => {
                     self.highlighter
                         .print_file_error(&file_path.display().to_string(), &e.to_string())?;
@@ -105,77 +274,444 @@ impl LogWatcher {
             }
         }
 
+        // Start stream readers
+        if sources.stdin {
+            let tx_clone = tx.clone();
+            let (encoding, partial_timeout) = (self.config.encoding, self.config.partial_timeout);
+            tokio::spawn(async move {
+                let stdin = tokio::io::stdin();
+                Self::forward_stream(stdin_label(), stdin, encoding, partial_timeout, &tx_clone)
+                    .await;
+                let _ = tx_clone.send(FileEvent::StreamEnded).await;
+            });
+        }
+        if let Some(command) = &sources.command {
+            let mut child = spawn_command(command)?;
+            let label = command_label(command);
+            let tx_clone = tx.clone();
+            let (encoding, partial_timeout) = (self.config.encoding, self.config.partial_timeout);
+            tokio::spawn(async move {
+                if let Some(stdout) = child.stdout.take() {
+                    Self::forward_stream(label, stdout, encoding, partial_timeout, &tx_clone).await;
+                }
+                let status = child.wait().await;
+                let _ = tx_clone.send(FileEvent::CommandExited { status }).await;
+            });
+        }
+
+        // Files are followed until interrupted; with only streams to read,
+        // stop once they have all ended
+        let follows_files = !files.is_empty() || sources.patterns.is_some();
+        let mut open_streams = usize::from(sources.stdin) + usize::from(sources.command.is_some());
+
         // Process file events
-        while let Some(event) = rx.recv().await {
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            let flush_at = self
+                .assembler
+                .as_ref()
+                .and_then(RecordAssembler::next_deadline);
+            let release_at = watched.next_release();
+            let event = tokio::select! {
+                event = rx.recv() => event,
+                _ = rescan_timer.tick(), if rescan => Some(FileEvent::Rescan),
+                _ = sleep_until(release_at.unwrap_or_else(Instant::now).into()), if release_at.is_some() => {
+                    Some(FileEvent::ReleaseMissing)
+                }
+                _ = sleep_until(flush_at.unwrap_or_else(Instant::now).into()), if flush_at.is_some() => {
+                    self.flush_expired_records().await?;
+                    continue;
+                }
+                _ = save_timer.tick(), if state.is_some() => {
+                    self.save_state(state.as_mut())?;
+                    continue;
+                }
+                Some(event) = async { terminal_events.as_mut()?.recv().await }, if terminal_events.is_some() => {
+                    let quit = self
+                        .tui
+                        .as_mut()
+                        .is_some_and(|tui| tui.state.handle_event(event));
+                    if quit {
+                        break;
+                    }
+                    continue;
+                }
+                _ = frame_timer.tick(), if self.tui.is_some() => {
+                    self.draw_tui()?;
+                    continue;
+                }
+                _ = &mut ctrl_c => None,
+            };
+            let Some(event) = event else {
+                break;
+            };
             match event {
-                FileEvent::NewLine { file_path, line } => {
-                    self.process_line(&file_path, &line).await?;
+                FileEvent::NewLine {
+                    file_path,
+                    line,
+                    position,
+                } => {
+                    self.handle_line(&file_path, &line, position).await?;
+                }
+                FileEvent::StreamEnded => {
+                    self.flush_record(&stdin_label()).await?;
+                    open_streams -= 1;
+                }
+                FileEvent::CommandExited { status } => {
+                    open_streams -= 1;
+                    if let Some(command) = &sources.command {
+                        self.handle_command_exited(&command_label(command), status)
+                            .await?;
+                    }
+                }
+                FileEvent::FileCreated { file_path } => {
+                    let Some(patterns) = &sources.patterns else {
+                        continue;
+                    };
+                    let new_files = if file_path.is_dir() {
+                        patterns.scan(&file_path)
+                    } else if patterns.matches(&file_path) {
+                        vec![file_path]
+                    } else {
+                        Vec::new()
+                    };
+                    for file_path in new_files {
+                        self.watch_new_file(file_path, &tx, &mut changes, &mut watched)
+                            .await?;
+                    }
+                }
+                FileEvent::Rescan => {
+                    let Some(patterns) = &sources.patterns else {
+                        continue;
+                    };
+                    for file_path in patterns.expand() {
+                        if patterns.matches(&file_path) {
+                            self.watch_new_file(file_path, &tx, &mut changes, &mut watched)
+                                .await?;
+                        }
+                    }
                 }
                 FileEvent::FileRotated { file_path } => {
+                    self.flush_record(&file_path).await?;
+                    watched.rotated(&file_path);
                     self.handle_file_rotation(&file_path).await?;
                 }
+                FileEvent::FileTruncated { file_path } => {
+                    self.flush_record(&file_path).await?;
+                    self.highlighter
+                        .print_file_truncated(&file_path.display().to_string())?;
+                }
+                FileEvent::FileMissing { file_path } => {
+                    self.flush_record(&file_path).await?;
+                    // Files found through a directory or glob are released
+                    // if they don't come back soon; named files are waited for
+                    if !watched.missing(&file_path) {
+                        self.highlighter
+                            .print_file_missing(&file_path.display().to_string())?;
+                    }
+                }
+                FileEvent::ReleaseMissing => {
+                    for file_path in watched.expired(Instant::now()) {
+                        watched.release(&file_path, &mut changes);
+                        if let Some(state) = &mut state {
+                            state.remove(&file_path);
+                        }
+                        self.held_checkpoints.remove(&file_path);
+                        self.stats.files_removed += 1;
+                        self.highlighter
+                            .print_file_removed(&file_path.display().to_string())?;
+                        // Picked up again if it was recreated just now
+                        if file_path.is_file() {
+                            self.watch_new_file(file_path, &tx, &mut changes, &mut watched)
+                                .await?;
+                        }
+                    }
+                }
+                FileEvent::Checkpoint {
+                    file_path,
+                    checkpoint,
+                } => {
+                    if let Some(state) = &mut state {
+                        self.record_checkpoint(state, &file_path, checkpoint);
+                    }
+                }
                 FileEvent::FileError { file_path, error } => {
                     self.highlighter
                         .print_file_error(&file_path.display().to_string(), &error.to_string())?;
                 }
             }
+            // The view stays open to browse what streams sent
+            if !follows_files && open_streams == 0 && self.tui.is_none() {
+                break;
+            }
+        }
+
+        // Records still waiting for more lines are complete at shutdown
+        let records = self
+            .assembler
+            .as_mut()
+            .map(RecordAssembler::flush_all)
+            .unwrap_or_default();
+        for (file_path, record, position) in records {
+            self.process_line(&file_path, &record, position).await?;
+        }
+        self.save_state(state.as_mut())?;
+
+        Ok(())
+    }
+
+    /// Complete the command's last record and report how it ended
+    async fn handle_command_exited(
+        &mut self,
+        label: &Path,
+        status: std::io::Result<ExitStatus>,
+    ) -> Result<()> {
+        self.stats.command_status = status.as_ref().ok().copied();
+        self.flush_record(label).await?;
+        self.highlighter
+            .print_command_exited(&label.display().to_string(), &status)
+    }
+
+    /// Match a line, or with `--multiline-*` add it to the record being
+    /// assembled for its source and match the record it completes
+    async fn handle_line(
+        &mut self,
+        file_path: &Path,
+        line: &str,
+        position: LinePosition,
+    ) -> Result<()> {
+        if self.skips(line) {
+            return Ok(());
+        }
+        match &mut self.assembler {
+            Some(assembler) => {
+                if let Some((record, start)) = assembler.push(file_path, line, position) {
+                    self.process_line(file_path, &record, start).await?;
+                }
+                Ok(())
+            }
+            None => self.process_line(file_path, line, position).await,
+        }
+    }
+
+    /// Whether a line is dropped before matching (`--skip-blank`)
+    fn skips(&self, line: &str) -> bool {
+        self.config.skip_blank && line.trim().is_empty()
+    }
+
+    /// Match the record being assembled for a source, if any
+    async fn flush_record(&mut self, file_path: &Path) -> Result<()> {
+        let record = self
+            .assembler
+            .as_mut()
+            .and_then(|assembler| assembler.flush(file_path));
+        if let Some((record, position)) = record {
+            self.process_line(file_path, &record, position).await?;
+        }
+        Ok(())
+    }
+
+    /// Match the records that have waited long enough for more lines
+    async fn flush_expired_records(&mut self) -> Result<()> {
+        let records = self
+            .assembler
+            .as_mut()
+            .map(|assembler| assembler.flush_expired(Instant::now()))
+            .unwrap_or_default();
+        for (file_path, record, position) in records {
+            self.process_line(&file_path, &record, position).await?;
+        }
+        Ok(())
+    }
+
+    /// Where `--lines` or `--since` says to start reading a file, if either
+    /// is used
+    fn start_offset(&self, file_path: &Path) -> Result<Option<u64>> {
+        if let Some(count) = self.config.last_lines {
+            let (buffer_size, encoding) = (self.config.buffer_size, self.config.encoding);
+            return offset_of_last_lines(file_path, count, buffer_size, encoding).map(Some);
+        }
+        if let Some(since) = self.config.since {
+            let timestamp_of = |line: &str| line_timestamp(line, self.parser.parse(line).as_ref());
+            return offset_since(file_path, since, self.config.encoding, timestamp_of).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// The `--state-file` checkpoints, if one is used. An unreadable state
+    /// file is reported and replaced rather than stopping the watcher.
+    fn load_state(&mut self) -> Result<Option<StateFile>> {
+        let Some(path) = &self.config.state_file else {
+            return Ok(None);
+        };
+        match StateFile::load(path) {
+            Ok(state) => Ok(Some(state)),
+            Err(e) => {
+                self.highlighter
+                    .print_warning(&format!("Starting without saved positions: {:#}", e))?;
+                Ok(Some(StateFile::new(path)))
+            }
+        }
+    }
+
+    /// Record how far a file has been read. While a record is being
+    /// assembled there, the saved offset stays at the record's start, so a
+    /// restart after a crash reads the record again instead of skipping it.
+    fn record_checkpoint(
+        &mut self,
+        state: &mut StateFile,
+        file_path: &Path,
+        checkpoint: Checkpoint,
+    ) {
+        let pending = self
+            .assembler
+            .as_ref()
+            .and_then(|assembler| assembler.pending_offset(file_path));
+        match pending {
+            Some(start) if start < checkpoint.offset => {
+                let held = Checkpoint {
+                    offset: start,
+                    ..checkpoint
+                };
+                state.record(file_path, held);
+                self.held_checkpoints
+                    .insert(file_path.to_path_buf(), checkpoint);
+            }
+            _ => {
+                state.record(file_path, checkpoint);
+                self.held_checkpoints.remove(file_path);
+            }
+        }
+    }
+
+    fn save_state(&mut self, state: Option<&mut StateFile>) -> Result<()> {
+        if let Some(state) = state {
+            // Records completed since their checkpoint came in no longer
+            // hold it back
+            for (file_path, checkpoint) in std::mem::take(&mut self.held_checkpoints) {
+                self.record_checkpoint(state, &file_path, checkpoint);
+            }
+            if let Err(e) = state.save() {
+                self.highlighter.print_warning(&format!("{:#}", e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start tailing a file that appeared after startup, from its start
+    /// unless it is a released file that was renamed back into view
+    async fn watch_new_file(
+        &mut self,
+        file_path: PathBuf,
+        tx: &mpsc::Sender<FileEvent>,
+        changes: &mut ChangeNotifier,
+        watched: &mut WatchedFiles,
+    ) -> Result<()> {
+        let id = FileId::of_path(&file_path);
+        if watched.contains(&file_path, id) {
+            // Already tailed, possibly under the name it was rotated from
+            return Ok(());
+        }
+        let offset = if id.is_some_and(|id| watched.retired.contains(&id)) {
+            None
+        } else {
+            Some(0)
+        };
+
+        match self
+            .start_file_watcher(file_path.clone(), tx.clone(), changes, offset)
+            .await
+        {
+            Ok(task) => {
+                watched.add(file_path.clone(), task, true);
+                self.stats.files_added += 1;
+                self.highlighter
+                    .print_file_added(&file_path.display().to_string())?;
+            }
+            // Removed again before it could be opened
+            Err(_) if !file_path.exists() => {}
+            Err(e) => {
+                self.highlighter
+                    .print_file_error(&file_path.display().to_string(), &e.to_string())?;
+            }
         }
 
         Ok(())
     }
 
+    /// Tail a file, reading whenever the filesystem reports a change to it.
+    /// Files whose changes can't be watched are polled every `poll_interval`.
+    /// The file is read from `offset`, or from its end if `None`.
     async fn start_file_watcher(
         &self,
         file_path: PathBuf,
         tx: mpsc::Sender<FileEvent>,
-    ) -> Result<RecommendedWatcher> {
-        let file_path_clone = file_path.clone();
-        let tx_clone = tx.clone();
-
-        let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
-            match res {
-                Ok(event) => {
-                    if matches!(event.kind, EventKind::Modify(_)) {
-                        // File was modified, we'll poll for new content
-                    }
-                }
-                Err(e) => {
-                    let _ = tx_clone.try_send(FileEvent::FileError {
-                        file_path: file_path_clone.clone(),
-                        error: e,
-                    });
-                }
-            }
-        })?;
+        changes: &mut ChangeNotifier,
+        offset: Option<u64>,
+    ) -> Result<JoinHandle<()>> {
+        let tail = match offset {
+            Some(offset) => FileTail::open_at(&file_path, offset, self.config.buffer_size)?,
+            None => FileTail::open(&file_path, self.config.buffer_size)?,
+        };
+        let mut tail = tail
+            .with_encoding(self.config.encoding)?
+            .with_partial_timeout(self.config.partial_timeout);
 
-        watcher.watch(&file_path, RecursiveMode::NonRecursive)?;
+        let polled = self.config.force_poll || is_network_filesystem(&file_path);
+        let waker = if polled {
+            None
+        } else {
+            changes.register(&file_path)
+        };
+        if waker.is_none() {
+            info!("Polling {} for changes", file_path.display());
+        }
 
-        // Start polling task for this file
         let file_path_clone = file_path.clone();
         let tx_clone = tx.clone();
         let poll_interval = self.config.poll_interval;
-        let buffer_size = self.config.buffer_size;
-
-        tokio::spawn(async move {
-            let mut last_size = get_file_size(&file_path_clone).unwrap_or(0);
+        let checkpoints = self.config.state_file.is_some();
+        let mut last_checkpoint = None;
 
+        let task = tokio::spawn(async move {
             loop {
-                sleep(Duration::from_millis(poll_interval)).await;
-
-                match Self::poll_file_changes(&file_path_clone, last_size, buffer_size).await {
-                    Ok((new_size, new_lines)) => {
-                        last_size = new_size;
-
-                        for line in new_lines {
-                            if let Err(e) = tx_clone
-                                .send(FileEvent::NewLine {
+                match tail.poll() {
+                    Ok(events) => {
+                        let mut events: Vec<FileEvent> = events
+                            .into_iter()
+                            .map(|event| {
+                                let file_path = file_path_clone.clone();
+                                match event {
+                                    TailEvent::Line(line, position) => FileEvent::NewLine {
+                                        file_path,
+                                        line,
+                                        position,
+                                    },
+                                    TailEvent::Truncated => FileEvent::FileTruncated { file_path },
+                                    TailEvent::Rotated => FileEvent::FileRotated { file_path },
+                                    TailEvent::Missing => FileEvent::FileMissing { file_path },
+                                }
+                            })
+                            .collect();
+                        // Sent after the lines, so it arrives once they are processed
+                        let checkpoint = tail.id().map(|id| Checkpoint {
+                            id,
+                            offset: tail.offset(),
+                        });
+                        if checkpoints && checkpoint != last_checkpoint {
+                            last_checkpoint = checkpoint;
+                            if let Some(checkpoint) = checkpoint {
+                                events.push(FileEvent::Checkpoint {
                                     file_path: file_path_clone.clone(),
-                                    line,
-                                })
-                                .await
-                            {
-                                error!("Failed to send line event: {}", e);
-                                break;
+                                    checkpoint,
+                                });
+                            }
+                        }
+                        for event in events {
+                            // Nobody is listening once the watcher has stopped
+                            if tx_clone.send(event).await.is_err() {
+                                return;
                             }
                         }
                     }
@@ -189,89 +725,280 @@ impl LogWatcher {
                         break;
                     }
                 }
+
+                // An unfinished line is emitted at its deadline even if
+                // nothing else changes. Writes to a file renamed away from
+                // the path aren't reported, so it is polled until the path
+                // is back.
+                match (&waker, tail.partial_deadline()) {
+                    (Some(waker), _) if tail.is_missing() => {
+                        let _ =
+                            timeout(Duration::from_millis(poll_interval), waker.notified()).await;
+                    }
+                    (Some(waker), Some(deadline)) => {
+                        let _ = timeout_at(deadline.into(), waker.notified()).await;
+                    }
+                    (Some(waker), None) => waker.notified().await,
+                    (None, _) => sleep(Duration::from_millis(poll_interval)).await,
+                }
             }
         });
 
-        Ok(watcher)
+        Ok(task)
+    }
+
+    /// Send each line of a stream as it arrives, until end of input. An
+    /// unfinished line is sent as it is after `partial_timeout` without more
+    /// of it.
+    async fn forward_stream<R>(
+        label: PathBuf,
+        stream: R,
+        encoding: TextEncoding,
+        partial_timeout: Duration,
+        tx: &mpsc::Sender<FileEvent>,
+    ) where
+        R: AsyncRead + Unpin,
+    {
+        let mut reader = tokio::io::BufReader::new(stream);
+        let mut line = Vec::new();
+        let mut position = LinePosition::start();
+        loop {
+            let unfinished = !line.is_empty();
+            let read = encoding.read_line_async(&mut reader, &mut line);
+            let read = if !unfinished {
+                Some(read.await)
+            } else {
+                // Reading is cancel safe: whatever was read is in `line`
+                timeout(partial_timeout, read).await.ok()
+            };
+            let (finished, ended) = match read {
+                // Nothing more of the unfinished line arrived in time
+                None => (true, false),
+                Some(Ok(read)) => (read == 0 || encoding.ends_line(&line), read == 0),
+                Some(Err(e)) => {
+                    let _ = tx
+                        .send(FileEvent::FileError {
+                            file_path: label,
+                            error: notify::Error::generic(&e.to_string()),
+                        })
+                        .await;
+                    break;
+                }
+            };
+            if finished && !line.is_empty() {
+                let event = FileEvent::NewLine {
+                    file_path: label.clone(),
+                    line: encoding.decode(&line),
+                    position,
+                };
+                position = position.next(line.len());
+                line.clear();
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+            if ended {
+                break;
+            }
+        }
     }
 
-    async fn poll_file_changes(
+    async fn process_existing_file(
+        &mut self,
         file_path: &PathBuf,
-        last_size: u64,
-        buffer_size: usize,
-    ) -> Result<(u64, Vec<String>)> {
-        let current_size = get_file_size(file_path)?;
+    ) -> Result<HashMap<String, usize>> {
+        let mut pattern_counts: HashMap<String, usize> = HashMap::new();
 
-        if current_size < last_size {
-            // File was rotated
-            return Err(anyhow::anyhow!("File rotation detected"));
+        let mut file = File::open(file_path)?;
+        let compression = Compression::detect(&mut file)?;
+        if compression != Compression::None {
+            let decoder = compression.decoder(file)?;
+            let reader = BufReader::with_capacity(self.config.buffer_size, decoder);
+            self.process_compressed_file(file_path, reader, &mut pattern_counts)?;
+            return Ok(pattern_counts);
         }
 
-        if current_size > last_size {
-            // File has new content
-            let file = File::open(file_path)?;
-            let mut reader = BufReader::with_capacity(buffer_size, file);
-
-            // Seek to last position
-            reader.seek(SeekFrom::Start(last_size))?;
+        // Line numbers are only known when reading from the start
+        let mut position = LinePosition::start();
+        if let Some(offset) = self.start_offset(file_path)? {
+            file.seek(SeekFrom::Start(offset))?;
+            if offset > 0 {
+                position = LinePosition {
+                    offset,
+                    number: None,
+                };
+            }
+        }
+        let mut reader = BufReader::with_capacity(self.config.buffer_size, file);
+        let encoding = self.config.encoding;
+        let mut line = Vec::new();
+
+        while encoding.read_line(&mut reader, &mut line)? > 0 {
+            let text = encoding.decode(&line);
+            self.dry_run_line(file_path, &text, position, &mut pattern_counts)?;
+            position = position.next(line.len());
+            line.clear();
+        }
+        self.dry_run_flush(file_path, &mut pattern_counts)?;
 
-            let mut lines = Vec::new();
-            let mut line = String::new();
+        Ok(pattern_counts)
+    }
 
-            while reader.read_line(&mut line)? > 0 {
-                if !line.trim().is_empty() {
-                    lines.push(line.trim().to_string());
+    /// Dry-run a decompressed file. It can't be searched for `--lines` or
+    /// `--since`, so the start is found while reading it.
+    fn process_compressed_file<R: BufRead>(
+        &mut self,
+        file_path: &Path,
+        mut reader: R,
+        pattern_counts: &mut HashMap<String, usize>,
+    ) -> Result<()> {
+        let encoding = self.config.encoding;
+        let mut since = self.config.since;
+        let mut last_lines = VecDeque::new();
+        let mut line = Vec::new();
+        // Offsets are in the decompressed text
+        let mut next = LinePosition::start();
+
+        while encoding.read_line(&mut reader, &mut line)? > 0 {
+            let text = encoding.decode(&line);
+            let position = next;
+            next = next.next(line.len());
+            line.clear();
+            if let Some(time) = since {
+                match line_timestamp(&text, self.parser.parse(&text).as_ref()) {
+                    Some(logged) if logged >= time => since = None,
+                    _ => continue,
                 }
-                line.clear();
             }
-
-            Ok((current_size, lines))
-        } else {
-            Ok((current_size, Vec::new()))
+            match self.config.last_lines {
+                Some(count) => {
+                    last_lines.push_back((text, position));
+                    if last_lines.len() > count {
+                        last_lines.pop_front();
+                    }
+                }
+                None => self.dry_run_line(file_path, &text, position, pattern_counts)?,
+            }
         }
+        for (text, position) in last_lines {
+            self.dry_run_line(file_path, &text, position, pattern_counts)?;
+        }
+
+        self.dry_run_flush(file_path, pattern_counts)
     }
 
-    async fn process_existing_file(
+    /// Dry-run a stream such as standard input to its end
+    async fn process_stream<R>(
         &mut self,
-        file_path: &PathBuf,
-    ) -> Result<HashMap<String, usize>> {
-        let mut pattern_counts: HashMap<String, usize> = HashMap::new();
-
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
+        label: &Path,
+        stream: R,
+        pattern_counts: &mut HashMap<String, usize>,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut reader = tokio::io::BufReader::new(stream);
+        let encoding = self.config.encoding;
+        let mut line = Vec::new();
+        let mut position = LinePosition::start();
+        while encoding.read_line_async(&mut reader, &mut line).await? > 0 {
+            self.dry_run_line(label, &encoding.decode(&line), position, pattern_counts)?;
+            position = position.next(line.len());
+            line.clear();
+        }
+        self.dry_run_flush(label, pattern_counts)
+    }
 
-        for line_result in reader.lines() {
-            let line = line_result?;
+    /// Dry-run counterpart of `handle_line`
+    fn dry_run_line(
+        &mut self,
+        file_path: &Path,
+        line: &str,
+        position: LinePosition,
+        pattern_counts: &mut HashMap<String, usize>,
+    ) -> Result<()> {
+        if self.skips(line) {
+            return Ok(());
+        }
+        match &mut self.assembler {
+            Some(assembler) => {
+                if let Some((record, start)) = assembler.push(file_path, line, position) {
+                    self.process_dry_run_line(file_path, &record, start, pattern_counts)?;
+                }
+                Ok(())
+            }
+            None => self.process_dry_run_line(file_path, line, position, pattern_counts),
+        }
+    }
 
-            // Check if line should be excluded
-            if self.config.should_exclude(&line) {
-                self.stats.lines_excluded += 1;
-                continue;
+    /// Dry-run the last record of a file or stream
+    fn dry_run_flush(
+        &mut self,
+        file_path: &Path,
+        pattern_counts: &mut HashMap<String, usize>,
+    ) -> Result<()> {
+        let record = self
+            .assembler
+            .as_mut()
+            .and_then(|assembler| assembler.flush(file_path));
+        match record {
+            Some((record, position)) => {
+                self.process_dry_run_line(file_path, &record, position, pattern_counts)
             }
+            None => Ok(()),
+        }
+    }
 
-            self.stats.lines_processed += 1;
+    fn process_dry_run_line(
+        &mut self,
+        file_path: &Path,
+        line: &str,
+        position: LinePosition,
+        pattern_counts: &mut HashMap<String, usize>,
+    ) -> Result<()> {
+        // Check if line should be excluded
+        if self.config.should_exclude(line) {
+            self.stats.lines_excluded += 1;
+            return Ok(());
+        }
 
-            let match_result = self.matcher.match_line(&line);
+        self.stats.lines_processed += 1;
 
-            if match_result.matched {
-                self.stats.matches_found += 1;
-                if let Some(pattern) = &match_result.pattern {
-                    *pattern_counts.entry(pattern.clone()).or_insert(0) += 1;
-                }
+        let record = self.parser.parse(line);
+        let match_result = self.matcher.match_record(line, record.as_ref());
+        self.record_line(file_path, position, line, record.as_ref(), &match_result)?;
+        self.print_context(file_path, line, position, match_result.matched, false)?;
 
-                self.highlighter.print_line(
-                    &line,
-                    Some(&file_path.file_name().unwrap().to_string_lossy()),
-                    &match_result,
-                    true, // dry run
-                )?;
+        if match_result.matched {
+            self.stats.matches_found += 1;
+            for pattern in &match_result.patterns {
+                *pattern_counts.entry(pattern.clone()).or_insert(0) += 1;
+                *self.stats.rule_matches.entry(pattern.clone()).or_insert(0) += 1;
             }
+            if let Some(record) = &record {
+                self.stats.count_fields(&self.config.summary_fields, record);
+            }
+            self.stats.count_captures(&match_result.captures);
+
+            self.highlighter.print_match(
+                file_path,
+                position,
+                line,
+                record.as_ref(),
+                &match_result,
+                true, // dry run
+            )?;
         }
 
-        Ok(pattern_counts)
+        Ok(())
     }
 
-    async fn process_line(&mut self, file_path: &Path, line: &str) -> Result<()> {
+    async fn process_line(
+        &mut self,
+        file_path: &Path,
+        line: &str,
+        position: LinePosition,
+    ) -> Result<()> {
         // Check if line should be excluded
         if self.config.should_exclude(line) {
             self.stats.lines_excluded += 1;
@@ -280,30 +1007,53 @@ impl LogWatcher {
 
         self.stats.lines_processed += 1;
 
-        let match_result = self.matcher.match_line(line);
+        let record = self.parser.parse(line);
+        let match_result = self.matcher.match_record(line, record.as_ref());
 
         if match_result.matched {
             self.stats.matches_found += 1;
+            for pattern in &match_result.patterns {
+                *self.stats.rule_matches.entry(pattern.clone()).or_insert(0) += 1;
+            }
 
-            // Send notification if needed
+            // Send one notification naming the rules that ask for one. A
+            // command's label may have no file name (`--exec ..`).
             if match_result.should_notify {
-                if let Some(pattern) = &match_result.pattern {
-                    self.notifier
-                        .send_notification(
-                            pattern,
-                            line,
-                            Some(&file_path.file_name().unwrap().to_string_lossy()),
-                        )
-                        .await?;
+                let filename = file_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or_else(|| file_path.to_string_lossy());
+                let sent = self
+                    .notifier
+                    .send_record_notification(
+                        &match_result.patterns,
+                        line,
+                        Some(&filename),
+                        record.as_ref(),
+                        &match_result.captures,
+                    )
+                    .await?;
+                if sent {
                     self.stats.notifications_sent += 1;
                 }
             }
         }
 
-        // Print the line
-        self.highlighter.print_line(
+        self.record_line(file_path, position, line, record.as_ref(), &match_result)?;
+
+        if let Some(tui) = &mut self.tui {
+            tui.state.push(file_path, line, &match_result);
+            return Ok(());
+        }
+
+        // Print the line, after any context it brings
+        let shown = match_result.matched || !self.config.quiet;
+        self.print_context(file_path, line, position, match_result.matched, shown)?;
+        self.highlighter.print_match(
+            file_path,
+            position,
             line,
-            Some(&file_path.file_name().unwrap().to_string_lossy()),
+            record.as_ref(),
             &match_result,
             false, // not dry run
         )?;
@@ -311,14 +1061,89 @@ impl LogWatcher {
         Ok(())
     }
 
+    /// Record a line to the `--output-file`. A failed write is reported and
+    /// the line skipped rather than stopping the watcher.
+    fn record_line(
+        &mut self,
+        file_path: &Path,
+        position: LinePosition,
+        line: &str,
+        record: Option<&Record>,
+        match_result: &MatchResult,
+    ) -> Result<()> {
+        let Some(output_file) = &mut self.output_file else {
+            return Ok(());
+        };
+        if let Err(e) = output_file.record(file_path, position, line, record, match_result) {
+            self.highlighter.print_warning(&format!("{:#}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Redraw the `--tui` view with the latest messages and counters
+    fn draw_tui(&mut self) -> Result<()> {
+        let Some(tui) = &mut self.tui else {
+            return Ok(());
+        };
+        for message in self.highlighter.take_messages() {
+            tui.state.show_message(message);
+        }
+        tui.state.tick(Instant::now());
+        tui.draw(&self.stats)
+    }
+
+    /// Leave the `--tui` view, printing messages it didn't get to show
+    fn close_tui(&mut self) {
+        if self.tui.take().is_none() {
+            return;
+        }
+        let messages = self.highlighter.take_messages();
+        self.highlighter.capture_messages(false);
+        for message in messages {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Print the context lines due before a line with `-A`, `-B` or `-C`.
+    /// Lines that aren't `shown` are kept in case a match follows.
+    fn print_context(
+        &mut self,
+        file_path: &Path,
+        line: &str,
+        position: LinePosition,
+        matched: bool,
+        shown: bool,
+    ) -> Result<()> {
+        let Some(context) = &mut self.context else {
+            return Ok(());
+        };
+        let output = if matched {
+            context.matched(file_path)
+        } else {
+            context.unmatched(file_path, line, position, shown)
+        };
+        for item in output {
+            match item {
+                ContextOutput::Separator => self.highlighter.print_context_separator()?,
+                ContextOutput::Line(context) => {
+                    let record = self.parser.parse(&context.line);
+                    self.highlighter.print_context(
+                        file_path,
+                        context.position,
+                        &context.line,
+                        record.as_ref(),
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The tail has already drained the old file and switched to the new one
     async fn handle_file_rotation(&mut self, file_path: &Path) -> Result<()> {
         self.highlighter
             .print_file_rotation(&file_path.display().to_string())?;
 
-        // Wait a bit for the new file to be created
-        sleep(Duration::from_millis(1000)).await;
-
-        // Try to reopen the file
         if file_path.exists() {
             self.highlighter
                 .print_file_reopened(&file_path.display().to_string())?;
@@ -333,47 +1158,335 @@ impl LogWatcher {
     }
 }
 
+/// Wakes the tail task of each file when the filesystem reports a change to
+/// it. One notify watcher serves every file; it watches the directories
+/// holding the files, so that files created by rotation are seen too.
+/// Files created under a directory or glob root are reported as
+/// [`FileEvent::FileCreated`].
+struct ChangeNotifier {
+    /// `None` if no watcher could be created, in which case files are polled
+    watcher: Option<RecommendedWatcher>,
+    wakers: Arc<Mutex<HashMap<PathBuf, Arc<Notify>>>>,
+    /// Paths each registered file is woken by
+    keys: HashMap<PathBuf, Vec<PathBuf>>,
+    watched_dirs: HashSet<PathBuf>,
+    /// Directories watched for new files, and whether recursively
+    roots: Vec<(PathBuf, bool)>,
+    discovering: Arc<AtomicBool>,
+}
+
+impl ChangeNotifier {
+    fn new(tx: mpsc::Sender<FileEvent>) -> Self {
+        let wakers: Arc<Mutex<HashMap<PathBuf, Arc<Notify>>>> = Arc::default();
+        let wakers_clone = Arc::clone(&wakers);
+        let discovering = Arc::new(AtomicBool::new(false));
+        let discovering_clone = Arc::clone(&discovering);
+
+        let watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+            match res {
+                // Our own reads show up as access events
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(event) => {
+                    let discovering = discovering_clone.load(Ordering::Relaxed);
+                    let mut created = Vec::new();
+                    {
+                        let wakers = wakers_clone.lock().unwrap();
+                        if event.need_rescan() {
+                            // Events were dropped; any file may have changed
+                            wakers.values().for_each(|waker| waker.notify_one());
+                        }
+                        for path in &event.paths {
+                            match wakers.get(path) {
+                                Some(waker) => waker.notify_one(),
+                                None if discovering && is_creation(&event.kind) => {
+                                    created.push(path.clone())
+                                }
+                                None => {}
+                            }
+                        }
+                    }
+                    if discovering && event.need_rescan() {
+                        let _ = tx.blocking_send(FileEvent::Rescan);
+                    }
+                    for file_path in created {
+                        let _ = tx.blocking_send(FileEvent::FileCreated { file_path });
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.try_send(FileEvent::FileError {
+                        file_path: e.paths.first().cloned().unwrap_or_default(),
+                        error: e,
+                    });
+                }
+            }
+        });
+        if let Err(e) = &watcher {
+            info!("File change events unavailable, polling instead: {}", e);
+        }
+
+        Self {
+            watcher: watcher.ok(),
+            wakers,
+            keys: HashMap::new(),
+            watched_dirs: HashSet::new(),
+            roots: Vec::new(),
+            discovering,
+        }
+    }
+
+    /// Report files created under a directory, returning whether it could
+    /// be watched
+    fn watch_root(&mut self, dir: &Path, recursive: bool) -> bool {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return false;
+        };
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        if let Err(e) = watcher.watch(dir, mode) {
+            info!("Cannot watch {}: {}", dir.display(), e);
+            return false;
+        }
+        self.roots.push((dir.to_path_buf(), recursive));
+        self.discovering.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Start watching a file, returning the waker its tail task should wait
+    /// on, or `None` if it has to be polled
+    fn register(&mut self, path: &Path) -> Option<Arc<Notify>> {
+        let watcher = self.watcher.as_mut()?;
+
+        // Events name the watched directory joined with the file name; a
+        // symlinked file is also watched where its target lives
+        let mut keys = vec![std::path::absolute(path).ok()?];
+        if let Ok(target) = path.canonicalize() {
+            if target != keys[0] {
+                keys.push(target);
+            }
+        }
+
+        let waker = Arc::new(Notify::new());
+        for key in &keys {
+            let dir = key.parent()?.to_path_buf();
+            // Watching a directory again would change how a root is watched
+            let covered = self
+                .roots
+                .iter()
+                .any(|(root, recursive)| dir == *root || (*recursive && dir.starts_with(root)));
+            if !covered && !self.watched_dirs.contains(&dir) {
+                if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    info!("Cannot watch {}: {}", dir.display(), e);
+                    return None;
+                }
+                self.watched_dirs.insert(dir);
+            }
+            self.wakers
+                .lock()
+                .unwrap()
+                .insert(key.clone(), Arc::clone(&waker));
+        }
+        self.keys.insert(path.to_path_buf(), keys);
+
+        Some(waker)
+    }
+
+    /// Stop waking a file's tail task
+    fn unregister(&mut self, path: &Path) {
+        if let Some(keys) = self.keys.remove(path) {
+            let mut wakers = self.wakers.lock().unwrap();
+            for key in keys {
+                wakers.remove(&key);
+            }
+        }
+    }
+}
+
+/// Whether an event can bring a new file into view
+fn is_creation(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Name(
+                RenameMode::To | RenameMode::Both | RenameMode::Any
+            ))
+    )
+}
+
+/// Files being tailed, so that files found through a directory or glob can
+/// be released when they are deleted
+#[derive(Debug, Default)]
+struct WatchedFiles {
+    files: HashMap<PathBuf, WatchedFile>,
+    /// Files that were released or rotated away. One that reappears under a
+    /// matching name is only read from its end, so it isn't read twice.
+    retired: HashSet<FileId>,
+}
+
+#[derive(Debug)]
+struct WatchedFile {
+    task: JoinHandle<()>,
+    id: Option<FileId>,
+    /// Found through a directory or glob rather than named
+    discovered: bool,
+    /// When a discovered file's path went missing
+    missing_since: Option<Instant>,
+}
+
+impl WatchedFiles {
+    fn add(&mut self, path: PathBuf, task: JoinHandle<()>, discovered: bool) {
+        let id = FileId::of_path(&path);
+        self.files.insert(
+            path,
+            WatchedFile {
+                task,
+                id,
+                discovered,
+                missing_since: None,
+            },
+        );
+    }
+
+    /// Whether a path, or the file it names, is already being tailed
+    fn contains(&self, path: &Path, id: Option<FileId>) -> bool {
+        self.files.contains_key(path)
+            || (id.is_some() && self.files.values().any(|file| file.id == id))
+    }
+
+    /// The path of a file went missing. Returns whether it is a discovered
+    /// file, which is released once [`MISSING_GRACE`] has passed.
+    fn missing(&mut self, path: &Path) -> bool {
+        match self.files.get_mut(path) {
+            Some(file) if file.discovered => {
+                file.missing_since.get_or_insert_with(Instant::now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// When the next missing file is due to be released
+    fn next_release(&self) -> Option<Instant> {
+        self.files
+            .values()
+            .filter_map(|file| file.missing_since)
+            .min()
+            .map(|since| since + MISSING_GRACE)
+    }
+
+    /// Missing files due to be released by `now`
+    fn expired(&self, now: Instant) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(_, file)| {
+                file.missing_since
+                    .is_some_and(|since| since + MISSING_GRACE <= now)
+            })
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// The tail switched to the file now at `path`
+    fn rotated(&mut self, path: &Path) {
+        if let Some(file) = self.files.get_mut(path) {
+            self.retired.extend(file.id);
+            file.id = FileId::of_path(path);
+            file.missing_since = None;
+        }
+    }
+
+    /// Stop tailing a file
+    fn release(&mut self, path: &Path, changes: &mut ChangeNotifier) {
+        if let Some(file) = self.files.remove(path) {
+            file.task.abort();
+            self.retired.extend(file.id);
+            changes.unregister(path);
+        }
+    }
+}
+
+impl Drop for WatchedFiles {
+    /// Tailing stops with tail mode, however it ends
+    fn drop(&mut self) {
+        for file in self.files.values() {
+            file.task.abort();
+        }
+    }
+}
+
+/// Sources to read besides the files found at startup
+#[derive(Debug, Default)]
+struct Sources {
+    /// Read standard input (`--file -`)
+    stdin: bool,
+    /// Shell command whose output is read (`--exec`)
+    command: Option<String>,
+    /// Directory and glob arguments, watched for new files
+    patterns: Option<FileSet>,
+}
+
 #[derive(Debug)]
 enum FileEvent {
     NewLine {
         file_path: PathBuf,
         line: String,
+        position: LinePosition,
     },
-    #[allow(dead_code)]
     FileRotated {
         file_path: PathBuf,
     },
+    FileTruncated {
+        file_path: PathBuf,
+    },
+    /// The path was removed or renamed and nothing has replaced it yet
+    FileMissing {
+        file_path: PathBuf,
+    },
+    /// Discovered files missing for longer than [`MISSING_GRACE`] are due
+    /// to be released
+    ReleaseMissing,
     FileError {
         file_path: PathBuf,
         error: notify::Error,
     },
+    /// The `--exec` command ended, with its status unless it couldn't be read
+    CommandExited {
+        status: std::io::Result<ExitStatus>,
+    },
+    /// Standard input ended
+    StreamEnded,
+    /// A file or directory appeared under a directory or glob root
+    FileCreated {
+        file_path: PathBuf,
+    },
+    /// Time to look for new files under roots that can't be watched
+    Rescan,
+    /// Every line read from the file up to here has been sent
+    Checkpoint {
+        file_path: PathBuf,
+        checkpoint: Checkpoint,
+    },
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::cli::Args;
+    use crate::multiline::MultilineRules;
+    use crate::rule::Rule;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
     fn create_test_config() -> Config {
         let args = Args {
             files: vec![PathBuf::from("test.log")],
-            completions: None,
             patterns: "ERROR".to_string(),
-            regex: false,
-            case_insensitive: false,
-            color_map: None,
             notify: false,
-            notify_patterns: None,
-            notify_throttle: 5,
             dry_run: true,
-            quiet: false,
-            exclude: None,
             no_color: true,
-            prefix_file: None,
-            poll_interval: 100,
-            buffer_size: 8192,
+            ..Default::default()
         };
         Config::from_args(&args).unwrap()
     }
@@ -396,29 +1509,75 @@ mod tests {
         assert_eq!(watcher.stats.matches_found, 2);
     }
 
-    #[test]
-    fn test_poll_file_changes() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "line 1").unwrap();
-        temp_file.flush().unwrap();
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dry_run_command_output() {
+        let mut config = create_test_config();
+        config.files = vec![];
+        config.exec = Some("printf 'ERROR one\\nfine\\nERROR two\\n'; exit 2".to_string());
 
-        let initial_size = get_file_size(temp_file.path()).unwrap();
+        let mut watcher = LogWatcher::new(config);
+        watcher.run().await.unwrap();
 
-        writeln!(temp_file, "line 2").unwrap();
-        temp_file.flush().unwrap();
+        assert_eq!(watcher.stats.lines_processed, 3);
+        assert_eq!(watcher.stats.matches_found, 2);
+        assert_eq!(watcher.stats.command_status.unwrap().code(), Some(2));
+    }
 
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        let result = rt.block_on(LogWatcher::poll_file_changes(
-            &temp_file.path().to_path_buf(),
-            initial_size,
-            1024,
-        ));
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tail_mode_ends_with_command() {
+        let mut config = create_test_config();
+        config.files = vec![];
+        config.dry_run = false;
+        config.exec = Some("echo 'ERROR from command'".to_string());
 
-        assert!(result.is_ok());
-        let (new_size, lines) = result.unwrap();
-        assert!(new_size > initial_size);
-        assert_eq!(lines.len(), 1);
-        assert_eq!(lines[0], "line 2");
+        let mut watcher = LogWatcher::new(config);
+        tokio::time::timeout(Duration::from_secs(5), watcher.run())
+            .await
+            .expect("tail mode should stop when the command exits")
+            .unwrap();
+
+        assert_eq!(watcher.stats.matches_found, 1);
+        assert!(watcher.stats.command_status.unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn test_command_exit_without_status_flushes_its_record() {
+        let mut config = create_test_config();
+        config.multiline = Some(MultilineRules {
+            start: None,
+            indent: true,
+            timeout: Duration::from_secs(60),
+        });
+        let mut watcher = LogWatcher::new(config);
+        watcher.highlighter.capture_messages(true);
+
+        let label = command_label("./job.sh");
+        let assembler = watcher.assembler.as_mut().unwrap();
+        assembler.push(&stdin_label(), "ERROR from stdin", LinePosition::default());
+        assembler.push(&label, "ERROR from command", LinePosition::default());
+
+        let error = std::io::Error::other("wait failed");
+        watcher
+            .handle_command_exited(&label, Err(error))
+            .await
+            .unwrap();
+
+        // Only the command's record is complete; standard input's still waits
+        assert_eq!(watcher.stats.lines_processed, 1);
+        assert!(watcher
+            .assembler
+            .as_ref()
+            .unwrap()
+            .pending_offset(&stdin_label())
+            .is_some());
+        assert!(watcher.stats.command_status.is_none());
+        assert!(watcher
+            .highlighter
+            .take_messages()
+            .iter()
+            .any(|message| message.contains("wait failed")));
     }
 
     #[tokio::test]
@@ -449,7 +1608,11 @@ mod tests {
 
         // Test processing a line
         let result = watcher
-            .process_line(temp_file.path(), "ERROR: Test error")
+            .process_line(
+                temp_file.path(),
+                "ERROR: Test error",
+                LinePosition::default(),
+            )
             .await;
         assert!(result.is_ok());
     }
@@ -520,7 +1683,7 @@ mod tests {
         let result = rt.block_on(async {
             tokio::time::timeout(
                 std::time::Duration::from_millis(100),
-                watcher.run_tail_mode(&files),
+                watcher.run_tail_mode(&files, &Sources::default()),
             )
             .await
         });
@@ -558,173 +1721,498 @@ mod tests {
 
         let mut config = create_test_config();
         config.files = vec![temp_file.path().to_path_buf()];
-        config.patterns = vec!["ERROR".to_string(), "WARN".to_string()];
-        config.dry_run = true;
+        config.rules = vec![Rule::literal("ERROR"), Rule::literal("WARN")];
+        config.dry_run = true;
+
+        let mut watcher = LogWatcher::new(config);
+        let result = watcher.run().await;
+        assert!(result.is_ok());
+        assert_eq!(watcher.stats.matches_found, 3); // 2 ERROR + 1 WARN
+    }
+
+    #[tokio::test]
+    async fn test_process_line_with_notification() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ERROR: Test error").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut config = create_test_config();
+        config.notify_enabled = true;
+        config.rules = vec![Rule::literal("ERROR")];
+
+        let mut watcher = LogWatcher::new(config);
+
+        // Test processing a line that should trigger notification
+        let result = watcher
+            .process_line(
+                temp_file.path(),
+                "ERROR: Critical error occurred",
+                LinePosition::default(),
+            )
+            .await;
+
+        // Check if the result is ok, if not print the error for debugging
+        if let Err(e) = &result {
+            eprintln!("Notification test failed with error: {}", e);
+            let error_msg = e.to_string();
+            // Handle different notification system errors across platforms
+            if error_msg.contains("can only be set once") || // macOS
+               error_msg.contains("org.freedesktop.DBus.Error.ServiceUnknown") || // Linux
+               error_msg.contains(".service files") || // Linux D-Bus (various error formats)
+               error_msg.contains("Notifications") || // Linux D-Bus notification service
+               error_msg.contains("No such file or directory") || // Missing notification daemon
+               error_msg.contains("I/O error")
+            // General I/O errors for notifications
+            {
+                // This is expected behavior in test environment, so we consider it a success
+                // The notification counter is 0 because the notification failed before being sent
+                assert_eq!(watcher.stats.notifications_sent, 0);
+                return;
+            }
+        }
+
+        assert!(result.is_ok());
+        assert_eq!(watcher.stats.notifications_sent, 1);
+    }
+
+    #[tokio::test]
+    async fn test_throttled_notifications_are_not_counted() {
+        let mut config = create_test_config();
+        config.notify_enabled = true;
+        config.notify_throttle = 0;
+        config.all_matches = true;
+        config.rules = vec![Rule::literal("ERROR"), Rule::literal("timeout")];
+
+        let mut watcher = LogWatcher::new(config);
+        watcher
+            .process_line(
+                Path::new("app.log"),
+                "ERROR: upstream timeout",
+                LinePosition::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(watcher.stats.matches_found, 1);
+        assert_eq!(watcher.stats.notifications_sent, 0);
+    }
+
+    #[tokio::test]
+    async fn test_notification_for_label_without_file_name() {
+        let mut config = create_test_config();
+        config.notify_enabled = true;
+        config.rules = vec![Rule::literal("ERROR")];
+
+        let mut watcher = LogWatcher::new(config);
+
+        // `--exec ..` is labelled "..". Sending the notification may fail
+        // without a notification service, but mustn't panic.
+        let label = command_label("..");
+        let _ = watcher
+            .process_line(&label, "ERROR: from a command", LinePosition::default())
+            .await;
+        assert_eq!(watcher.stats.matches_found, 1);
+    }
+
+    #[tokio::test]
+    async fn test_process_line_without_notification() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "INFO: Normal operation").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut config = create_test_config();
+        config.notify_enabled = true;
+        config.rules = vec![Rule::literal("ERROR")];
+
+        let mut watcher = LogWatcher::new(config);
+
+        // Test processing a line that should not trigger notification
+        let result = watcher
+            .process_line(
+                temp_file.path(),
+                "INFO: Normal operation",
+                LinePosition::default(),
+            )
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(watcher.stats.notifications_sent, 0);
+    }
+
+    #[tokio::test]
+    async fn test_handle_file_rotation_file_not_found() {
+        let config = create_test_config();
+        let mut watcher = LogWatcher::new(config);
+
+        // Test file rotation handling with a non-existent file
+        let result = watcher
+            .handle_file_rotation(&PathBuf::from("/non/existent/file.log"))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_start_file_watcher() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ERROR: Test error").unwrap();
+        temp_file.flush().unwrap();
+
+        let config = create_test_config();
+        let watcher = LogWatcher::new(config);
+
+        let (tx, _rx) = mpsc::channel::<FileEvent>(100);
+        let mut changes = ChangeNotifier::new(tx.clone());
+
+        // Test watcher creation
+        let result = watcher
+            .start_file_watcher(temp_file.path().to_path_buf(), tx, &mut changes, None)
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_file_watcher_follows_rotation() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("app.log");
+        std::fs::write(&path, "ERROR: before start\n").unwrap();
+
+        // Only change events can wake the tail this quickly
+        let mut config = create_test_config();
+        config.poll_interval = 60_000;
+        let watcher = LogWatcher::new(config);
+        let (tx, mut rx) = mpsc::channel::<FileEvent>(100);
+        let mut changes = ChangeNotifier::new(tx.clone());
+        watcher
+            .start_file_watcher(path.clone(), tx, &mut changes, None)
+            .await
+            .unwrap();
+
+        let mut old = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        writeln!(old, "ERROR: last line of the old file").unwrap();
+        std::fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        std::fs::write(&path, "ERROR: first line of the new file\n").unwrap();
+
+        let mut seen = Vec::new();
+        while seen.len() < 3 {
+            let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            seen.push(match event {
+                FileEvent::NewLine { line, .. } => line,
+                FileEvent::FileRotated { .. } => "<rotated>".to_string(),
+                // Seen if a wakeup lands between the rename and the create
+                FileEvent::FileMissing { .. } => continue,
+                other => panic!("unexpected event: {:?}", other),
+            });
+        }
+        assert_eq!(
+            seen,
+            vec![
+                "ERROR: last line of the old file",
+                "<rotated>",
+                "ERROR: first line of the new file",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tail_mode_starts_with_last_lines() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ERROR: first").unwrap();
+        writeln!(temp_file, "ERROR: second").unwrap();
+        writeln!(temp_file, "ERROR: third").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut config = create_test_config();
+        config.files = vec![temp_file.path().to_path_buf()];
+        config.dry_run = false;
+        config.last_lines = Some(2);
 
         let mut watcher = LogWatcher::new(config);
-        let result = watcher.run().await;
-        assert!(result.is_ok());
-        assert_eq!(watcher.stats.matches_found, 3); // 2 ERROR + 1 WARN
+        let result = tokio::time::timeout(Duration::from_millis(300), watcher.run()).await;
+        assert!(result.is_err(), "tail mode runs until interrupted");
+
+        assert_eq!(watcher.stats.lines_processed, 2);
     }
 
     #[tokio::test]
-    async fn test_poll_file_changes_with_rotation() {
+    async fn test_multiline_record_flushed_after_timeout() {
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "line 1").unwrap();
-        temp_file.flush().unwrap();
+        let mut config = create_test_config();
+        config.files = vec![temp_file.path().to_path_buf()];
+        config.dry_run = false;
+        config.multiline = Some(MultilineRules {
+            start: None,
+            indent: true,
+            timeout: Duration::from_millis(100),
+        });
 
-        let initial_size = get_file_size(temp_file.path()).unwrap();
+        let mut watcher = LogWatcher::new(config);
+        let run = tokio::time::timeout(Duration::from_millis(800), watcher.run());
+        let write = async {
+            sleep(Duration::from_millis(200)).await;
+            write!(
+                temp_file,
+                "ERROR Request failed\n\tat Service.run\n\tat Main.main\n"
+            )
+            .unwrap();
+            temp_file.flush().unwrap();
+        };
+        let (result, ()) = tokio::join!(run, write);
+        assert!(result.is_err(), "tail mode runs until interrupted");
 
-        // Simulate file rotation by truncating the file
-        temp_file.as_file_mut().set_len(0).unwrap();
-        temp_file.flush().unwrap();
+        // No line followed the trace; the timeout completed it
+        assert_eq!(watcher.stats.lines_processed, 1);
+        assert_eq!(watcher.stats.matches_found, 1);
+    }
 
-        let result =
-            LogWatcher::poll_file_changes(&temp_file.path().to_path_buf(), initial_size, 1024)
-                .await;
+    #[test]
+    fn test_checkpoint_held_while_record_is_pending() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let log = temp_dir.path().join("app.log");
+        std::fs::write(&log, "ERROR Request failed\n\tat Service.run\n").unwrap();
+        let id = FileId::of_path(&log).unwrap();
 
-        // Should detect file rotation
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("File rotation detected"));
+        let mut config = create_test_config();
+        config.multiline = Some(MultilineRules {
+            start: None,
+            indent: true,
+            timeout: Duration::from_millis(100),
+        });
+        let mut watcher = LogWatcher::new(config);
+        let mut state = StateFile::new(temp_dir.path().join("state.json"));
+
+        let assembler = watcher.assembler.as_mut().unwrap();
+        assembler.push(&log, "ERROR Request failed", LinePosition::default());
+        assembler.push(
+            &log,
+            "\tat Service.run",
+            LinePosition {
+                offset: 21,
+                number: Some(2),
+            },
+        );
+        let checkpoint = Checkpoint { id, offset: 37 };
+        watcher.record_checkpoint(&mut state, &log, checkpoint);
+        assert_eq!(state.resume_offset(&log), Some(0));
+
+        // Once the record is complete, the next save moves the checkpoint on
+        watcher.assembler.as_mut().unwrap().flush(&log);
+        watcher.save_state(Some(&mut state)).unwrap();
+        assert_eq!(state.resume_offset(&log), Some(37));
+        assert!(watcher.held_checkpoints.is_empty());
     }
 
     #[tokio::test]
-    async fn test_poll_file_changes_no_new_content() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "line 1").unwrap();
-        temp_file.flush().unwrap();
-
-        let initial_size = get_file_size(temp_file.path()).unwrap();
+    async fn test_dry_run_compressed_file_with_last_lines() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut encoder =
+            flate2::write::GzEncoder::new(temp_file.reopen().unwrap(), Default::default());
+        encoder
+            .write_all(b"ERROR: one\nERROR: two\nERROR: three\n")
+            .unwrap();
+        encoder.finish().unwrap();
 
-        let result =
-            LogWatcher::poll_file_changes(&temp_file.path().to_path_buf(), initial_size, 1024)
-                .await;
+        let mut config = create_test_config();
+        config.files = vec![temp_file.path().to_path_buf()];
+        config.last_lines = Some(2);
 
-        assert!(result.is_ok());
-        let (new_size, lines) = result.unwrap();
-        assert_eq!(new_size, initial_size);
-        assert_eq!(lines.len(), 0);
+        let mut watcher = LogWatcher::new(config);
+        watcher.run().await.unwrap();
+        assert_eq!(watcher.stats.lines_processed, 2);
+        assert_eq!(watcher.stats.matches_found, 2);
     }
 
     #[tokio::test]
-    async fn test_poll_file_changes_with_seeking() {
+    async fn test_partial_line_emitted_after_timeout() {
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "line 1").unwrap();
-        writeln!(temp_file, "line 2").unwrap();
-        temp_file.flush().unwrap();
+        let mut config = create_test_config();
+        config.files = vec![temp_file.path().to_path_buf()];
+        config.dry_run = false;
+        config.partial_timeout = Duration::from_millis(100);
+
+        let mut watcher = LogWatcher::new(config);
+        let run = tokio::time::timeout(Duration::from_millis(800), watcher.run());
+        let write = async {
+            sleep(Duration::from_millis(200)).await;
+            write!(temp_file, "ERROR: no newline yet").unwrap();
+            temp_file.flush().unwrap();
+        };
+        let (result, ()) = tokio::join!(run, write);
+        assert!(result.is_err(), "tail mode runs until interrupted");
 
-        let initial_size = get_file_size(temp_file.path()).unwrap();
+        assert_eq!(watcher.stats.lines_processed, 1);
+        assert_eq!(watcher.stats.matches_found, 1);
+    }
 
-        // Add more content
-        writeln!(temp_file, "line 3").unwrap();
-        writeln!(temp_file, "line 4").unwrap();
-        temp_file.flush().unwrap();
+    /// Run tail mode on a glob while a matching file is created and deleted
+    async fn discover_and_release(mut config: Config, settle: Duration) -> WatcherStats {
+        let dir = tempfile::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("old.log"), "ERROR: before start\n").unwrap();
+        config.files = vec![dir.path().join("*.log")];
+        config.dry_run = false;
 
-        let result =
-            LogWatcher::poll_file_changes(&temp_file.path().to_path_buf(), initial_size, 1024)
-                .await;
+        let mut watcher = LogWatcher::new(config);
+        let new_file = dir.path().join("new.log");
+        // Removed files are released after MISSING_GRACE
+        let run = tokio::time::timeout(settle * 3 + MISSING_GRACE, watcher.run());
+        let changes = async {
+            sleep(settle / 2).await;
+            std::fs::write(dir.path().join("new.txt"), "ERROR: not matched\n").unwrap();
+            std::fs::write(&new_file, "ERROR: written before it was seen\n").unwrap();
+            sleep(settle).await;
+            std::fs::remove_file(&new_file).unwrap();
+        };
+        let (result, ()) = tokio::join!(run, changes);
+        assert!(result.is_err(), "tail mode runs until interrupted");
 
-        assert!(result.is_ok());
-        let (new_size, lines) = result.unwrap();
-        assert!(new_size > initial_size);
-        assert_eq!(lines.len(), 2);
-        assert_eq!(lines[0], "line 3");
-        assert_eq!(lines[1], "line 4");
+        watcher.stats
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn test_process_line_with_notification() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "ERROR: Test error").unwrap();
-        temp_file.flush().unwrap();
+    async fn test_glob_picks_up_new_files() {
+        let stats = discover_and_release(create_test_config(), Duration::from_millis(500)).await;
 
+        assert_eq!(stats.files_watched, 1);
+        assert_eq!(stats.files_added, 1);
+        assert_eq!(stats.files_removed, 1);
+        // New files are read from the start, existing ones from the end
+        assert_eq!(stats.matches_found, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_glob_rescanned_when_polling() {
         let mut config = create_test_config();
-        config.notify_enabled = true;
-        config.notify_patterns = vec!["ERROR".to_string()];
+        config.force_poll = true;
+        config.poll_interval = 50;
+        let stats = discover_and_release(config, Duration::from_millis(1500)).await;
 
-        let mut watcher = LogWatcher::new(config);
+        assert_eq!(stats.files_added, 1);
+        assert_eq!(stats.files_removed, 1);
+        assert_eq!(stats.matches_found, 1);
+    }
 
-        // Test processing a line that should trigger notification
-        let result = watcher
-            .process_line(temp_file.path(), "ERROR: Critical error occurred")
-            .await;
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_discovered_file_rotated_by_rename_and_create() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = dir.path().join("app.log");
+        let mut config = create_test_config();
+        config.files = vec![dir.path().join("*.log")];
+        config.dry_run = false;
 
-        // Check if the result is ok, if not print the error for debugging
-        if let Err(e) = &result {
-            eprintln!("Notification test failed with error: {}", e);
-            let error_msg = e.to_string();
-            // Handle different notification system errors across platforms
-            if error_msg.contains("can only be set once") || // macOS
-               error_msg.contains("org.freedesktop.DBus.Error.ServiceUnknown") || // Linux
-               error_msg.contains(".service files") || // Linux D-Bus (various error formats)
-               error_msg.contains("Notifications") || // Linux D-Bus notification service
-               error_msg.contains("No such file or directory") || // Missing notification daemon
-               error_msg.contains("I/O error")
-            // General I/O errors for notifications
-            {
-                // This is expected behavior in test environment, so we consider it a success
-                // The notification counter is 0 because the notification failed before being sent
-                assert_eq!(watcher.stats.notifications_sent, 0);
-                return;
-            }
-        }
+        let mut watcher = LogWatcher::new(config);
+        let run = tokio::time::timeout(Duration::from_millis(2000), watcher.run());
+        let rotate = async {
+            sleep(Duration::from_millis(200)).await;
+            std::fs::write(&log, "").unwrap();
+            sleep(Duration::from_millis(300)).await;
+            let rotated = dir.path().join("app.log.1");
+            std::fs::rename(&log, &rotated).unwrap();
+            sleep(Duration::from_millis(100)).await;
+            // The writer hasn't reopened the file yet
+            let mut old = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&rotated)
+                .unwrap();
+            writeln!(old, "ERROR: written after the rename").unwrap();
+            sleep(Duration::from_millis(300)).await;
+            std::fs::write(&log, "ERROR: written to the new file\n").unwrap();
+        };
+        let (result, ()) = tokio::join!(run, rotate);
+        assert!(result.is_err(), "tail mode runs until interrupted");
 
-        assert!(result.is_ok());
-        assert_eq!(watcher.stats.notifications_sent, 1);
+        assert_eq!(watcher.stats.files_added, 1);
+        assert_eq!(watcher.stats.matches_found, 2);
+        assert_eq!(watcher.stats.files_removed, 0);
     }
 
-    #[tokio::test]
-    async fn test_process_line_without_notification() {
+    /// Tail a fresh file and wait for one appended line
+    async fn first_line_after_write(config: Config) -> (String, Duration) {
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "INFO: Normal operation").unwrap();
+        let watcher = LogWatcher::new(config);
+        let (tx, mut rx) = mpsc::channel::<FileEvent>(100);
+        let mut changes = ChangeNotifier::new(tx.clone());
+        watcher
+            .start_file_watcher(temp_file.path().to_path_buf(), tx, &mut changes, None)
+            .await
+            .unwrap();
+        // Let the task finish its first read before writing
+        sleep(Duration::from_millis(50)).await;
+
+        let written = std::time::Instant::now();
+        writeln!(temp_file, "ERROR: fresh write").unwrap();
         temp_file.flush().unwrap();
 
-        let mut config = create_test_config();
-        config.notify_enabled = true;
-        config.notify_patterns = vec!["ERROR".to_string()];
+        match tokio::time::timeout(Duration::from_secs(5), rx.recv()).await {
+            Ok(Some(FileEvent::NewLine { line, .. })) => (line, written.elapsed()),
+            other => panic!("expected a line, got {:?}", other),
+        }
+    }
 
-        let mut watcher = LogWatcher::new(config);
+    #[tokio::test]
+    async fn test_change_events_drive_reads() {
+        let mut config = create_test_config();
+        config.poll_interval = 60_000;
 
-        // Test processing a line that should not trigger notification
-        let result = watcher
-            .process_line(temp_file.path(), "INFO: Normal operation")
-            .await;
-        assert!(result.is_ok());
-        assert_eq!(watcher.stats.notifications_sent, 0);
+        let (line, latency) = first_line_after_write(config).await;
+        assert_eq!(line, "ERROR: fresh write");
+        assert!(latency < Duration::from_secs(1), "took {:?}", latency);
     }
 
     #[tokio::test]
-    async fn test_handle_file_rotation_file_not_found() {
-        let config = create_test_config();
-        let mut watcher = LogWatcher::new(config);
+    async fn test_forced_polling() {
+        let mut config = create_test_config();
+        config.force_poll = true;
+        config.poll_interval = 10;
 
-        // Test file rotation handling with a non-existent file
-        let result = watcher
-            .handle_file_rotation(&PathBuf::from("/non/existent/file.log"))
-            .await;
-        assert!(result.is_ok());
+        let (line, _) = first_line_after_write(config).await;
+        assert_eq!(line, "ERROR: fresh write");
     }
 
     #[tokio::test]
-    async fn test_start_file_watcher() {
+    async fn test_tail_task_stops_without_receiver() {
         let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "ERROR: Test error").unwrap();
-        temp_file.flush().unwrap();
-
-        let config = create_test_config();
+        let mut config = create_test_config();
+        config.force_poll = true;
+        config.poll_interval = 10;
         let watcher = LogWatcher::new(config);
+        let (tx, rx) = mpsc::channel::<FileEvent>(100);
+        let mut changes = ChangeNotifier::new(tx.clone());
+        let task = watcher
+            .start_file_watcher(temp_file.path().to_path_buf(), tx, &mut changes, None)
+            .await
+            .unwrap();
 
-        let (tx, _rx) = mpsc::channel::<FileEvent>(100);
+        drop(rx);
+        writeln!(temp_file, "ERROR: nobody reads this").unwrap();
+        temp_file.flush().unwrap();
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("the task ends once its line can't be sent")
+            .unwrap();
+    }
 
-        // Test watcher creation
-        let result = watcher
-            .start_file_watcher(temp_file.path().to_path_buf(), tx)
-            .await;
+    #[tokio::test]
+    async fn test_dropping_watched_files_aborts_tasks() {
+        let (alive, mut stopped) = mpsc::channel::<()>(1);
+        let task = tokio::spawn(async move {
+            let _alive = alive;
+            std::future::pending::<()>().await;
+        });
+        let mut watched = WatchedFiles::default();
+        watched.add(PathBuf::from("app.log"), task, false);
 
-        assert!(result.is_ok());
+        drop(watched);
+        // The sender is dropped with the aborted task
+        let result = tokio::time::timeout(Duration::from_secs(5), stopped.recv()).await;
+        assert_eq!(result, Ok(None));
     }
 
     #[tokio::test]
@@ -740,7 +2228,11 @@ mod tests {
 
         // Test FileEvent::NewLine processing
         let result = watcher
-            .process_line(temp_file.path(), "ERROR: New error occurred")
+            .process_line(
+                temp_file.path(),
+                "ERROR: New error occurred",
+                LinePosition::default(),
+            )
             .await;
         assert!(result.is_ok());
         assert_eq!(watcher.stats.lines_processed, 1);
@@ -799,7 +2291,7 @@ mod tests {
         // Test tail mode with a short timeout to avoid hanging
         let result = tokio::time::timeout(
             std::time::Duration::from_millis(50),
-            watcher.run_tail_mode(&files),
+            watcher.run_tail_mode(&files, &Sources::default()),
         )
         .await;
 
@@ -818,7 +2310,11 @@ mod tests {
 
         // Test processing a new line event
         let result = watcher
-            .process_line(temp_file.path(), "ERROR: New error occurred")
+            .process_line(
+                temp_file.path(),
+                "ERROR: New error occurred",
+                LinePosition::default(),
+            )
             .await;
         assert!(result.is_ok());
         assert_eq!(watcher.stats.matches_found, 1);
@@ -866,40 +2362,13 @@ mod tests {
 
         // Test error handling in start_file_watcher
         let (tx, _rx) = tokio::sync::mpsc::channel(100);
+        let mut changes = ChangeNotifier::new(tx.clone());
         let result = watcher
-            .start_file_watcher(temp_file.path().to_path_buf(), tx)
+            .start_file_watcher(temp_file.path().to_path_buf(), tx, &mut changes, None)
             .await;
         assert!(result.is_ok());
     }
 
-    #[tokio::test]
-    async fn test_poll_file_changes_error_handling() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "line 1").unwrap();
-        temp_file.flush().unwrap();
-
-        let initial_size = get_file_size(temp_file.path()).unwrap();
-
-        // Test error handling in poll_file_changes
-        let result =
-            LogWatcher::poll_file_changes(&temp_file.path().to_path_buf(), initial_size, 1024)
-                .await;
-
-        assert!(result.is_ok());
-        let (new_size, lines) = result.unwrap();
-        assert_eq!(new_size, initial_size);
-        assert_eq!(lines.len(), 0);
-    }
-
-    #[tokio::test]
-    async fn test_poll_file_changes_with_file_error() {
-        // Test with non-existent file to trigger error path
-        let result =
-            LogWatcher::poll_file_changes(&PathBuf::from("/non/existent/file.log"), 0, 1024).await;
-
-        assert!(result.is_err());
-    }
-
     #[tokio::test]
     async fn test_file_event_channel_error() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -918,6 +2387,7 @@ mod tests {
             .send(FileEvent::NewLine {
                 file_path: temp_file.path().to_path_buf(),
                 line: "ERROR: Test".to_string(),
+                position: LinePosition::default(),
             })
             .await;
 
@@ -934,7 +2404,7 @@ mod tests {
         let files = vec![PathBuf::from("/non/existent/file.log")];
 
         // Test dry mode with file error
-        let result = watcher.run_dry_mode(&files).await;
+        let result = watcher.run_dry_mode(&files, &Sources::default()).await;
         assert!(result.is_ok());
     }
 
@@ -949,7 +2419,7 @@ mod tests {
         // Test tail mode with file error - should handle gracefully
         let result = tokio::time::timeout(
             std::time::Duration::from_millis(100),
-            watcher.run_tail_mode(&files),
+            watcher.run_tail_mode(&files, &Sources::default()),
         )
         .await;
 
@@ -979,6 +2449,7 @@ mod tests {
                 .send(FileEvent::NewLine {
                     file_path: file_path.clone(),
                     line: "ERROR: Test".to_string(),
+                    position: LinePosition::default(),
                 })
                 .await;
         });
@@ -994,6 +2465,7 @@ mod tests {
             .send(FileEvent::NewLine {
                 file_path: temp_file.path().to_path_buf(),
                 line: "ERROR: Test".to_string(),
+                position: LinePosition::default(),
             })
             .await;
 
@@ -1024,26 +2496,6 @@ mod tests {
         assert!(result.is_err());
     }
 
-    #[tokio::test]
-    async fn test_poll_file_changes_error_sending() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "ERROR: Test error").unwrap();
-        temp_file.flush().unwrap();
-
-        let config = create_test_config();
-        let _watcher = LogWatcher::new(config);
-
-        // Create a channel and close the receiver to trigger send errors
-        let (_tx, rx) = tokio::sync::mpsc::channel::<FileEvent>(1);
-        drop(rx);
-
-        // Test the error path in poll_file_changes
-        let result =
-            LogWatcher::poll_file_changes(&PathBuf::from("/non/existent/file.log"), 0, 1024).await;
-
-        assert!(result.is_err());
-    }
-
     #[tokio::test]
     async fn test_startup_info_coverage() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -1056,7 +2508,8 @@ mod tests {
         let mut watcher = LogWatcher::new(config);
 
         // Test that startup info is printed
-        let result = watcher.highlighter.print_startup_info();
+        let stats = WatcherStats::default();
+        let result = watcher.highlighter.print_startup_info(&stats, &[]);
         assert!(result.is_ok());
     }
 
@@ -1074,7 +2527,7 @@ mod tests {
         let files = vec![temp_file.path().to_path_buf()];
 
         // Test dry run mode to cover summary printing
-        let result = watcher.run_dry_mode(&files).await;
+        let result = watcher.run_dry_mode(&files, &Sources::default()).await;
         assert!(result.is_ok());
     }
 
@@ -1093,7 +2546,7 @@ mod tests {
         let files = vec![temp_file.path().to_path_buf()];
 
         // Test dry run mode to cover pattern counting
-        let result = watcher.run_dry_mode(&files).await;
+        let result = watcher.run_dry_mode(&files, &Sources::default()).await;
         assert!(result.is_ok());
     }
 
@@ -1149,7 +2602,7 @@ mod tests {
         // Test tail mode with timeout to avoid hanging
         let result = tokio::time::timeout(
             std::time::Duration::from_millis(100),
-            watcher.run_tail_mode(&files),
+            watcher.run_tail_mode(&files, &Sources::default()),
         )
         .await;
 
@@ -1173,6 +2626,7 @@ mod tests {
             FileEvent::NewLine {
                 file_path: temp_file.path().to_path_buf(),
                 line: "ERROR: Test error".to_string(),
+                position: LinePosition::default(),
             },
             FileEvent::FileRotated {
                 file_path: temp_file.path().to_path_buf(),
@@ -1185,15 +2639,25 @@ mod tests {
 
         for event in events {
             let result = match event {
-                FileEvent::NewLine { file_path, line } => {
-                    watcher.process_line(&file_path, &line).await
-                }
+                FileEvent::NewLine {
+                    file_path,
+                    line,
+                    position,
+                } => watcher.process_line(&file_path, &line, position).await,
                 FileEvent::FileRotated { file_path } => {
                     watcher.handle_file_rotation(&file_path).await
                 }
                 FileEvent::FileError { file_path, error } => watcher
                     .highlighter
                     .print_file_error(&file_path.display().to_string(), &error.to_string()),
+                FileEvent::FileTruncated { .. }
+                | FileEvent::FileMissing { .. }
+                | FileEvent::CommandExited { .. }
+                | FileEvent::StreamEnded
+                | FileEvent::FileCreated { .. }
+                | FileEvent::Rescan
+                | FileEvent::ReleaseMissing
+                | FileEvent::Checkpoint { .. } => Ok(()),
             };
             assert!(result.is_ok());
         }
@@ -1210,35 +2674,14 @@ mod tests {
 
         // Test error path in start_file_watcher
         let (tx, _rx) = tokio::sync::mpsc::channel::<FileEvent>(1);
+        let mut changes = ChangeNotifier::new(tx.clone());
         let file_path = temp_file.path().to_path_buf();
 
         // This should work without errors
-        let result = watcher.start_file_watcher(file_path, tx).await;
-        assert!(result.is_ok());
-    }
-
-    #[tokio::test]
-    async fn test_poll_file_changes_seek_coverage() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "ERROR: Test error").unwrap();
-        writeln!(temp_file, "INFO: Normal operation").unwrap();
-        temp_file.flush().unwrap();
-
-        let config = create_test_config();
-        let _watcher = LogWatcher::new(config);
-
-        // Test poll_file_changes with seeking
-        let result = LogWatcher::poll_file_changes(
-            &temp_file.path().to_path_buf(),
-            0, // Start from beginning
-            1024,
-        )
-        .await;
-
+        let result = watcher
+            .start_file_watcher(file_path, tx, &mut changes, None)
+            .await;
         assert!(result.is_ok());
-        let (new_size, lines) = result.unwrap();
-        assert!(new_size > 0);
-        assert!(!lines.is_empty());
     }
 
     #[tokio::test]
@@ -1249,13 +2692,17 @@ mod tests {
 
         let mut config = create_test_config();
         config.notify_enabled = true;
-        config.notify_patterns = vec!["ERROR".to_string()];
+        config.rules = vec![Rule::literal("ERROR")];
 
         let mut watcher = LogWatcher::new(config);
 
         // Test process_line with notification enabled
         let result = watcher
-            .process_line(temp_file.path(), "ERROR: Critical error occurred")
+            .process_line(
+                temp_file.path(),
+                "ERROR: Critical error occurred",
+                LinePosition::default(),
+            )
             .await;
 
         // Check if the result is ok, if not print the error for debugging
@@ -1300,6 +2747,7 @@ mod tests {
             FileEvent::NewLine {
                 file_path: temp_file.path().to_path_buf(),
                 line: "ERROR: Test".to_string(),
+                position: LinePosition::default(),
             },
             FileEvent::FileError {
                 file_path: temp_file.path().to_path_buf(),
@@ -1346,13 +2794,17 @@ mod tests {
 
         let mut config = create_test_config();
         config.notify_enabled = true;
-        config.notify_patterns = vec!["ERROR".to_string()];
+        config.rules = vec![Rule::literal("ERROR")];
 
         let mut watcher = LogWatcher::new(config);
 
-        // Test process_line to cover file_name().unwrap() calls
+        // Test process_line with a notification for the line
         let result = watcher
-            .process_line(temp_file.path(), "ERROR: Critical error occurred")
+            .process_line(
+                temp_file.path(),
+                "ERROR: Critical error occurred",
+                LinePosition::default(),
+            )
             .await;
 
         // Check if the result is ok, if not print the error for debugging
@@ -1404,7 +2856,7 @@ mod tests {
 
         let mut config = create_test_config();
         config.files = vec![temp_file.path().to_path_buf()];
-        config.patterns = vec!["ERROR".to_string(), "WARN".to_string()];
+        config.rules = vec![Rule::literal("ERROR"), Rule::literal("WARN")];
         config.dry_run = true;
 
         let mut watcher = LogWatcher::new(config);
@@ -1430,6 +2882,7 @@ mod tests {
             FileEvent::NewLine {
                 file_path: temp_file.path().to_path_buf(),
                 line: "ERROR: Test error".to_string(),
+                position: LinePosition::default(),
             },
             FileEvent::FileRotated {
                 file_path: temp_file.path().to_path_buf(),
@@ -1442,15 +2895,25 @@ mod tests {
 
         for event in events {
             let result = match event {
-                FileEvent::NewLine { file_path, line } => {
-                    watcher.process_line(&file_path, &line).await
-                }
+                FileEvent::NewLine {
+                    file_path,
+                    line,
+                    position,
+                } => watcher.process_line(&file_path, &line, position).await,
                 FileEvent::FileRotated { file_path } => {
                     watcher.handle_file_rotation(&file_path).await
                 }
                 FileEvent::FileError { file_path, error } => watcher
                     .highlighter
                     .print_file_error(&file_path.display().to_string(), &error.to_string()),
+                FileEvent::FileTruncated { .. }
+                | FileEvent::FileMissing { .. }
+                | FileEvent::CommandExited { .. }
+                | FileEvent::StreamEnded
+                | FileEvent::FileCreated { .. }
+                | FileEvent::Rescan
+                | FileEvent::ReleaseMissing
+                | FileEvent::Checkpoint { .. } => Ok(()),
             };
             assert!(result.is_ok());
         }
@@ -1489,30 +2952,6 @@ mod tests {
         assert!(result.is_err());
     }
 
-    #[tokio::test]
-    async fn test_seek_operation_coverage_line_216() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "ERROR: Test error").unwrap();
-        writeln!(temp_file, "INFO: Normal operation").unwrap();
-        temp_file.flush().unwrap();
-
-        let config = create_test_config();
-        let _watcher = LogWatcher::new(config);
-
-        // Test poll_file_changes with seeking to cover line 216
-        let result = LogWatcher::poll_file_changes(
-            &temp_file.path().to_path_buf(),
-            0, // Start from beginning to trigger seek
-            1024,
-        )
-        .await;
-
-        assert!(result.is_ok());
-        let (new_size, lines) = result.unwrap();
-        assert!(new_size > 0);
-        assert!(!lines.is_empty());
-    }
-
     #[tokio::test]
     async fn test_notification_success_coverage_line_283() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -1521,13 +2960,17 @@ mod tests {
 
         let mut config = create_test_config();
         config.notify_enabled = true;
-        config.notify_patterns = vec!["ERROR".to_string()];
+        config.rules = vec![Rule::literal("ERROR")];
 
         let mut watcher = LogWatcher::new(config);
 
         // Test process_line with notification to cover line 283
         let result = watcher
-            .process_line(temp_file.path(), "ERROR: Critical error occurred")
+            .process_line(
+                temp_file.path(),
+                "ERROR: Critical error occurred",
+                LinePosition::default(),
+            )
             .await;
 
         // Check if the result is ok, if not print the error for debugging
@@ -1575,36 +3018,13 @@ mod tests {
             .send(FileEvent::NewLine {
                 file_path: temp_file.path().to_path_buf(),
                 line: "ERROR: Test".to_string(),
+                position: LinePosition::default(),
             })
             .await;
 
         assert!(result.is_err());
     }
 
-    #[tokio::test]
-    async fn test_poll_file_changes_with_seek_coverage_line_216() {
-        let mut temp_file = NamedTempFile::new().unwrap();
-        writeln!(temp_file, "ERROR: Test error").unwrap();
-        writeln!(temp_file, "INFO: Normal operation").unwrap();
-        writeln!(temp_file, "WARN: Warning message").unwrap();
-        temp_file.flush().unwrap();
-
-        let config = create_test_config();
-        let _watcher = LogWatcher::new(config);
-
-        // Test poll_file_changes with different seek positions to cover line 216
-        let result = LogWatcher::poll_file_changes(
-            &temp_file.path().to_path_buf(),
-            10, // Seek to position 10 to trigger seek operation
-            1024,
-        )
-        .await;
-
-        assert!(result.is_ok());
-        let (new_size, _lines) = result.unwrap();
-        assert!(new_size > 0);
-    }
-
     #[tokio::test]
     async fn test_comprehensive_file_event_processing() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -1621,7 +3041,11 @@ mod tests {
 
         // Test NewLine event processing
         let result = watcher
-            .process_line(&file_path, "ERROR: New error occurred")
+            .process_line(
+                &file_path,
+                "ERROR: New error occurred",
+                LinePosition::default(),
+            )
             .await;
         assert!(result.is_ok());
 
@@ -1635,4 +3059,46 @@ mod tests {
             .print_file_error(&file_path.display().to_string(), "Test error");
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_all_matches_counts_every_rule() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        writeln!(temp_file, "ERROR: upstream WARN timeout").unwrap();
+        writeln!(temp_file, "WARN: slow response").unwrap();
+        temp_file.flush().unwrap();
+
+        let mut config = create_test_config();
+        config.rules = vec![Rule::literal("ERROR"), Rule::literal("WARN")];
+        config.all_matches = true;
+
+        let mut watcher = LogWatcher::new(config);
+        let counts = watcher
+            .process_existing_file(&temp_file.path().to_path_buf())
+            .await
+            .unwrap();
+
+        assert_eq!(watcher.stats.matches_found, 2);
+        assert_eq!(counts.get("ERROR"), Some(&1));
+        assert_eq!(counts.get("WARN"), Some(&2));
+        assert_eq!(watcher.stats.rule_matches.get("WARN"), Some(&2));
+    }
+
+    #[tokio::test]
+    async fn test_first_match_counts_one_rule_per_line() {
+        let mut config = create_test_config();
+        config.rules = vec![Rule::literal("ERROR"), Rule::literal("WARN")];
+
+        let mut watcher = LogWatcher::new(config);
+        watcher
+            .process_line(
+                Path::new("app.log"),
+                "ERROR: upstream WARN timeout",
+                LinePosition::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(watcher.stats.rule_matches.get("ERROR"), Some(&1));
+        assert_eq!(watcher.stats.rule_matches.get("WARN"), None);
+    }
 }
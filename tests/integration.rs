@@ -277,3 +277,865 @@ fn test_notification_patterns() {
         .stdout(predicate::str::contains("ERROR: Critical error occurred"))
         .stdout(predicate::str::contains("INFO: Normal operation"));
 }
+
+#[test]
+fn test_config_file_profile() {
+    let mut log_file = NamedTempFile::new().unwrap();
+    writeln!(log_file, "payment declined for order 42").unwrap();
+    writeln!(log_file, "ERROR: unrelated failure").unwrap();
+    log_file.flush().unwrap();
+
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(
+        config_file,
+        "patterns = [\"ERROR\"]\n\n[profiles.payments]\nfiles = [{:?}]\npatterns = [\"declined\"]",
+        log_file.path().to_str().unwrap()
+    )
+    .unwrap();
+    config_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--config",
+        config_file.path().to_str().unwrap(),
+        "--profile",
+        "payments",
+        "--dry-run",
+        "--quiet",
+        "--no-color",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("payment declined"))
+        .stdout(predicate::str::contains("unrelated failure").not());
+}
+
+#[test]
+fn test_config_file_unknown_profile() {
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(
+        config_file,
+        "[profiles.payments]\npatterns = [\"declined\"]"
+    )
+    .unwrap();
+    config_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--config",
+        config_file.path().to_str().unwrap(),
+        "--profile",
+        "billing",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("Unknown profile: billing"));
+}
+
+#[test]
+fn test_highlight_mode_span_keeps_line_text() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "prefix text ERROR: disk full, WARN follows").unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--pattern",
+        "ERROR,WARN",
+        "--highlight-mode",
+        "span",
+        "--no-color",
+    ]);
+
+    cmd.assert().success().stdout(predicate::str::contains(
+        "[DRY-RUN] prefix text ERROR: disk full, WARN follows",
+    ));
+}
+
+#[test]
+fn test_all_matches_summary() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "ERROR: request timeout").unwrap();
+    writeln!(temp_file, "WARN: retrying").unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--pattern",
+        "ERROR,WARN,timeout",
+        "--all-matches",
+        "--no-color",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("timeout: 1 matches"))
+        .stdout(predicate::str::contains("ERROR: 1 matches"))
+        .stdout(predicate::str::contains("WARN: 1 matches"));
+}
+
+#[test]
+fn test_json_field_rules() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, r#"{{"level":"info","status":200,"msg":"ok"}}"#).unwrap();
+    writeln!(
+        temp_file,
+        r#"{{"level":"error","status":503,"msg":"upstream"}}"#
+    )
+    .unwrap();
+    writeln!(
+        temp_file,
+        r#"{{"level":"warn","status":504,"msg":"gateway timeout"}}"#
+    )
+    .unwrap();
+    writeln!(temp_file, "plain text ERROR line").unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--where",
+        "status >= 500 and msg !~ /timeout/",
+        "--where",
+        r#"level == "warn""#,
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("upstream"))
+        .stdout(predicate::str::contains("gateway timeout"))
+        .stdout(predicate::str::contains(
+            "status >= 500 and msg !~ /timeout/: 1 matches",
+        ))
+        .stdout(predicate::str::contains(r#"level == "warn": 1 matches"#))
+        // --where replaces the default patterns
+        .stdout(predicate::str::contains("plain text ERROR line").not());
+}
+
+#[test]
+fn test_json_lines_fall_back_to_text_patterns() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, r#"{{"level":"error","status":503}}"#).unwrap();
+    writeln!(temp_file, "ERROR not json").unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--pattern",
+        "ERROR",
+        "--where",
+        "status == 503",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("status == 503: 1 matches"))
+        .stdout(predicate::str::contains("ERROR: 1 matches"));
+}
+
+#[test]
+fn test_invalid_field_expression() {
+    let temp_file = NamedTempFile::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--where",
+        "status >=",
+    ]);
+
+    cmd.assert()
+        .failure()
+        .code(2)
+        .stderr(predicate::str::contains("Invalid field rule"));
+}
+
+#[test]
+fn test_logfmt_field_rules_and_rendering() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, r#"level=warn msg="disk low" pct=93"#).unwrap();
+    writeln!(temp_file, r#"level=warning msg="disk ok" pct=40"#).unwrap();
+    writeln!(temp_file, r#"level=info msg="all good" pct=12"#).unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--render-fields",
+        "--where",
+        "pct > 30",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("pct > 30: 2 matches"))
+        // Shorter values are padded to line up with earlier lines
+        .stdout(predicate::str::contains(
+            r#"[DRY-RUN] level=warning msg="disk ok"  pct=40"#,
+        ))
+        .stdout(predicate::str::contains("all good").not());
+}
+
+#[test]
+fn test_syslog_field_rules() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(
+        temp_file,
+        "<38>Oct 11 22:14:15 web1 sshd[4123]: Accepted publickey for deploy"
+    )
+    .unwrap();
+    writeln!(
+        temp_file,
+        "<34>Oct 11 22:14:16 web1 sshd[4124]: Failed password for root"
+    )
+    .unwrap();
+    writeln!(
+        temp_file,
+        "<34>1 2003-10-11T22:14:15.003Z web1 su - ID47 - 'su root' failed"
+    )
+    .unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--input-format",
+        "syslog",
+        "--where",
+        "severity <= err and app = sshd",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Failed password for root"))
+        .stdout(predicate::str::contains(
+            "severity <= err and app = sshd: 1 matches",
+        ))
+        .stdout(predicate::str::contains("Accepted publickey").not())
+        .stdout(predicate::str::contains("'su root' failed").not());
+}
+
+#[test]
+fn test_access_log_rules_and_summary() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    for (path, status) in [("/", 200), ("/api", 503), ("/api", 502), ("/login", 500)] {
+        writeln!(
+            temp_file,
+            r#"10.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET {} HTTP/1.1" {} 12 "-" "curl/8.0""#,
+            path, status
+        )
+        .unwrap();
+    }
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--where",
+        "status >= 500",
+        "--summarize",
+        "path",
+        "--summarize",
+        "status_class",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("status >= 500: 3 matches"))
+        .stdout(predicate::str::contains(
+            "  By path:\n    /api: 2\n    /login: 1",
+        ))
+        .stdout(predicate::str::contains("  By status_class:\n    5xx: 3"));
+}
+
+#[test]
+fn test_custom_log_format() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, r#"10.0.0.1 "GET /fast HTTP/1.1" 200 0.120"#).unwrap();
+    writeln!(temp_file, r#"10.0.0.1 "GET /slow HTTP/1.1" 200 2.750"#).unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--log-format",
+        r#"$remote_addr "$request" $status $request_time"#,
+        "--where",
+        "request_time > 2.0",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("/slow"))
+        .stdout(predicate::str::contains("/fast").not());
+}
+
+#[test]
+fn test_named_captures_in_summary() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "sshd: password for root failed").unwrap();
+    writeln!(temp_file, "sshd: password for alice failed").unwrap();
+    writeln!(temp_file, "sshd: password for root failed").unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--regex",
+        "--pattern",
+        r"for (?P<user>\w+) failed",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r"By user (for (?P<user>\w+) failed):",
+        ))
+        .stdout(predicate::str::contains("root: 2"))
+        .stdout(predicate::str::contains("alice: 1"));
+}
+
+/// Config file turning desktop notifications off, for tail-mode tests
+fn no_notify_config() -> NamedTempFile {
+    let mut config_file = NamedTempFile::new().unwrap();
+    writeln!(config_file, "notify = false").unwrap();
+    config_file.flush().unwrap();
+    config_file
+}
+
+#[test]
+fn test_stdin_source() {
+    let config_file = no_notify_config();
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", "-", "--no-color", "--quiet", "--config"])
+        .arg(config_file.path())
+        .write_stdin("starting\nERROR: disk full\n");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR: disk full"))
+        .stdout(predicate::str::contains("Lines processed: 2"))
+        .stdout(predicate::str::contains("Matches found: 1"));
+}
+
+#[test]
+fn test_stdin_source_dry_run() {
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["-f", "-", "--dry-run", "--no-color"])
+        .write_stdin("WARN: slow\nok\nWARN: slower\n");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("WARN: 2 matches"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_source_reports_exit_status() {
+    let config_file = no_notify_config();
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--exec", "echo 'ERROR: job failed'; exit 3", "--no-color"])
+        .arg("--config")
+        .arg(config_file.path());
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR: job failed"))
+        .stdout(predicate::str::contains("Command exited with code 3"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_exit_is_a_json_event() {
+    let config_file = no_notify_config();
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--exec",
+        "echo 'ERROR: job failed'; exit 3",
+        "--output",
+        "json",
+    ])
+    .arg("--config")
+    .arg(config_file.path());
+
+    let output = cmd.assert().success().get_output().clone();
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Command exited"));
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let exited = events
+        .iter()
+        .find(|event| event["event"] == "command_exited")
+        .expect("a command_exited event");
+    assert_eq!(exited["exit_code"], 3);
+    assert_eq!(exited["file"], "echo");
+    assert_eq!(events.last().unwrap()["command_exit_code"], 3);
+}
+
+#[test]
+fn test_no_source_is_an_error() {
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--config", "/dev/null", "--pattern", "ERROR"]);
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--file or --exec"));
+}
+
+#[test]
+fn test_glob_and_directory_arguments() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::create_dir(dir.path().join("api")).unwrap();
+    std::fs::write(dir.path().join("app.log"), "ERROR in app\n").unwrap();
+    std::fs::write(dir.path().join("api/server.log"), "ERROR in api\n").unwrap();
+    std::fs::write(dir.path().join("notes.txt"), "ERROR in notes\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        dir.path().join("**/*.log").to_str().unwrap(),
+        "--dry-run",
+        "--pattern",
+        "ERROR",
+        "--no-color",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Files watched: 2"))
+        .stdout(predicate::str::contains("ERROR in app"))
+        .stdout(predicate::str::contains("ERROR in api"))
+        .stdout(predicate::str::contains("ERROR in notes").not());
+
+    // A directory stands for the files directly inside it
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        dir.path().to_str().unwrap(),
+        "--dry-run",
+        "--pattern",
+        "ERROR",
+        "--no-color",
+    ]);
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Files watched: 2"))
+        .stdout(predicate::str::contains("ERROR in notes"))
+        .stdout(predicate::str::contains("ERROR in api").not());
+}
+
+/// Tail `log` until interrupted, appending `line` once it is running
+#[cfg(unix)]
+fn tail_until_interrupted(log: &std::path::Path, state: &std::path::Path, line: &str) -> String {
+    use std::time::Duration;
+
+    let config_file = no_notify_config();
+    let child = std::process::Command::new(assert_cmd::cargo::cargo_bin("logwatcher"))
+        .args(["--no-color", "--file"])
+        .arg(log)
+        .arg("--state-file")
+        .arg(state)
+        .arg("--config")
+        .arg(config_file.path())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(500));
+    let mut file = std::fs::OpenOptions::new().append(true).open(log).unwrap();
+    writeln!(file, "{}", line).unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_state_file_resumes_after_restart() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let log = dir.path().join("app.log");
+    let state = dir.path().join("state.json");
+    std::fs::write(&log, "ERROR: before the first run\n").unwrap();
+
+    let first = tail_until_interrupted(&log, &state, "ERROR: seen by the first run");
+    assert!(first.contains("ERROR: seen by the first run"));
+    assert!(!first.contains("before the first run"));
+    assert!(state.exists());
+
+    // Written while logwatcher was stopped
+    let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+    writeln!(file, "ERROR: written while stopped").unwrap();
+
+    let second = tail_until_interrupted(&log, &state, "ERROR: seen by the second run");
+    assert!(second.contains("ERROR: written while stopped"));
+    assert!(second.contains("ERROR: seen by the second run"));
+    assert!(!second.contains("seen by the first run"));
+}
+
+#[test]
+fn test_multiline_stack_traces() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "2025-01-07 15:00:01 INFO Handling request").unwrap();
+    writeln!(temp_file, "2025-01-07 15:00:02 ERROR Unhandled exception").unwrap();
+    writeln!(temp_file, "Traceback (most recent call last):").unwrap();
+    writeln!(temp_file, "  File \"app.py\", line 3, in <module>").unwrap();
+    writeln!(temp_file, "ValueError: bad input").unwrap();
+    writeln!(temp_file, "2025-01-07 15:00:03 INFO Recovered").unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args([
+        "--file",
+        temp_file.path().to_str().unwrap(),
+        "--dry-run",
+        "--no-color",
+        "--pattern",
+        "ValueError",
+        "--multiline-start",
+        r"^\d{4}-\d{2}-\d{2} ",
+    ]);
+
+    // The match on the last line of the trace reports the whole record
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[DRY-RUN] 2025-01-07 15:00:02 ERROR Unhandled exception\nTraceback",
+        ))
+        .stdout(predicate::str::contains("Lines processed: 3"))
+        .stdout(predicate::str::contains("Matches found: 1"));
+}
+
+#[test]
+fn test_last_lines_and_since() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    for minute in 0..10 {
+        writeln!(
+            temp_file,
+            "2026-10-17T09:0{}:00Z ERROR tick {}",
+            minute, minute
+        )
+        .unwrap();
+    }
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--dry-run", "--no-color", "-n", "3"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR tick 6").not())
+        .stdout(predicate::str::contains("ERROR tick 7"))
+        .stdout(predicate::str::contains("Lines processed: 3"));
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--dry-run", "--no-color", "--since", "2026-10-17T09:05:00Z"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR tick 4").not())
+        .stdout(predicate::str::contains("ERROR tick 5"))
+        .stdout(predicate::str::contains("Lines processed: 5"));
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--since", "last tuesday"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --since"));
+}
+
+#[test]
+fn test_blank_lines_are_kept_unless_skipped() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    write!(temp_file, "ERROR first\r\n\n   \n  ERROR indented  \n").unwrap();
+    temp_file.flush().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--dry-run", "--no-color"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[DRY-RUN]   ERROR indented  \n"))
+        .stdout(predicate::str::contains("Lines processed: 4"));
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--dry-run", "--no-color", "--skip-blank"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Lines processed: 2"));
+}
+
+#[test]
+fn test_invalid_bytes_and_encodings() {
+    // Invalid UTF-8 and an unfinished last line on stdin
+    let config_file = no_notify_config();
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", "-", "--no-color", "--config"])
+        .arg(config_file.path())
+        .write_stdin(&b"ERROR \xff\xfe corrupt\nERROR: no newline"[..]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR \u{fffd}\u{fffd} corrupt"))
+        .stdout(predicate::str::contains("ERROR: no newline"))
+        .stdout(predicate::str::contains("Matches found: 2"));
+
+    let mut temp_file = NamedTempFile::new().unwrap();
+    temp_file
+        .write_all(b"ERROR: \xc9chec de connexion\n")
+        .unwrap();
+    temp_file.flush().unwrap();
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--dry-run", "--no-color", "--encoding", "latin1"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR: Échec de connexion"));
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--encoding", "ebcdic"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --encoding"));
+}
+
+#[test]
+fn test_include_rotated_reads_compressed_copies() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let log = dir.path().join("app.log");
+    std::fs::write(&log, "ERROR: current\n").unwrap();
+    std::fs::write(dir.path().join("app.log.1"), "ERROR: yesterday\n").unwrap();
+    let gzip = std::fs::File::create(dir.path().join("app.log.2.gz")).unwrap();
+    let mut encoder = flate2::write::GzEncoder::new(gzip, Default::default());
+    encoder.write_all(b"ERROR: last week\n").unwrap();
+    encoder.finish().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", log.to_str().unwrap()]).args([
+        "--dry-run",
+        "--no-color",
+        "--include-rotated",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::is_match("(?s)last week.*yesterday.*current").unwrap())
+        .stdout(predicate::str::contains("ERROR: 3 matches"));
+
+    // Compressed files given directly are read too
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", dir.path().join("app.log.2.gz").to_str().unwrap()])
+        .args(["--dry-run", "--no-color"]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR: last week"));
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", log.to_str().unwrap(), "--include-rotated"]);
+    cmd.assert().failure();
+}
+
+#[test]
+fn test_json_output() {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    writeln!(temp_file, "INFO: starting").unwrap();
+    writeln!(temp_file, "ERROR: disk full").unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--dry-run", "--output", "ndjson"]);
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let events: Vec<serde_json::Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let types: Vec<&str> = events
+        .iter()
+        .map(|event| event["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(types, vec!["start", "line", "dry_run_summary", "summary"]);
+    assert_eq!(events[0]["files_watched"], 1);
+    let line = &events[1];
+    assert_eq!(line["line"], "ERROR: disk full");
+    assert_eq!(line["offset"], 15);
+    assert_eq!(line["line_number"], 2);
+    assert_eq!(line["rules"], serde_json::json!(["ERROR"]));
+    assert_eq!(line["file"], temp_file.path().to_str().unwrap());
+    assert_eq!(events[2]["matches"]["ERROR"], 1);
+    assert_eq!(events[3]["lines_processed"], 2);
+}
+
+#[test]
+fn test_format_template() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let log = dir.path().join("app.log");
+    std::fs::write(&log, "INFO: starting\nERROR: disk full\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", log.to_str().unwrap()]).args([
+        "--dry-run",
+        "--no-color",
+        "--format",
+        "{file}:{lineno} [{rule:>7}] {line:.10}|",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("app.log:2 [  ERROR] ERROR: dis|"))
+        .stdout(predicate::str::contains("[DRY-RUN]").not());
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", log.to_str().unwrap()])
+        .args(["--dry-run", "--format", "{line|sparkly}"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid --format"));
+}
+
+#[test]
+fn test_output_file_with_rotation() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let log = dir.path().join("app.log");
+    let matches = dir.path().join("matches.log");
+    std::fs::write(
+        &log,
+        "ERROR: first\nINFO: fine\nERROR: second\nERROR: third\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", log.to_str().unwrap()])
+        .args(["--dry-run", "--quiet", "--no-color"])
+        .args(["--output-file", matches.to_str().unwrap()])
+        .args([
+            "--output-file-format",
+            "json",
+            "--output-file-max-size",
+            "1",
+        ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("ERROR: third"));
+
+    let lines = |path: &std::path::Path| -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let event: serde_json::Value = serde_json::from_str(line).unwrap();
+                event["line"].as_str().unwrap().to_string()
+            })
+            .collect()
+    };
+    // Every record is over the size limit, so each gets a file of its own
+    assert_eq!(
+        lines(&dir.path().join("matches.log.2")),
+        vec!["ERROR: first"]
+    );
+    assert_eq!(
+        lines(&dir.path().join("matches.log.1")),
+        vec!["ERROR: second"]
+    );
+    assert_eq!(lines(&matches), vec!["ERROR: third"]);
+}
+
+#[test]
+fn test_context_lines() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let log = dir.path().join("app.log");
+    std::fs::write(
+        &log,
+        "one\ntwo\nthree\nERROR: first\nfour\nfive\nsix\nseven\nERROR: second\neight\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", log.to_str().unwrap()]).args([
+        "--dry-run",
+        "--no-color",
+        "-B",
+        "2",
+        "-A",
+        "1",
+    ]);
+    cmd.assert().success().stdout(predicate::str::contains(
+        "two\nthree\n[DRY-RUN] ERROR: first\nfour\n--\nsix\nseven\n[DRY-RUN] ERROR: second\neight\n",
+    ));
+
+    // -A takes precedence over -C
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", log.to_str().unwrap()]).args([
+        "--dry-run",
+        "--no-color",
+        "-C",
+        "1",
+        "-A",
+        "0",
+    ]);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "three\n[DRY-RUN] ERROR: first\n--\nseven\n[DRY-RUN] ERROR: second\n",
+        ))
+        .stdout(predicate::str::contains("eight").not());
+}
+
+#[test]
+fn test_tui_needs_a_terminal() {
+    let temp_file = NamedTempFile::new().unwrap();
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap(), "--tui"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--tui needs a terminal"));
+
+    let mut cmd = Command::cargo_bin("logwatcher").unwrap();
+    cmd.args(["--file", temp_file.path().to_str().unwrap()])
+        .args(["--tui", "--dry-run"]);
+    cmd.assert().failure();
+}